@@ -39,6 +39,7 @@ enum ExternalGameboyEvent {
     Stop,
     Keyup(gameboy::joypad::Button),
     Keydown(gameboy::joypad::Button),
+    SwipeCard,
 }
 
 enum InternalGameboyEvent {
@@ -46,6 +47,37 @@ enum InternalGameboyEvent {
     AudioSamples([gameboy::spu::SampleFrame; SAMPLE_COUNT_PER_EVENT]),
 }
 
+/// Peripheral plugged on the link cable, selected with `UGBE_SERIAL` as `name[:argument]` (for example `printer` to
+/// plug a Game Boy Printer saving its prints to the current directory), nothing is plugged by default. The available
+/// peripherals are listed with `UGBE_SERIAL=help` or when the peripheral can't be plugged.
+fn serial_peripheral() -> Result<Option<Box<dyn gameboy::serial::Peripheral>>> {
+    let registry = gameboy::serial::registry::Registry::new();
+    let print_entries = || {
+        println!("Serial peripherals (UGBE_SERIAL):");
+        for entry in registry.entries() {
+            println!("    {}: {}", entry.name(), entry.description());
+        }
+    };
+
+    let spec = match std::env::var("UGBE_SERIAL") {
+        Ok(spec) => spec,
+        Err(_) => return Ok(None),
+    };
+
+    if spec == "help" {
+        print_entries();
+        return Ok(None);
+    }
+
+    match registry.create_from_spec(&spec) {
+        Ok(peripheral) => Ok(Some(peripheral)),
+        Err(err) => {
+            print_entries();
+            Err(err).context(format!("unable to plug the serial peripheral '{}'", spec))
+        }
+    }
+}
+
 fn main() -> Result<()> {
     let boot_rom = bootrom::BootRom::from_path(BOOT_ROM_PATH)
         .context(format!("unable to parse boom rom '{}'", BOOT_ROM_PATH))?;
@@ -150,10 +182,18 @@ fn main() -> Result<()> {
     let (sender_internal, receiver_internal) = crossbeam_channel::unbounded();
     let (sender_external, receiver_external) = crossbeam_channel::unbounded();
 
-    let gameboy = gameboy::GameboyBuilder::new(boot_rom, cartridge)
+    let gameboy_builder = gameboy::GameboyBuilder::new(boot_rom, cartridge)
         .set_screen_color_grayscale()
-        .set_screen_frame_blending(None)
-        .build();
+        .set_screen_frame_blending(None);
+
+    for warning in gameboy_builder.warnings() {
+        println!("Warning: {}", warning);
+    }
+
+    let mut gameboy = gameboy_builder.build();
+    if let Some(peripheral) = serial_peripheral()? {
+        gameboy.serial().connect(peripheral);
+    }
 
     let emulation_thread =
         { std::thread::spawn(|| run_emulation(gameboy, sender_internal, receiver_external)) };
@@ -171,6 +211,10 @@ fn main() -> Result<()> {
                     sender_external.send(ExternalGameboyEvent::Stop)?;
                     break 'running;
                 }
+                sdl2::event::Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::F1),
+                    ..
+                } => sender_external.send(ExternalGameboyEvent::SwipeCard)?,
                 sdl2::event::Event::ControllerDeviceAdded { which, .. } => {
                     if let Ok(controller) = game_controller_subsystem.open(which) {
                         println!("Successfully added controller '{}'", controller.name());
@@ -325,6 +369,8 @@ fn run_emulation(
 
     let mut before_emulation = gameboy.clock().now();
 
+    let mut printed_image_count = 0;
+
     let mut lag_duration = std::time::Duration::new(0, 0);
     let mut before_frame = std::time::Instant::now();
 
@@ -337,6 +383,15 @@ fn run_emulation(
                     ExternalGameboyEvent::Stop => break 'running,
                     ExternalGameboyEvent::Keyup(button) => gameboy.joypad().keyup(button),
                     ExternalGameboyEvent::Keydown(button) => gameboy.joypad().keydown(button),
+                    ExternalGameboyEvent::SwipeCard => {
+                        if let Some(card) = gameboy
+                            .serial()
+                            .peripheral_mut::<gameboy::serial::barcode::BarcodeBoy>()
+                            .and_then(|barcode_boy| barcode_boy.swipe_next())
+                        {
+                            println!("Card '{}' swiped", card.barcode());
+                        }
+                    }
                 }
             }
 
@@ -372,6 +427,30 @@ fn run_emulation(
             before_emulation.restart(gameboy.clock())
         };
 
+        // Save the images printed by the Game Boy Printer
+        if let Some(printer) = gameboy
+            .serial()
+            .peripheral_mut::<gameboy::serial::printer::Printer>()
+        {
+            for image in printer.take_printed_images() {
+                // Keep the images printed by the previous sessions
+                let path = loop {
+                    printed_image_count += 1;
+
+                    let path = format!("print-{}.png", printed_image_count);
+                    if !std::path::Path::new(&path).exists() {
+                        break path;
+                    }
+                };
+                match std::fs::File::create(&path).and_then(|mut file| image.write_png(&mut file)) {
+                    Ok(()) => println!("Printed image saved to '{}'", path),
+                    Err(err) => {
+                        println!("Warning: unable to save printed image '{}': {}", path, err)
+                    }
+                }
+            }
+        }
+
         // Send the new frame to the main thread
         {
             let frame_data = unsafe {
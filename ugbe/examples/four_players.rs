@@ -0,0 +1,47 @@
+//! Run four players connected with a Four Player Adapter (DMG-07) without any display.
+//!
+//! Usage: `cargo run --example four_players -- <rom> [frames]`
+
+use std::error::Error;
+
+use ugbe::cartridge::Cartridge;
+use ugbe::gameboy::link::four_player::{FourPlayerAdapter, PORT_COUNT};
+use ugbe::gameboy::{screen, GameboyBuilder};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1);
+    let rom_path = args.next().ok_or("missing ROM path")?;
+    let frame_count: usize = match args.next() {
+        Some(frames) => frames.parse()?,
+        None => 600,
+    };
+
+    let mut gameboys = Vec::new();
+    for _ in 0..PORT_COUNT {
+        let cartridge = Cartridge::from_rom_path(&rom_path)?;
+        gameboys.push(GameboyBuilder::new_without_boot_rom(cartridge).build());
+    }
+
+    let mut adapter = FourPlayerAdapter::new(gameboys)?;
+
+    let mut frames = 0;
+    while frames < frame_count {
+        let results = adapter.tick();
+
+        // Player 1 paces the emulation
+        if let Some((Some(screen::Event::VBlank), _)) = results[0] {
+            frames += 1;
+
+            if frames % 60 == 0 {
+                println!(
+                    "Frame {}: {:?} phase, connected players {:?}",
+                    frames,
+                    adapter.phase(),
+                    adapter.connected_players()
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
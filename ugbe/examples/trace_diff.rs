@@ -0,0 +1,90 @@
+//! Find the first instruction where two runs diverge, or record a run to compare it later with another version of
+//! ugbe.
+//!
+//! Usage:
+//! - `cargo run --example trace_diff -- record <rom> <trace> [instructions] [--doctor]`
+//! - `cargo run --example trace_diff -- compare <rom|trace> <rom|trace> [instructions] [--doctor]`
+//!
+//! The files with the `.gb`, `.gbc` or `.sgb` extension are run without a boot ROM, the other files are read as
+//! traces, like the ones recorded by this tool or the Gameboy Doctor logs. With `--doctor`, the ROMs are run with
+//! LY always read as 0x90, as expected by the Gameboy Doctor logs.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use ugbe::cartridge::Cartridge;
+use ugbe::debug::diff::{find_divergence, GameboySource, Source, TraceReader};
+use ugbe::gameboy::GameboyBuilder;
+
+/// Instructions shown before and after the divergence
+const CONTEXT: usize = 8;
+
+fn open(path: &str, doctor_mode: bool) -> Result<Box<dyn Source>, Box<dyn Error>> {
+    let is_rom = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| ["gb", "gbc", "sgb"].contains(&extension.to_lowercase().as_str()));
+
+    if is_rom {
+        let cartridge = Cartridge::from_rom_path(path)?;
+        let gameboy = GameboyBuilder::new_without_boot_rom(cartridge).build();
+        Ok(Box::new(
+            GameboySource::new(gameboy).set_doctor_mode(doctor_mode),
+        ))
+    } else {
+        Ok(Box::new(TraceReader::open(path)?))
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let (flags, args): (Vec<String>, Vec<String>) = std::env::args()
+        .skip(1)
+        .partition(|arg| arg.starts_with("--"));
+    let doctor_mode = match flags.as_slice() {
+        [] => false,
+        [flag] if flag == "--doctor" => true,
+        _ => return Err(format!("unknown flags {:?}", flags).into()),
+    };
+    let mut args = args.into_iter();
+    let command = args
+        .next()
+        .ok_or("missing command, 'record' or 'compare'")?;
+    let first = args.next().ok_or("missing ROM or trace path")?;
+    let second = args.next().ok_or("missing ROM or trace path")?;
+    let max_steps: usize = match args.next() {
+        Some(instructions) => instructions.parse()?,
+        None => 1_000_000,
+    };
+
+    match command.as_str() {
+        "record" => {
+            let mut source = open(&first, doctor_mode)?;
+            let mut trace = BufWriter::new(File::create(&second)?);
+
+            for _ in 0..max_steps {
+                match source.next_step()? {
+                    Some(step) => writeln!(trace, "{}", step)?,
+                    None => break,
+                }
+            }
+            trace.flush()?;
+        }
+        "compare" => {
+            let mut left = open(&first, doctor_mode)?;
+            let mut right = open(&second, doctor_mode)?;
+
+            match find_divergence(left.as_mut(), right.as_mut(), max_steps, CONTEXT)? {
+                Some(divergence) => {
+                    print!("{}", divergence);
+                    std::process::exit(1);
+                }
+                None => println!("No divergence found"),
+            }
+        }
+        _ => return Err(format!("unknown command '{}'", command).into()),
+    }
+
+    Ok(())
+}
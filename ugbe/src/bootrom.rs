@@ -3,17 +3,32 @@ use std::{fs, io, path::Path};
 
 use thiserror::Error;
 
+use crate::gameboy::Model;
+
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("file have an invalid size (got '{0}', expected '{1}')")]
-    InvalidSize(usize, usize),
+    #[error("file have an invalid size (got '{0}', expected '256' or '2304')")]
+    InvalidSize(usize),
 
     #[error("failed to read the file")]
     ReadError(#[from] io::Error),
 }
 
+/// CRC32 of the known boot ROM dumps
+const KNOWN_BOOT_ROMS: [(u32, Model); 7] = [
+    (0xC2F5CC97, Model::DMG0),
+    (0x59C8598E, Model::DMG),
+    (0xE6920754, Model::MGB),
+    (0xEC8A83B9, Model::SGB),
+    (0x53D0DD63, Model::SGB2),
+    (0x41884E46, Model::CGB),
+    (0xFFD6B0F1, Model::AGB),
+];
+
+/// Boot ROM of a DMG-like model (0x100 bytes) or of a CGB-like model (0x900 bytes), the CGB boot ROM is split in
+/// two parts with a hole in 0x100-0x1FF where the cartridge header is visible.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct BootRom([u8; 0x100]);
+pub struct BootRom(Box<[u8]>);
 
 impl BootRom {
     pub fn from_path<P: ?Sized + AsRef<Path>>(path: &P) -> Result<Self, Error> {
@@ -23,19 +38,42 @@ impl BootRom {
 
         io::Read::read_to_end(&mut reader, &mut buffer)?;
 
-        if buffer.len() != 0x100 {
-            return Err(Error::InvalidSize(buffer.len(), 0x100));
+        Self::from_bytes(&buffer)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != 0x100 && bytes.len() != 0x900 {
+            return Err(Error::InvalidSize(bytes.len()));
         }
 
-        // We unwrap as we already checked the size before
-        Ok(Self(buffer.try_into().unwrap()))
+        Ok(Self(bytes.into()))
+    }
+
+    pub fn size(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check if the address is mapped to the boot ROM when it is enabled
+    pub fn is_mapped(&self, address: u16) -> bool {
+        let address = address as usize;
+        address < self.0.len() && !(0x100..0x200).contains(&address)
+    }
+
+    /// Identify the model this boot ROM has been dumped from, `None` if this isn't a known boot ROM
+    pub fn model(&self) -> Option<Model> {
+        let crc = crate::crc::crc32(&self.0);
+
+        KNOWN_BOOT_ROMS
+            .iter()
+            .find(|(known_crc, _)| *known_crc == crc)
+            .map(|(_, model)| *model)
     }
 }
 
-impl Index<u8> for BootRom {
+impl Index<u16> for BootRom {
     type Output = u8;
 
-    fn index(&self, index: u8) -> &Self::Output {
+    fn index(&self, index: u16) -> &Self::Output {
         &self.0[index as usize]
     }
 }
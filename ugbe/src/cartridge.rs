@@ -906,18 +906,18 @@ impl Cartridge {
 
         io::Read::read_to_end(&mut rom_reader, &mut rom_buffer)?;
 
-        let header = Header::from_rom(&rom_buffer)?;
+        Self::from_rom(rom_buffer)
+    }
+
+    pub fn from_rom(rom: Vec<u8>) -> Result<Self, Error> {
+        let header = Header::from_rom(&rom)?;
 
         let ram = match &header.ram_size.0 {
             0 => None,
             size => Some(vec![0; *size]),
         };
 
-        Ok(Self {
-            header,
-            rom: rom_buffer,
-            ram,
-        })
+        Ok(Self { header, rom, ram })
     }
 
     pub fn header(&self) -> &Header {
@@ -928,17 +928,15 @@ impl Cartridge {
         &self.rom
     }
 
+    pub(crate) fn mut_rom(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+
     pub fn ram(&self) -> Option<&[u8]> {
-        match &self.ram {
-            Some(ram) => Some(ram),
-            None => todo!(),
-        }
+        self.ram.as_deref()
     }
 
     pub fn mut_ram(&mut self) -> Option<&mut [u8]> {
-        match &mut self.ram {
-            Some(ram) => Some(ram),
-            None => todo!(),
-        }
+        self.ram.as_deref_mut()
     }
 }
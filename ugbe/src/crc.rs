@@ -0,0 +1,18 @@
+/// CRC32 (IEEE 802.3) as used by zip and PNG
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    !crc32_update(0xFFFFFFFF, bytes)
+}
+
+/// Update a running CRC32, the initial value is 0xFFFFFFFF and the final result has to be inverted
+pub(crate) fn crc32_update(mut crc: u32, bytes: &[u8]) -> u32 {
+    for byte in bytes {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+
+    crc
+}
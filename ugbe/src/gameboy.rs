@@ -3,33 +3,80 @@ mod cartridge;
 pub mod clock;
 mod components;
 mod cpu;
+pub mod debug;
+pub mod infrared;
 mod interrupt;
 pub mod joypad;
+pub mod link;
 mod mmu;
+mod model;
 mod ppu;
+pub mod serial;
+mod sgb;
 pub mod spu;
+#[cfg(test)]
+mod tests;
 mod timer;
 mod wram;
 
+pub use model::Model;
 pub use ppu::screen;
 
+use thiserror::Error;
+
+/// Non fatal issues detected while building a Gameboy, the Gameboy can still be built but it may not behave as
+/// the selected model
+#[derive(Error, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Warning {
+    #[error("boot ROM has an unexpected size for the model {model:?} (got '{size}', expected '{expected}')")]
+    BootRomSizeMismatch {
+        model: Model,
+        size: usize,
+        expected: usize,
+    },
+
+    #[error(
+        "boot ROM has been dumped from a {boot_rom_model:?} but the selected model is {model:?}"
+    )]
+    BootRomModelMismatch { model: Model, boot_rom_model: Model },
+}
+
 pub struct GameboyBuilder {
-    boot_rom: crate::bootrom::BootRom,
+    boot_rom: Option<crate::bootrom::BootRom>,
     cartridge: crate::cartridge::Cartridge,
+    model: Model,
     screen_config: screen::Config,
+    ppu_memory_locking: bool,
 }
 
 impl GameboyBuilder {
-    // TODO: Remove the requirements of the boot rom and allow to construct a Gameboy without it
     // TODO: Check that the cartridge is ok (for example if CGB is required we won't be able to boot the game
     pub fn new(boot_rom: crate::bootrom::BootRom, cartridge: crate::cartridge::Cartridge) -> Self {
         Self {
-            boot_rom,
+            boot_rom: Some(boot_rom),
+            cartridge,
+            model: Model::default(),
+            screen_config: screen::Config::default(),
+            ppu_memory_locking: true,
+        }
+    }
+
+    /// Without a boot ROM the Gameboy directly starts the cartridge, with the registers initialized to the values
+    /// the boot ROM of the selected model would have left.
+    pub fn new_without_boot_rom(cartridge: crate::cartridge::Cartridge) -> Self {
+        Self {
+            boot_rom: None,
             cartridge,
+            model: Model::default(),
             screen_config: screen::Config::default(),
+            ppu_memory_locking: true,
         }
     }
 
+    pub fn set_model(self, model: Model) -> Self {
+        Self { model, ..self }
+    }
+
     pub fn set_screen_config(self, screen_config: screen::Config) -> Self {
         Self {
             screen_config,
@@ -51,28 +98,93 @@ impl GameboyBuilder {
         self
     }
 
-    pub fn build(self) -> Gameboy {
-        Gameboy {
+    /// Disabling the PPU memory locking allows the CPU to access VRAM and OAM whatever the PPU mode is, this is
+    /// not accurate but can be useful to inspect the memory while debugging.
+    pub fn set_ppu_memory_locking(self, ppu_memory_locking: bool) -> Self {
+        Self {
+            ppu_memory_locking,
+            ..self
+        }
+    }
+
+    /// Check that the configuration is consistent, like the boot ROM matching the selected model
+    pub fn warnings(&self) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+
+        if let Some(boot_rom) = &self.boot_rom {
+            if boot_rom.size() != self.model.boot_rom_size() {
+                warnings.push(Warning::BootRomSizeMismatch {
+                    model: self.model,
+                    size: boot_rom.size(),
+                    expected: self.model.boot_rom_size(),
+                });
+            }
+
+            match boot_rom.model() {
+                Some(boot_rom_model) if boot_rom_model != self.model => {
+                    warnings.push(Warning::BootRomModelMismatch {
+                        model: self.model,
+                        boot_rom_model,
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        warnings
+    }
+
+    pub fn build(mut self) -> Gameboy {
+        self.screen_config
+            .set_default_color_palette(self.model.color_palette());
+
+        let cpu = match self.boot_rom {
+            Some(_) => cpu::Cpu::new(),
+            None => cpu::Cpu::new_post_boot(self.model, self.cartridge.header().checksum),
+        };
+
+        // The SGB only listens to the commands of the cartridges declaring their support in the header
+        let joypad = if self.model.is_sgb()
+            && self.cartridge.header().sgb_suppport == crate::cartridge::SGBSupport::Supported
+        {
+            joypad::Joypad::new_sgb()
+        } else {
+            joypad::Joypad::new()
+        };
+
+        let mut gameboy = Gameboy {
             mmu: mmu::MMU::new(),
             boot_rom: self.boot_rom,
             cartridge: self.cartridge.into(),
-            joypad: joypad::Joypad::new(),
-            ppu: ppu::PPU::new(self.screen_config),
-            spu: spu::Spu::new(),
-            cpu: cpu::Cpu::new(),
+            joypad,
+            ppu: ppu::PPU::new(self.screen_config, self.model, self.ppu_memory_locking),
+            spu: spu::Spu::new(self.model),
+            cpu,
             bus: bus::Bus::new(),
             interrupt: interrupt::Interrupt::new(),
             work_ram: wram::WorkRam::new(),
             high_ram: wram::WorkRam::new(),
             timer: timer::Timer::new(),
+            serial: serial::Serial::new(),
+            infrared: infrared::Infrared::new(self.model.is_cgb()),
             clock: clock::Clock::new(),
+            model: self.model,
+            last_memory_operation: None,
+            last_screen_event: None,
+            tracer: None,
+        };
+
+        if gameboy.boot_rom.is_none() {
+            gameboy.initialize_post_boot_io();
         }
+
+        gameboy
     }
 }
 
 pub struct Gameboy {
     mmu: mmu::MMU,
-    boot_rom: crate::bootrom::BootRom,
+    boot_rom: Option<crate::bootrom::BootRom>,
     cartridge: cartridge::Cartridge,
     joypad: joypad::Joypad,
     ppu: ppu::PPU,
@@ -83,13 +195,29 @@ pub struct Gameboy {
     work_ram: wram::WorkRam<0x2000>,
     high_ram: wram::WorkRam<0x7F>,
     timer: timer::Timer,
+    serial: serial::Serial,
+    infrared: infrared::Infrared,
     clock: clock::Clock,
+    model: Model,
+    /// Memory operation of the last tick, `None` when it wasn't a M-cycle
+    last_memory_operation: Option<bus::MemoryOperation>,
+    /// Screen event of the last tick
+    last_screen_event: Option<screen::Event>,
+    tracer: Option<debug::trace::Tracer>,
 }
 
 impl Gameboy {
     pub fn tick(&mut self) -> (Option<screen::Event>, Option<spu::SampleFrame>) {
+        self.last_memory_operation = None;
+
         if self.clock.is_m_cycle() {
             let memory_operation = self.cpu.tick(&self.bus, &mut self.interrupt);
+            self.last_memory_operation = Some(memory_operation);
+
+            if let Some((address, registers)) = self.cpu.take_traced_instruction() {
+                self.trace_instruction(address, registers);
+            }
+
             self.bus.tick(
                 memory_operation,
                 &mut self.mmu,
@@ -98,19 +226,30 @@ impl Gameboy {
                     ppu: &mut self.ppu,
                     spu: &mut self.spu,
                     timer: &mut self.timer,
+                    serial: &mut self.serial,
+                    infrared: &mut self.infrared,
                     interrupt: &mut self.interrupt,
-                    boot_rom: &mut self.boot_rom,
+                    boot_rom: &self.boot_rom,
                     cartridge: &mut self.cartridge,
                     work_ram: &mut self.work_ram,
                     high_ram: &mut self.high_ram,
                 },
             );
+
+            if let Some(command) = self.joypad.take_sgb_command() {
+                self.ppu.sgb_command(&command);
+            }
         }
 
         let screen_event = self.ppu.tick(&mut self.interrupt);
+        self.last_screen_event = screen_event;
 
         self.timer.tick(&mut self.interrupt);
 
+        self.serial.tick(&mut self.interrupt);
+
+        self.infrared.tick();
+
         self.spu.tick(&self.timer);
 
         let sample_frame = if self.clock.is_apu_cycle() {
@@ -126,15 +265,267 @@ impl Gameboy {
         (screen_event, sample_frame)
     }
 
+    /// Set the I/O registers to the values left by the boot ROM and unmap it
+    fn initialize_post_boot_io(&mut self) {
+        let mut ctx = components::MMUContext {
+            joypad: &mut self.joypad,
+            ppu: &mut self.ppu,
+            spu: &mut self.spu,
+            timer: &mut self.timer,
+            serial: &mut self.serial,
+            infrared: &mut self.infrared,
+            interrupt: &mut self.interrupt,
+            boot_rom: &self.boot_rom,
+            cartridge: &mut self.cartridge,
+            work_ram: &mut self.work_ram,
+            high_ram: &mut self.high_ram,
+        };
+
+        let registers: &[(u16, u8)] = &[
+            (0xFF26, 0x80), // NR52
+            (0xFF11, 0x80), // NR11
+            (0xFF12, 0xF3), // NR12
+            (0xFF25, 0xF3), // NR51
+            (0xFF24, 0x77), // NR50
+            (0xFF47, 0xFC), // BGP
+            (0xFF40, 0x91), // LCDC
+            (0xFF0F, 0x01), // IF
+            (0xFF50, 0x01), // Boot ROM unmapping
+        ];
+
+        for (address, value) in registers {
+            components::Mmu::write_byte(&mut self.mmu, &mut ctx, *address, *value);
+        }
+    }
+
+    /// Read a byte as the CPU would, without the side effects of the memory operations
+    fn read_byte(&self, address: u16) -> u8 {
+        let ctx = components::MMUReadContext {
+            joypad: &self.joypad,
+            ppu: &self.ppu,
+            spu: &self.spu,
+            timer: &self.timer,
+            serial: &self.serial,
+            infrared: &self.infrared,
+            interrupt: &self.interrupt,
+            boot_rom: &self.boot_rom,
+            cartridge: &self.cartridge,
+            work_ram: &self.work_ram,
+            high_ram: &self.high_ram,
+        };
+
+        components::Mmu::read_byte(&self.mmu, &ctx, address)
+    }
+
+    /// Trace the instruction decoded by the CPU, before the memory operation of this M-cycle is performed
+    fn trace_instruction(&mut self, address: u16, registers: cpu::registers::Registers) {
+        let mut pcmem = [0; 4];
+        for (offset, byte) in pcmem.iter_mut().enumerate() {
+            *byte = self.read_byte(address.wrapping_add(offset as u16));
+        }
+
+        let entry = debug::trace::Entry {
+            a: registers.a(),
+            f: registers.f(),
+            b: registers.b(),
+            c: registers.c(),
+            d: registers.d(),
+            e: registers.e(),
+            h: registers.h(),
+            l: registers.l(),
+            sp: registers.sp(),
+            pc: address,
+            pcmem,
+            bank: self.bank(address),
+            t_cycle: self.clock.t_cycle_count(),
+            label: None,
+        };
+
+        if let Some(tracer) = &mut self.tracer {
+            tracer.trace(entry);
+        }
+    }
+
+    /// Start writing a line per executed instruction, return the previous tracer
+    pub fn start_trace(&mut self, tracer: debug::trace::Tracer) -> Option<debug::trace::Tracer> {
+        self.cpu.set_tracing(true);
+        self.tracer.replace(tracer)
+    }
+
+    pub fn stop_trace(&mut self) -> Option<debug::trace::Tracer> {
+        self.cpu.set_tracing(false);
+        self.tracer.take()
+    }
+
+    pub fn tracer(&self) -> Option<&debug::trace::Tracer> {
+        self.tracer.as_ref()
+    }
+
+    pub fn tracer_mut(&mut self) -> Option<&mut debug::trace::Tracer> {
+        self.tracer.as_mut()
+    }
+
+    /// Inspect the memory without the side effects of the CPU accesses
+    pub fn memory(&self) -> debug::memory::Memory<&Self> {
+        debug::memory::Memory::new(self)
+    }
+
+    /// Inspect and patch the memory without the side effects of the CPU accesses
+    pub fn memory_mut(&mut self) -> debug::memory::Memory<&mut Self> {
+        debug::memory::Memory::new(self)
+    }
+
+    pub fn cpu_state(&self) -> debug::cpu::CpuState {
+        debug::cpu::CpuState::new(
+            self.cpu.registers(),
+            self.cpu.ime(),
+            self.cpu.is_halted(),
+            self.cpu.at_instruction_boundary(),
+        )
+    }
+
+    /// Restore the registers, IME and the halt state, best done between two instructions
+    pub fn set_cpu_state(&mut self, state: &debug::cpu::CpuState) {
+        let registers = self.cpu.mut_registers();
+        registers.set_a(state.a);
+        registers.set_f(state.f);
+        registers.set_b(state.b);
+        registers.set_c(state.c);
+        registers.set_d(state.d);
+        registers.set_e(state.e);
+        registers.set_h(state.h);
+        registers.set_l(state.l);
+        registers.set_sp(state.sp);
+
+        self.cpu.set_pc(state.pc);
+        self.cpu.set_ime(state.ime);
+        self.cpu.set_halted(state.halted);
+    }
+
+    /// Jump to the given address, the instruction being fetched is fetched again from there
+    pub fn set_pc(&mut self, pc: u16) {
+        self.cpu.set_pc(pc);
+    }
+
+    pub fn set_register(&mut self, register: debug::cpu::Register, value: u16) {
+        match register {
+            debug::cpu::Register::PC => self.cpu.set_pc(value),
+            _ => debug::cpu::set_register(self.cpu.mut_registers(), register, value),
+        }
+    }
+
+    pub fn set_ime(&mut self, ime: bool) {
+        self.cpu.set_ime(ime);
+    }
+
+    /// Halt the CPU between two instructions, or wake it up without dispatching an interrupt
+    pub fn set_halted(&mut self, halted: bool) {
+        self.cpu.set_halted(halted);
+    }
+
+    /// Decoded value of the I/O registers, read without side effects
+    pub fn io_registers(&self) -> debug::io::IoRegisters {
+        debug::io::IoRegisters::read(&self.spu, |address| self.read_byte(address))
+    }
+
+    /// Write an I/O register as the CPU would, with the side effects of the write
+    pub fn write_io_register(
+        &mut self,
+        address: u16,
+        value: u8,
+    ) -> Result<(), debug::memory::Error> {
+        if !matches!(address, 0xFF00..=0xFF7F | 0xFFFF) {
+            return Err(debug::memory::Error::Unmapped(address));
+        }
+
+        let mut ctx = components::MMUContext {
+            joypad: &mut self.joypad,
+            ppu: &mut self.ppu,
+            spu: &mut self.spu,
+            timer: &mut self.timer,
+            serial: &mut self.serial,
+            infrared: &mut self.infrared,
+            interrupt: &mut self.interrupt,
+            boot_rom: &self.boot_rom,
+            cartridge: &mut self.cartridge,
+            work_ram: &mut self.work_ram,
+            high_ram: &mut self.high_ram,
+        };
+
+        components::Mmu::write_byte(&mut self.mmu, &mut ctx, address, value);
+        Ok(())
+    }
+
+    /// Render the tiles, the tile maps and the sprites held by the VRAM and the OAM
+    pub fn video(&self) -> debug::video::Video<'_> {
+        debug::video::Video::new(self)
+    }
+
+    /// Check if the boot ROM is mapped over the cartridge at the given address
+    fn is_boot_rom_mapped(&self, address: u16) -> bool {
+        self.mmu.is_boot_rom_enabled()
+            && self
+                .boot_rom
+                .as_ref()
+                .is_some_and(|boot_rom| boot_rom.is_mapped(address))
+    }
+
+    /// Bank mapped at the given address, 0 for the areas without banking
+    pub fn bank(&self, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => self.cartridge.rom_bank_0(),
+            0x4000..=0x7FFF => self.cartridge.rom_bank_n(),
+            0xA000..=0xBFFF => self.cartridge.ram_bank(),
+            // The work RAM bank 1 is always mapped, as on the DMG
+            0xD000..=0xDFFF => 1,
+            _ => 0,
+        }
+    }
+
     pub fn clock(&self) -> &clock::Clock {
         &self.clock
     }
 
+    pub fn serial(&mut self) -> &mut serial::Serial {
+        &mut self.serial
+    }
+
+    /// Exchange a bit on the link cable, return the bit output before shifting
+    pub(crate) fn serial_shift(&mut self, incoming: u8) -> u8 {
+        let outgoing = self.serial.output_bit();
+        self.serial.shift(incoming, &mut self.interrupt);
+        outgoing
+    }
+
+    pub fn infrared(&mut self) -> &mut infrared::Infrared {
+        &mut self.infrared
+    }
+
+    /// Exchange a whole byte on the link cable when clocked by another device, return the byte output
+    pub(crate) fn serial_exchange(&mut self, incoming: u8) -> u8 {
+        (0..8).rev().fold(0, |outgoing, bit| {
+            (outgoing << 1) | self.serial_shift((incoming >> bit) & 0b1)
+        })
+    }
+
     pub fn joypad(&mut self) -> &mut joypad::Joypad {
         &mut self.joypad
     }
 
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
     pub fn screen(&self) -> &screen::Screen {
         self.ppu.screen()
     }
+
+    /// Screen including the border drawn by the SGB, only available when emulating a SGB
+    pub fn sgb_screen(&self) -> Option<&screen::SgbScreen> {
+        self.ppu.sgb_screen()
+    }
+
+    pub fn set_ppu_memory_locking(&mut self, ppu_memory_locking: bool) {
+        self.ppu.set_memory_locking(ppu_memory_locking);
+    }
 }
@@ -1,10 +1,33 @@
 use super::components::Mmu;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ReadKind {
+    /// First byte of an instruction
+    Opcode,
+    /// Bytes following the opcode, including the second byte of the CB prefixed instructions
+    Operand,
+    Data,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum MemoryOperation {
     None,
-    Read { address: u16 },
-    Write { address: u16, value: u8 },
+    Read {
+        address: u16,
+        kind: ReadKind,
+    },
+    Write {
+        address: u16,
+        value: u8,
+    },
+    /// The IDU is incrementing or decrementing a register, its value is put on the address bus without any access
+    IncDec {
+        address: u16,
+    },
+    /// Read at an address coming from a register that the IDU is incrementing or decrementing at the same time
+    ReadIncDec {
+        address: u16,
+    },
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -29,8 +52,25 @@ impl Bus {
     ) {
         match memory_operation {
             MemoryOperation::None => {}
-            MemoryOperation::Read { address } => self.data = mmu.read_byte(mmu_ctx, address),
-            MemoryOperation::Write { address, value } => mmu.write_byte(mmu_ctx, address, value),
+            MemoryOperation::Read { address, .. } => {
+                mmu_ctx.ppu.oam_bug(address, super::ppu::OamBugAccess::Read);
+                self.data = mmu.read_byte(&mmu_ctx.as_read(), address)
+            }
+            MemoryOperation::Write { address, value } => {
+                mmu_ctx
+                    .ppu
+                    .oam_bug(address, super::ppu::OamBugAccess::Write);
+                mmu.write_byte(mmu_ctx, address, value)
+            }
+            MemoryOperation::IncDec { address } => mmu_ctx
+                .ppu
+                .oam_bug(address, super::ppu::OamBugAccess::Write),
+            MemoryOperation::ReadIncDec { address } => {
+                mmu_ctx
+                    .ppu
+                    .oam_bug(address, super::ppu::OamBugAccess::ReadIncDec);
+                self.data = mmu.read_byte(&mmu_ctx.as_read(), address)
+            }
         }
     }
 }
@@ -15,6 +15,23 @@ impl std::fmt::Debug for Cartridge {
 }
 
 impl Cartridge {
+    pub fn rom(&self) -> &[u8] {
+        self.cartridge.rom()
+    }
+
+    /// ROM content, to patch it without going through the MBC
+    pub fn mut_rom(&mut self) -> &mut [u8] {
+        self.cartridge.mut_rom()
+    }
+
+    pub fn ram(&self) -> Option<&[u8]> {
+        self.cartridge.ram()
+    }
+
+    pub fn mut_ram(&mut self) -> Option<&mut [u8]> {
+        self.cartridge.mut_ram()
+    }
+
     pub fn read_rom_bank_0(&self, address: u16) -> u8 {
         self.mbc.read_rom_bank_0(self.cartridge.rom(), address)
     }
@@ -27,6 +44,21 @@ impl Cartridge {
         self.mbc.write_rom(self.cartridge.rom(), address, value)
     }
 
+    pub fn rom_bank_0(&self) -> usize {
+        self.mbc.rom_bank_0(self.cartridge.rom())
+    }
+
+    pub fn rom_bank_n(&self) -> usize {
+        self.mbc.rom_bank_n(self.cartridge.rom())
+    }
+
+    pub fn ram_bank(&self) -> usize {
+        match self.cartridge.ram() {
+            Some(ram) => self.mbc.ram_bank(ram),
+            None => 0,
+        }
+    }
+
     pub fn read_ram(&self, address: u16) -> u8 {
         match self.cartridge.ram() {
             Some(ram) => self.mbc.read_ram(ram, address),
@@ -16,6 +16,11 @@ pub trait MBC {
     fn read_rom_bank_n(&self, rom: &[u8], address: u16) -> u8;
     fn write_rom(&mut self, rom: &[u8], address: u16, value: u8);
 
+    /// Banks currently mapped in the two ROM areas and the RAM area
+    fn rom_bank_0(&self, rom: &[u8]) -> usize;
+    fn rom_bank_n(&self, rom: &[u8]) -> usize;
+    fn ram_bank(&self, ram: &[u8]) -> usize;
+
     fn read_ram(&self, ram: &[u8], address: u16) -> u8;
     fn write_ram(&self, ram: &mut [u8], address: u16, value: u8);
 
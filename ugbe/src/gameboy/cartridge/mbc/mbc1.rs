@@ -126,6 +126,26 @@ impl super::MBC for MBC {
         }
     }
 
+    fn rom_bank_0(&self, rom: &[u8]) -> usize {
+        if self.mode {
+            self.zero_bank_n(rom)
+        } else {
+            0
+        }
+    }
+
+    fn rom_bank_n(&self, rom: &[u8]) -> usize {
+        self.high_bank_n(rom)
+    }
+
+    fn ram_bank(&self, ram: &[u8]) -> usize {
+        if self.mode && ram.len() == 32 * 1024 {
+            self.ram_bank_n as usize
+        } else {
+            0
+        }
+    }
+
     fn write_rom(&mut self, rom: &[u8], address: u16, value: u8) {
         match address {
             0x0000..=0x1FFF => {
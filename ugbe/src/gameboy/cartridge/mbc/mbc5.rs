@@ -95,6 +95,18 @@ impl super::MBC for MBC {
         }
     }
 
+    fn rom_bank_0(&self, _: &[u8]) -> usize {
+        0
+    }
+
+    fn rom_bank_n(&self, _: &[u8]) -> usize {
+        self.rom_bank_n as usize
+    }
+
+    fn ram_bank(&self, _: &[u8]) -> usize {
+        self.ram_bank_n as usize
+    }
+
     fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
         let ram_bank_n = self.ram_bank_n as usize;
         let idx = address as usize + 0x2000 * ram_bank_n;
@@ -50,6 +50,18 @@ impl super::MBC for MBC {
 
     fn write_rom(&mut self, _: &[u8], _: u16, _: u8) {}
 
+    fn rom_bank_0(&self, _: &[u8]) -> usize {
+        0
+    }
+
+    fn rom_bank_n(&self, _: &[u8]) -> usize {
+        1
+    }
+
+    fn ram_bank(&self, _: &[u8]) -> usize {
+        0
+    }
+
     fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
         let idx = address as usize;
 
@@ -15,6 +15,11 @@ impl Clock {
         self.t_cycle_count = self.t_cycle_count.wrapping_add(1);
     }
 
+    /// T-cycles elapsed since the Gameboy started, wrapping around
+    pub fn t_cycle_count(&self) -> usize {
+        self.t_cycle_count
+    }
+
     pub fn is_m_cycle(&self) -> bool {
         self.t_cycle_count % 4 == 0
     }
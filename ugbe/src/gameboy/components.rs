@@ -11,7 +11,6 @@ pub trait InterruptLine {
     fn highest_priority(&self) -> Option<InterruptKind>;
     fn ack(&mut self, kind: InterruptKind);
     fn request(&mut self, kind: InterruptKind);
-    fn flags_not_empty(&self) -> bool;
 }
 
 #[derive(Debug)]
@@ -20,17 +19,53 @@ pub struct MMUContext<'components> {
     pub ppu: &'components mut super::ppu::PPU,
     pub spu: &'components mut super::spu::Spu,
     pub timer: &'components mut super::timer::Timer,
+    pub serial: &'components mut super::serial::Serial,
+    pub infrared: &'components mut super::infrared::Infrared,
     pub interrupt: &'components mut super::interrupt::Interrupt,
-    pub boot_rom: &'components mut crate::bootrom::BootRom,
+    pub boot_rom: &'components Option<crate::bootrom::BootRom>,
     pub cartridge: &'components mut super::cartridge::Cartridge,
     pub work_ram: &'components mut super::wram::WorkRam<0x2000>,
     pub high_ram: &'components mut super::wram::WorkRam<0x7F>,
 }
 
+impl MMUContext<'_> {
+    pub fn as_read(&self) -> MMUReadContext<'_> {
+        MMUReadContext {
+            joypad: self.joypad,
+            ppu: self.ppu,
+            spu: self.spu,
+            timer: self.timer,
+            serial: self.serial,
+            infrared: self.infrared,
+            interrupt: self.interrupt,
+            boot_rom: self.boot_rom,
+            cartridge: self.cartridge,
+            work_ram: self.work_ram,
+            high_ram: self.high_ram,
+        }
+    }
+}
+
+/// Shared access to the components, reading the memory doesn't need more
+#[derive(Debug, Clone, Copy)]
+pub struct MMUReadContext<'components> {
+    pub joypad: &'components super::joypad::Joypad,
+    pub ppu: &'components super::ppu::PPU,
+    pub spu: &'components super::spu::Spu,
+    pub timer: &'components super::timer::Timer,
+    pub serial: &'components super::serial::Serial,
+    pub infrared: &'components super::infrared::Infrared,
+    pub interrupt: &'components super::interrupt::Interrupt,
+    pub boot_rom: &'components Option<crate::bootrom::BootRom>,
+    pub cartridge: &'components super::cartridge::Cartridge,
+    pub work_ram: &'components super::wram::WorkRam<0x2000>,
+    pub high_ram: &'components super::wram::WorkRam<0x7F>,
+}
+
 pub trait Mmu {
-    fn read_byte(&self, ctx: &MMUContext, address: u16) -> u8;
+    fn read_byte(&self, ctx: &MMUReadContext, address: u16) -> u8;
 
-    fn read_word(&self, ctx: &MMUContext, address: u16) -> u16 {
+    fn read_word(&self, ctx: &MMUReadContext, address: u16) -> u16 {
         u16::from_le_bytes([
             self.read_byte(ctx, address),
             self.read_byte(ctx, address.wrapping_add(1)),
@@ -1,10 +1,10 @@
 use super::{
-    bus::{Bus, MemoryOperation},
+    bus::{Bus, MemoryOperation, ReadKind},
     components::{InterruptKind, InterruptLine},
 };
 
-mod instructions;
-mod registers;
+pub(super) mod instructions;
+pub(super) mod registers;
 
 use registers::Registers;
 
@@ -28,7 +28,7 @@ enum InterruptDispatchState {
     DecrementingSP,
     PushingMsbPC,
     PushingLsbPC,
-    ChangingPC,
+    ChangingPC(Option<InterruptKind>),
     Complete,
 }
 
@@ -54,7 +54,7 @@ impl InterruptDispatchState {
                 registers.set_sp(sp.wrapping_sub(1));
 
                 let _ = std::mem::replace(self, Self::PushingMsbPC);
-                InterruptDispatchExecutionState::Yield(MemoryOperation::None)
+                InterruptDispatchExecutionState::Yield(MemoryOperation::IncDec { address: sp })
             }
             Self::PushingMsbPC => {
                 let sp = registers.sp();
@@ -73,14 +73,18 @@ impl InterruptDispatchState {
 
                 let [_, value] = registers.pc().to_be_bytes();
 
-                let _ = std::mem::replace(self, Self::ChangingPC);
+                // The interrupt to dispatch is chosen once the MSB of PC has been pushed, if this push
+                // overwrote IE (SP was 0x0000) the dispatch could be cancelled and PC will be set to 0x0000.
+                // The push of the LSB happens too late to have any influence on the interrupt being dispatched.
+                let interrupt = interrupt_line.highest_priority();
+
+                let _ = std::mem::replace(self, Self::ChangingPC(interrupt));
                 InterruptDispatchExecutionState::Yield(MemoryOperation::Write {
                     address: sp,
                     value,
                 })
             }
-            Self::ChangingPC => {
-                let interrupt = interrupt_line.highest_priority();
+            Self::ChangingPC(interrupt) => {
                 if let Some(interrupt_kind) = interrupt {
                     interrupt_line.ack(interrupt_kind);
                     registers.set_pc(match interrupt_kind {
@@ -115,6 +119,13 @@ pub struct Cpu {
     state: State,
     ime: bool,
     enable_ime: bool,
+    ime_enabled_by_ei: bool,
+    /// Address of the opcode of the instruction being fetched or executed
+    instruction_address: u16,
+    /// Opcode of the last decoded instruction, 0xCB for the CB prefixed instructions
+    opcode: u8,
+    tracing: bool,
+    traced_instruction: Option<(u16, Registers)>,
 }
 
 impl Cpu {
@@ -122,15 +133,127 @@ impl Cpu {
         Self {
             registers: Registers::new(),
             state: State::NotStarted,
-            ime: true,
+            ime: false,
             enable_ime: false,
+            ime_enabled_by_ei: false,
+            instruction_address: 0,
+            opcode: 0,
+            tracing: false,
+            traced_instruction: None,
+        }
+    }
+
+    /// Create a CPU in the state left by the boot ROM of the given model
+    pub fn new_post_boot(model: super::Model, header_checksum: u8) -> Self {
+        let [af, bc, de, hl] = model.post_boot_registers(header_checksum);
+
+        let mut registers = Registers::new();
+        registers.set_af(af);
+        registers.set_bc(bc);
+        registers.set_de(de);
+        registers.set_hl(hl);
+        registers.set_sp(0xFFFE);
+        registers.set_pc(0x0100);
+
+        Self {
+            registers,
+            ..Self::new()
         }
     }
 
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    pub fn mut_registers(&mut self) -> &mut Registers {
+        &mut self.registers
+    }
+
+    /// Jump to the given address, the fetch of the next instruction is restarted when it has already started
+    pub fn set_pc(&mut self, pc: u16) {
+        self.registers.set_pc(pc);
+
+        if self.at_instruction_boundary() {
+            self.state = State::NotStarted;
+        }
+    }
+
+    pub fn ime(&self) -> bool {
+        self.ime
+    }
+
+    /// Set IME immediately, cancelling a pending EI
+    pub fn set_ime(&mut self, ime: bool) {
+        self.ime = ime;
+        self.enable_ime = false;
+    }
+
+    /// Halt the CPU between two instructions or wake it up, the execution resumes at PC
+    pub fn set_halted(&mut self, halted: bool) {
+        let between_instructions =
+            self.at_instruction_boundary() || matches!(self.state, State::NotStarted);
+
+        if halted && between_instructions {
+            self.state = State::Halted;
+        } else if !halted && self.is_halted() {
+            self.state = State::NotStarted;
+        }
+    }
+
+    /// The opcode of the next instruction is being fetched, the previous instruction is complete
+    pub fn at_instruction_boundary(&self) -> bool {
+        matches!(self.state, State::WaitingPrefetchRead(false))
+    }
+
+    pub fn is_dispatching_interrupt(&self) -> bool {
+        matches!(
+            self.state,
+            State::StartInterruptDispatch | State::InterruptDispatching(_)
+        )
+    }
+
+    /// The opcode being fetched will be dropped on the next M-cycle to dispatch an interrupt
+    pub fn will_dispatch_interrupt(&self, interrupt_line: &impl InterruptLine) -> bool {
+        self.at_instruction_boundary() && self.ime && interrupt_line.highest_priority().is_some()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        matches!(self.state, State::Halted)
+    }
+
+    /// Address of the opcode of the instruction being fetched or executed
+    pub fn instruction_address(&self) -> u16 {
+        self.instruction_address
+    }
+
+    /// Opcode of the last decoded instruction, still the previous instruction while the next one is fetched
+    pub fn opcode(&self) -> u8 {
+        self.opcode
+    }
+
+    /// Record the registers at the start of each instruction, to be taken with `take_traced_instruction`
+    pub fn set_tracing(&mut self, tracing: bool) {
+        self.tracing = tracing;
+        self.traced_instruction = None;
+    }
+
+    /// Address of the instruction decoded during the last tick and the registers before its execution
+    pub fn take_traced_instruction(&mut self) -> Option<(u16, Registers)> {
+        self.traced_instruction.take()
+    }
+
     fn prefetch_next(&mut self, cb_prefixed: bool) -> MemoryOperation {
+        if !cb_prefixed {
+            self.instruction_address = self.registers.pc();
+        }
+
         self.state = State::WaitingPrefetchRead(cb_prefixed);
         MemoryOperation::Read {
             address: self.registers.pc(),
+            kind: match cb_prefixed {
+                true => ReadKind::Operand,
+                false => ReadKind::Opcode,
+            },
         }
     }
 
@@ -146,6 +269,8 @@ impl Cpu {
                     }
                 }
 
+                // EI only takes effect after the instruction following it has been fetched
+                self.ime_enabled_by_ei = self.enable_ime;
                 if self.enable_ime {
                     self.enable_ime = false;
                     self.ime = true;
@@ -157,8 +282,12 @@ impl Cpu {
                 self.tick(bus, interrupt_line)
             }
             State::DecodeAndExec(opcode, cb_prefixed) => {
-                if !*cb_prefixed && *opcode == 0xCB {
-                    return self.prefetch_next(true);
+                if !*cb_prefixed {
+                    self.opcode = *opcode;
+
+                    if *opcode == 0xCB {
+                        return self.prefetch_next(true);
+                    }
                 }
 
                 let instruction = match cb_prefixed {
@@ -166,6 +295,10 @@ impl Cpu {
                     false => instructions::INSTRUCTIONS_TABLE[bus.data() as usize],
                 };
 
+                if self.tracing {
+                    self.traced_instruction = Some((self.instruction_address, self.registers));
+                }
+
                 self.state =
                     State::ExecutingInstruction(instruction, instruction.create_execution());
 
@@ -180,8 +313,7 @@ impl Cpu {
                     instructions::InstructionExecutionState::YieldCpuOperation(cpu_op) => {
                         match cpu_op {
                             CpuOperation::EnableInterrupt => {
-                                self.enable_ime = true;
-                                self.ime = false;
+                                self.enable_ime = !self.ime;
                             }
                             CpuOperation::EnableInterruptNow => {
                                 self.enable_ime = false;
@@ -216,9 +348,17 @@ impl Cpu {
             State::AfterHalt => {
                 if interrupt_line.highest_priority().is_some() {
                     if self.ime {
+                        if self.ime_enabled_by_ei {
+                            // When HALT directly follows EI the HALT bug still happens, which means that the
+                            // interrupt will return to the HALT instruction and execute it again.
+                            self.registers.set_pc(self.registers.pc().wrapping_sub(1));
+                        }
+
                         self.state = State::StartInterruptDispatch;
                         self.tick(bus, interrupt_line)
                     } else {
+                        // HALT bug: the CPU doesn't halt, and fails to increment PC after reading the next
+                        // opcode, meaning that the byte following HALT is read twice.
                         self.state = State::DecodeAndExec(bus.data(), false);
                         self.tick(bus, interrupt_line)
                     }
@@ -228,7 +368,7 @@ impl Cpu {
                 }
             }
             State::Halted => {
-                if interrupt_line.flags_not_empty() {
+                if interrupt_line.highest_priority().is_some() {
                     self.prefetch_next(false)
                 } else {
                     MemoryOperation::None
@@ -4,14 +4,14 @@ mod alu;
 mod condition;
 mod operands;
 
-use super::super::components::{MMUContext, Mmu};
+use super::super::components::{MMUReadContext, Mmu};
 use super::registers::Registers;
 use super::MemoryOperation;
 
 pub trait Instruction: Send + Sync {
     fn raw_desc(&self) -> Cow<'static, str>;
 
-    fn desc(&self, pc: u16, mmu: &dyn Mmu, mmu_ctx: &MMUContext) -> Cow<'static, str> {
+    fn desc(&self, pc: u16, mmu: &dyn Mmu, mmu_ctx: &MMUReadContext) -> Cow<'static, str> {
         let raw_desc = self.raw_desc();
 
         if raw_desc.contains("u8") {
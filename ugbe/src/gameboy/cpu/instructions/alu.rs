@@ -1,5 +1,8 @@
 pub trait ALUOp {
     const STR: &'static str;
+
+    /// The operation always works on A, which is omitted in its mnemonic (like `RLCA` or `CPL`)
+    const IMPLICIT_OPERAND: bool = false;
 }
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -13,6 +16,11 @@ pub struct ALUOpResult<Value> {
 
 pub trait ALUOneOp<Value>: ALUOp {
     fn execute(value: Value, nf: bool, hf: bool, cf: bool) -> ALUOpResult<Value>;
+
+    /// Address put on the address bus when the operation is done by the IDU instead of the ALU
+    fn idu_address(_value: Value) -> Option<u16> {
+        None
+    }
 }
 
 pub trait ALUTwoOp<DstValue, SrcValue>: ALUOp {
@@ -56,6 +64,10 @@ impl ALUOneOp<u16> for Inc {
             cf: None,
         }
     }
+
+    fn idu_address(value: u16) -> Option<u16> {
+        Some(value)
+    }
 }
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -91,6 +103,10 @@ impl ALUOneOp<u16> for Dec {
             cf: None,
         }
     }
+
+    fn idu_address(value: u16) -> Option<u16> {
+        Some(value)
+    }
 }
 
 #[allow(clippy::upper_case_acronyms)]
@@ -273,6 +289,7 @@ pub struct RLCA {}
 
 impl ALUOp for RLCA {
     const STR: &'static str = "RLCA";
+    const IMPLICIT_OPERAND: bool = true;
 }
 
 impl ALUOneOp<u8> for RLCA {
@@ -290,6 +307,7 @@ pub struct RRCA {}
 
 impl ALUOp for RRCA {
     const STR: &'static str = "RRCA";
+    const IMPLICIT_OPERAND: bool = true;
 }
 
 impl ALUOneOp<u8> for RRCA {
@@ -307,6 +325,7 @@ pub struct RLA {}
 
 impl ALUOp for RLA {
     const STR: &'static str = "RLA";
+    const IMPLICIT_OPERAND: bool = true;
 }
 
 impl ALUOneOp<u8> for RLA {
@@ -324,6 +343,7 @@ pub struct RRA {}
 
 impl ALUOp for RRA {
     const STR: &'static str = "RRA";
+    const IMPLICIT_OPERAND: bool = true;
 }
 
 impl ALUOneOp<u8> for RRA {
@@ -341,6 +361,7 @@ pub struct DAA {}
 
 impl ALUOp for DAA {
     const STR: &'static str = "DAA";
+    const IMPLICIT_OPERAND: bool = true;
 }
 
 impl ALUOneOp<u8> for DAA {
@@ -377,6 +398,7 @@ pub struct CPL {}
 
 impl ALUOp for CPL {
     const STR: &'static str = "CPL";
+    const IMPLICIT_OPERAND: bool = true;
 }
 
 impl ALUOneOp<u8> for CPL {
@@ -399,6 +421,7 @@ pub struct SCF {}
 
 impl ALUOp for SCF {
     const STR: &'static str = "SCF";
+    const IMPLICIT_OPERAND: bool = true;
 }
 
 impl ALUOneOp<u8> for SCF {
@@ -419,6 +442,7 @@ pub struct CCF {}
 
 impl ALUOp for CCF {
     const STR: &'static str = "CCF";
+    const IMPLICIT_OPERAND: bool = true;
 }
 
 impl ALUOneOp<u8> for CCF {
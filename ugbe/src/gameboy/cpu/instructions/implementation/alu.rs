@@ -38,13 +38,20 @@ where
     Op: Operand + OperandIn + OperandOut + Send + Sync + 'static,
 {
     fn raw_desc(&self) -> Cow<'static, str> {
-        format!("{} {}", ALUOp::STR, Op::str()).into()
+        if ALUOp::IMPLICIT_OPERAND {
+            ALUOp::STR.into()
+        } else {
+            format!("{} {}", ALUOp::STR, Op::str()).into()
+        }
     }
 
     fn create_execution(&self) -> Box<dyn InstructionExecution + 'static> {
         Box::new(ALUExecution::<Op, Op, _, true>::Start(
             |_: Option<Op::Value>, value: Op::Value, registers: &mut Registers| {
-                ALUOp::execute(value, registers.nf(), registers.hf(), registers.cf())
+                (
+                    ALUOp::execute(value, registers.nf(), registers.hf(), registers.cf()),
+                    ALUOp::idu_address(value),
+                )
             },
         ))
     }
@@ -86,10 +93,13 @@ where
     fn create_execution(&self) -> Box<dyn InstructionExecution + 'static> {
         Box::new(ALUExecution::<Dst, Src, _, false>::Start(
             |dst: Option<Dst::Value>, src: Src::Value, registers: &mut Registers| {
-                ALUOp::execute(
-                    dst.expect("As DST_IS_SRC is false, dst should be set"),
-                    src,
-                    registers.cf(),
+                (
+                    ALUOp::execute(
+                        dst.expect("As DST_IS_SRC is false, dst should be set"),
+                        src,
+                        registers.cf(),
+                    ),
+                    None,
                 )
             },
         ))
@@ -128,7 +138,9 @@ where
 
     fn create_execution(&self) -> Box<dyn InstructionExecution + 'static> {
         Box::new(ALUExecution::<Op, Op, _, true>::Start(
-            |_: Option<Op::Value>, value: Op::Value, _: &mut Registers| ALUOp::execute(value),
+            |_: Option<Op::Value>, value: Op::Value, _: &mut Registers| {
+                (ALUOp::execute(value), None)
+            },
         ))
     }
 }
@@ -137,7 +149,7 @@ enum ALUExecution<Dst, Src, ALUFn, const DST_IS_SRC: bool>
 where
     Src: Operand + OperandIn + Send + Sync + 'static,
     Dst: Operand + OperandIn + OperandOut + Send + Sync + 'static,
-    ALUFn: Fn(Option<Dst::Value>, Src::Value, &mut Registers) -> ALUOpResult<Dst::Value>
+    ALUFn: Fn(Option<Dst::Value>, Src::Value, &mut Registers) -> (ALUOpResult<Dst::Value>, Option<u16>)
         + Send
         + 'static,
 {
@@ -156,8 +168,8 @@ where
         dst: Option<Dst::Value>,
         src: Src::Value,
     },
-    WritingToDst(Box<dyn OperandWriteExecution + 'static>),
-    Wait(usize),
+    WritingToDst(Box<dyn OperandWriteExecution + 'static>, Option<u16>),
+    Wait(usize, Option<u16>),
     Complete,
 }
 
@@ -166,7 +178,7 @@ impl<Dst, Src, ALUFn, const DST_IS_SRC: bool> InstructionExecution
 where
     Src: Operand + OperandIn + Send + Sync + 'static,
     Dst: Operand + OperandIn + OperandOut + Send + Sync + 'static,
-    ALUFn: Fn(Option<Dst::Value>, Src::Value, &mut Registers) -> ALUOpResult<Dst::Value>
+    ALUFn: Fn(Option<Dst::Value>, Src::Value, &mut Registers) -> (ALUOpResult<Dst::Value>, Option<u16>)
         + Send
         + Sync
         + 'static,
@@ -261,7 +273,7 @@ where
                 }
             },
             ALUExecution::Do { alu_fn, dst, src } => {
-                let result = alu_fn(dst, src, registers);
+                let (result, idu_address) = alu_fn(dst, src, registers);
 
                 if let Some(zf) = result.zf {
                     registers.set_zf(zf);
@@ -280,32 +292,47 @@ where
                 }
 
                 if let Some(value) = result.value {
-                    let _ = std::mem::replace(self, Self::WritingToDst(Dst::write_value(value)));
+                    let _ = std::mem::replace(
+                        self,
+                        Self::WritingToDst(Dst::write_value(value), idu_address),
+                    );
                     self.next(registers, data_bus)
                 } else {
-                    let _ = std::mem::replace(self, Self::Wait(alu_extra_cycles));
+                    let _ = std::mem::replace(self, Self::Wait(alu_extra_cycles, idu_address));
                     self.next(registers, data_bus)
                 }
             }
-            ALUExecution::WritingToDst(mut operand_write_value) => {
+            ALUExecution::WritingToDst(mut operand_write_value, idu_address) => {
                 match operand_write_value.next(registers, data_bus) {
                     OperandWriteExecutionState::Yield(memory_operation) => {
-                        let _ = std::mem::replace(self, Self::WritingToDst(operand_write_value));
+                        let _ = std::mem::replace(
+                            self,
+                            Self::WritingToDst(operand_write_value, idu_address),
+                        );
                         InstructionExecutionState::YieldMemoryOperation(memory_operation)
                     }
                     OperandWriteExecutionState::Complete => {
-                        let _ = std::mem::replace(self, Self::Wait(alu_extra_cycles));
+                        let _ = std::mem::replace(self, Self::Wait(alu_extra_cycles, idu_address));
                         self.next(registers, data_bus)
                     }
                 }
             }
-            ALUExecution::Wait(cycles) => {
+            ALUExecution::Wait(cycles, idu_address) => {
                 if cycles == 0 {
                     let _ = std::mem::replace(self, Self::Complete);
                     self.next(registers, data_bus)
                 } else {
-                    let _ = std::mem::replace(self, Self::Wait(cycles - 1));
-                    InstructionExecutionState::YieldMemoryOperation(MemoryOperation::None)
+                    let _ = std::mem::replace(self, Self::Wait(cycles - 1, None));
+
+                    // The IDU only puts the register on the address bus during the first extra cycle
+                    match idu_address {
+                        Some(address) => InstructionExecutionState::YieldMemoryOperation(
+                            MemoryOperation::IncDec { address },
+                        ),
+                        None => {
+                            InstructionExecutionState::YieldMemoryOperation(MemoryOperation::None)
+                        }
+                    }
                 }
             }
             ALUExecution::Complete => InstructionExecutionState::Complete,
@@ -94,7 +94,9 @@ where
                 registers.set_sp(sp.wrapping_sub(1));
 
                 let _ = std::mem::replace(self, Self::PushingMsbPC(new_address));
-                InstructionExecutionState::YieldMemoryOperation(MemoryOperation::None)
+                InstructionExecutionState::YieldMemoryOperation(MemoryOperation::IncDec {
+                    address: sp,
+                })
             }
             Self::PushingMsbPC(new_address) => {
                 let sp = registers.sp();
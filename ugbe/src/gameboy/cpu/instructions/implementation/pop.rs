@@ -68,7 +68,7 @@ where
                 registers.set_sp(sp.wrapping_add(1));
 
                 let _ = std::mem::replace(self, Self::PopingMsb);
-                InstructionExecutionState::YieldMemoryOperation(MemoryOperation::Read {
+                InstructionExecutionState::YieldMemoryOperation(MemoryOperation::ReadIncDec {
                     address: sp,
                 })
             }
@@ -77,7 +77,7 @@ where
                 registers.set_sp(sp.wrapping_add(1));
 
                 let _ = std::mem::replace(self, Self::PoppingEnd(data_bus));
-                InstructionExecutionState::YieldMemoryOperation(MemoryOperation::Read {
+                InstructionExecutionState::YieldMemoryOperation(MemoryOperation::ReadIncDec {
                     address: sp,
                 })
             }
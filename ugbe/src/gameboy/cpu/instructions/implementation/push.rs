@@ -78,7 +78,9 @@ where
                 registers.set_sp(sp.wrapping_sub(1));
 
                 let _ = std::mem::replace(self, Self::PushingMsb(bytes));
-                InstructionExecutionState::YieldMemoryOperation(MemoryOperation::None)
+                InstructionExecutionState::YieldMemoryOperation(MemoryOperation::IncDec {
+                    address: sp,
+                })
             }
             Self::PushingMsb(bytes) => {
                 let sp = registers.sp();
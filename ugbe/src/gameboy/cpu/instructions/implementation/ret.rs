@@ -31,7 +31,9 @@ where
     Cond: Condition + Send + Sync + 'static,
 {
     fn raw_desc(&self) -> Cow<'static, str> {
-        if Cond::is_none() {
+        if ENABLE_INTERRUPT {
+            "RETI".into()
+        } else if Cond::is_none() {
             "RET".into()
         } else {
             format!("RET {}", Cond::STR).into()
@@ -86,7 +88,7 @@ where
                 registers.set_sp(sp.wrapping_add(1));
 
                 let _ = std::mem::replace(self, Self::PopingMsbPC);
-                InstructionExecutionState::YieldMemoryOperation(MemoryOperation::Read {
+                InstructionExecutionState::YieldMemoryOperation(MemoryOperation::ReadIncDec {
                     address: sp,
                 })
             }
@@ -95,7 +97,7 @@ where
                 registers.set_sp(sp.wrapping_add(1));
 
                 let _ = std::mem::replace(self, Self::SettingPC(data_bus));
-                InstructionExecutionState::YieldMemoryOperation(MemoryOperation::Read {
+                InstructionExecutionState::YieldMemoryOperation(MemoryOperation::ReadIncDec {
                     address: sp,
                 })
             }
@@ -45,7 +45,9 @@ impl<const ADDRESS: u8> InstructionExecution for RstExecution<ADDRESS> {
                 registers.set_sp(sp.wrapping_sub(1));
 
                 let _ = std::mem::replace(self, Self::PushingMsbPC);
-                InstructionExecutionState::YieldMemoryOperation(MemoryOperation::None)
+                InstructionExecutionState::YieldMemoryOperation(MemoryOperation::IncDec {
+                    address: sp,
+                })
             }
             Self::PushingMsbPC => {
                 let sp = registers.sp();
@@ -3,7 +3,7 @@ use std::marker::PhantomData;
 use std::ops::{Index, IndexMut};
 
 use super::super::super::registers::Registers;
-use super::super::super::MemoryOperation;
+use super::super::super::{MemoryOperation, ReadKind};
 use super::{
     Operand, OperandIn, OperandOut, OperandReadExecution, OperandReadExecutionState,
     OperandRegister, OperandWriteExecution, OperandWriteExecutionState,
@@ -21,6 +21,11 @@ pub struct NoneAddress {}
 pub trait ValueToAddress<Value> {
     fn str() -> Cow<'static, str>;
     fn address(registers: &mut Registers, value: Value) -> u16;
+
+    /// Whether the register used as address is incremented or decremented by the IDU during the access
+    fn is_inc_dec() -> bool {
+        false
+    }
 }
 
 impl<Op> ValueToAddress<u8> for (Op, u8, NoneAddress)
@@ -61,6 +66,10 @@ where
         <Op as OperandRegister>::write_register(registers, value.wrapping_add(1));
         value
     }
+
+    fn is_inc_dec() -> bool {
+        true
+    }
 }
 
 impl<Op> ValueToAddress<u16> for (Op, u16, DecrementAddress)
@@ -75,6 +84,10 @@ where
         <Op as OperandRegister>::write_register(registers, value.wrapping_sub(1));
         value
     }
+
+    fn is_inc_dec() -> bool {
+        true
+    }
 }
 
 pub trait EndianNumeric {
@@ -187,7 +200,17 @@ where
                             self,
                             Self::Dereferencing(address, Value::Array::default(), 0),
                         );
-                        OperandReadExecutionState::Yield(MemoryOperation::Read { address })
+
+                        if <(Op, Op::Value, AddressOperation)>::is_inc_dec() {
+                            OperandReadExecutionState::Yield(MemoryOperation::ReadIncDec {
+                                address,
+                            })
+                        } else {
+                            OperandReadExecutionState::Yield(MemoryOperation::Read {
+                                address,
+                                kind: ReadKind::Data,
+                            })
+                        }
                     }
                 }
             }
@@ -202,7 +225,10 @@ where
 
                     let _ =
                         std::mem::replace(self, Self::Dereferencing(address, le_bytes, idx + 1));
-                    OperandReadExecutionState::Yield(MemoryOperation::Read { address })
+                    OperandReadExecutionState::Yield(MemoryOperation::Read {
+                        address,
+                        kind: ReadKind::Data,
+                    })
                 }
             }
             Self::Complete(value) => OperandReadExecutionState::Complete(value),
@@ -1,7 +1,7 @@
 use std::marker::PhantomData;
 
 use super::super::super::registers::Registers;
-use super::super::super::MemoryOperation;
+use super::super::super::{MemoryOperation, ReadKind};
 use super::{Operand, OperandReadExecution, OperandReadExecutionState, OperandRegister};
 
 pub trait ImmediateFromU8 {
@@ -60,7 +60,10 @@ where
 
                 let _ =
                     std::mem::replace(self, Self::Reading(0, <Op as Operand>::Value::from_u8(0)));
-                OperandReadExecutionState::Yield(MemoryOperation::Read { address: pc })
+                OperandReadExecutionState::Yield(MemoryOperation::Read {
+                    address: pc,
+                    kind: ReadKind::Operand,
+                })
             }
             Self::Reading(mut idx, mut value) => {
                 value = value | (<Op as Operand>::Value::from_u8(data_bus) << (idx * 8));
@@ -74,7 +77,10 @@ where
                     registers.set_pc(pc.wrapping_add(1));
 
                     let _ = std::mem::replace(self, Self::Reading(idx, value));
-                    OperandReadExecutionState::Yield(MemoryOperation::Read { address: pc })
+                    OperandReadExecutionState::Yield(MemoryOperation::Read {
+                        address: pc,
+                        kind: ReadKind::Operand,
+                    })
                 }
             }
             Self::Complete(value) => OperandReadExecutionState::Complete(value),
@@ -105,7 +111,10 @@ where
                 registers.set_pc(pc.wrapping_add(1));
 
                 let _ = std::mem::replace(self, Self::Computing);
-                OperandReadExecutionState::Yield(MemoryOperation::Read { address: pc })
+                OperandReadExecutionState::Yield(MemoryOperation::Read {
+                    address: pc,
+                    kind: ReadKind::Operand,
+                })
             }
             Self::Computing => {
                 let offset = data_bus as i8 as i16 as u16;
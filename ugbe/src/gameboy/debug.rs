@@ -0,0 +1,598 @@
+use std::ops::RangeInclusive;
+
+pub mod cdl;
+pub mod cpu;
+pub mod diff;
+pub mod disassembler;
+pub mod io;
+pub mod memory;
+pub mod profiler;
+pub mod symbols;
+pub mod trace;
+pub mod video;
+
+use super::{
+    bus::{MemoryOperation, ReadKind},
+    Gameboy,
+};
+
+/// Names of the I/O registers, as used in the Pan Docs and hardware.inc
+const IO_REGISTERS: &[(u16, &str)] = &[
+    (0xFF00, "P1"),
+    (0xFF01, "SB"),
+    (0xFF02, "SC"),
+    (0xFF04, "DIV"),
+    (0xFF05, "TIMA"),
+    (0xFF06, "TMA"),
+    (0xFF07, "TAC"),
+    (0xFF0F, "IF"),
+    (0xFF10, "NR10"),
+    (0xFF11, "NR11"),
+    (0xFF12, "NR12"),
+    (0xFF13, "NR13"),
+    (0xFF14, "NR14"),
+    (0xFF16, "NR21"),
+    (0xFF17, "NR22"),
+    (0xFF18, "NR23"),
+    (0xFF19, "NR24"),
+    (0xFF1A, "NR30"),
+    (0xFF1B, "NR31"),
+    (0xFF1C, "NR32"),
+    (0xFF1D, "NR33"),
+    (0xFF1E, "NR34"),
+    (0xFF20, "NR41"),
+    (0xFF21, "NR42"),
+    (0xFF22, "NR43"),
+    (0xFF23, "NR44"),
+    (0xFF24, "NR50"),
+    (0xFF25, "NR51"),
+    (0xFF26, "NR52"),
+    (0xFF40, "LCDC"),
+    (0xFF41, "STAT"),
+    (0xFF42, "SCY"),
+    (0xFF43, "SCX"),
+    (0xFF44, "LY"),
+    (0xFF45, "LYC"),
+    (0xFF46, "DMA"),
+    (0xFF47, "BGP"),
+    (0xFF48, "OBP0"),
+    (0xFF49, "OBP1"),
+    (0xFF4A, "WY"),
+    (0xFF4B, "WX"),
+    (0xFF4D, "KEY1"),
+    (0xFF4F, "VBK"),
+    (0xFF50, "BOOT"),
+    (0xFF51, "HDMA1"),
+    (0xFF52, "HDMA2"),
+    (0xFF53, "HDMA3"),
+    (0xFF54, "HDMA4"),
+    (0xFF55, "HDMA5"),
+    (0xFF56, "RP"),
+    (0xFF68, "BCPS"),
+    (0xFF69, "BCPD"),
+    (0xFF6A, "OCPS"),
+    (0xFF6B, "OCPD"),
+    (0xFF70, "SVBK"),
+    (0xFFFF, "IE"),
+];
+
+/// Name of the I/O register at the given address
+pub fn io_register_name(address: u16) -> Option<&'static str> {
+    IO_REGISTERS
+        .iter()
+        .find(|(register_address, _)| *register_address == address)
+        .map(|(_, name)| *name)
+}
+
+/// Address of an I/O register from its name, ignoring the case
+pub fn io_register_address(name: &str) -> Option<u16> {
+    IO_REGISTERS
+        .iter()
+        .find(|(_, register_name)| register_name.eq_ignore_ascii_case(name))
+        .map(|(address, _)| *address)
+}
+
+/// Execution stops when the CPU is about to execute the instruction at this address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Breakpoint {
+    address: u16,
+    bank: Option<usize>,
+}
+
+impl Breakpoint {
+    /// Breakpoint hit whatever the bank mapped at the address
+    pub fn new(address: u16) -> Self {
+        Self {
+            address,
+            bank: None,
+        }
+    }
+
+    /// Only hit the breakpoint when the given bank is mapped at the address
+    pub fn set_bank(self, bank: usize) -> Self {
+        Self {
+            bank: Some(bank),
+            ..self
+        }
+    }
+
+    /// Breakpoint on a label, like `Main.loop`, only hit in the bank of the label
+    pub fn at_symbol(symbol: &symbols::Symbol) -> Self {
+        Self::new(symbol.address()).set_bank(symbol.bank())
+    }
+
+    pub fn address(&self) -> u16 {
+        self.address
+    }
+
+    pub fn bank(&self) -> Option<usize> {
+        self.bank
+    }
+
+    fn matches(&self, address: u16, bank: usize) -> bool {
+        self.address == address && self.bank.is_none_or(|expected| expected == bank)
+    }
+}
+
+/// Execution stops when the CPU accesses an address in the given range
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Watchpoint {
+    range: RangeInclusive<u16>,
+    bank: Option<usize>,
+    read: bool,
+    write: bool,
+    execute: bool,
+}
+
+impl Watchpoint {
+    fn new(range: RangeInclusive<u16>, read: bool, write: bool, execute: bool) -> Self {
+        Self {
+            range,
+            bank: None,
+            read,
+            write,
+            execute,
+        }
+    }
+
+    /// Watch the data reads of the instructions, the opcode and operand fetches being ignored
+    pub fn read(range: RangeInclusive<u16>) -> Self {
+        Self::new(range, true, false, false)
+    }
+
+    pub fn write(range: RangeInclusive<u16>) -> Self {
+        Self::new(range, false, true, false)
+    }
+
+    /// Watch both the reads and the writes
+    pub fn access(range: RangeInclusive<u16>) -> Self {
+        Self::new(range, true, true, false)
+    }
+
+    /// Watch the execution of the instructions in the range
+    pub fn execute(range: RangeInclusive<u16>) -> Self {
+        Self::new(range, false, false, true)
+    }
+
+    /// Watch the reads and the writes of an I/O register, see `io_register_address` to find it by name
+    pub fn io_register(address: u16) -> Self {
+        Self::access(address..=address)
+    }
+
+    /// Only watch the accesses when the given bank is mapped in the range
+    pub fn set_bank(self, bank: usize) -> Self {
+        Self {
+            bank: Some(bank),
+            ..self
+        }
+    }
+
+    pub fn range(&self) -> &RangeInclusive<u16> {
+        &self.range
+    }
+
+    pub fn bank(&self) -> Option<usize> {
+        self.bank
+    }
+
+    fn matches(&self, access: Access, address: u16, bank: usize) -> bool {
+        let kind = match access {
+            Access::Read(_) => self.read,
+            Access::Write(_) => self.write,
+            Access::Execute => self.execute,
+        };
+
+        kind && self.range.contains(&address) && self.bank.is_none_or(|expected| expected == bank)
+    }
+}
+
+/// Access done by the CPU on the memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Access {
+    Read(u8),
+    Write(u8),
+    Execute,
+}
+
+/// Interrupt dispatched by the CPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Interrupt {
+    VBlank,
+    Stat,
+    Timer,
+    Serial,
+    Joypad,
+    /// The dispatch has been cancelled by the push of PC overwriting IE, the CPU jumps to 0x0000
+    Cancelled,
+}
+
+impl Interrupt {
+    fn from_vector(vector: u16) -> Self {
+        match vector {
+            0x40 => Self::VBlank,
+            0x48 => Self::Stat,
+            0x50 => Self::Timer,
+            0x58 => Self::Serial,
+            0x60 => Self::Joypad,
+            _ => Self::Cancelled,
+        }
+    }
+}
+
+/// Why the debugger paused the execution
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PauseReason {
+    /// Pause requested with `Debugger::pause`
+    Requested,
+    Breakpoint(Breakpoint),
+    /// The watched access happened during the execution of the instruction at `pc`, the execution is paused
+    /// right after the access, possibly in the middle of the instruction
+    Watchpoint {
+        watchpoint: Watchpoint,
+        access: Access,
+        address: u16,
+        bank: usize,
+        pc: u16,
+        register: Option<&'static str>,
+        /// Label of the address accessed, when symbols are loaded
+        label: Option<String>,
+    },
+    /// A step requested with `step_into`, `step_over` or `step_out` is complete
+    Step,
+    /// The CPU is about to execute the handler of the interrupt, as requested with `run_to_next_interrupt`
+    Interrupt(Interrupt),
+}
+
+/// Instruction the CPU started executing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Instruction {
+    pc: u16,
+    opcode: u8,
+    sp: u16,
+}
+
+impl Instruction {
+    fn call_size(&self) -> Option<u16> {
+        match self.opcode {
+            // CALL a16, CALL cc, a16
+            0xCD | 0xC4 | 0xCC | 0xD4 | 0xDC => Some(3),
+            // RST
+            0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => Some(1),
+            _ => None,
+        }
+    }
+
+    fn is_return(&self) -> bool {
+        // RET, RETI, RET cc
+        matches!(self.opcode, 0xC9 | 0xD9 | 0xC0 | 0xC8 | 0xD0 | 0xD8)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Mode {
+    Run,
+    Pause,
+    StepInto,
+    /// Run until the instruction following the call is reached with the stack of the caller
+    StepOver {
+        pc: u16,
+        sp: u16,
+    },
+    /// Run until a return leaves the stack frame of the current function
+    StepOut {
+        sp: u16,
+    },
+    NextInterrupt,
+}
+
+/// Debugger driving a Gameboy, pausing its execution on breakpoints, watchpoints and steps.
+///
+/// The debugger is fed with `Debugger::observe` after each `Gameboy::tick`, or ticks the Gameboy itself with
+/// `Debugger::run`. It follows the memory operations done by the CPU on each M-cycle: instructions are delimited by
+/// the fetches of their opcode, a pause on a breakpoint or a step happens once the opcode of the next instruction
+/// has been fetched, right before it is decoded. An interrupt dispatch aborting the fetch is known by then, so the
+/// instruction fetched again after the handler is only checked once.
+#[derive(Debug, Clone)]
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    mode: Mode,
+    pause_reason: Option<PauseReason>,
+    instruction: Option<Instruction>,
+    /// Instruction whose opcode has been fetched, checked right before the CPU decodes it
+    fetched: Option<Instruction>,
+    dispatching_interrupt: bool,
+    symbols: Option<symbols::SymbolTable>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            mode: Mode::Run,
+            pause_reason: None,
+            instruction: None,
+            fetched: None,
+            dispatching_interrupt: false,
+            symbols: None,
+        }
+    }
+
+    /// Use the labels of the program to describe the addresses accessed
+    pub fn set_symbols(&mut self, symbols: symbols::SymbolTable) {
+        self.symbols = Some(symbols);
+    }
+
+    pub fn symbols(&self) -> Option<&symbols::SymbolTable> {
+        self.symbols.as_ref()
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    /// Return false if the breakpoint wasn't set
+    pub fn remove_breakpoint(&mut self, breakpoint: &Breakpoint) -> bool {
+        let count = self.breakpoints.len();
+        self.breakpoints.retain(|other| other != breakpoint);
+        count != self.breakpoints.len()
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        if !self.watchpoints.contains(&watchpoint) {
+            self.watchpoints.push(watchpoint);
+        }
+    }
+
+    /// Return false if the watchpoint wasn't set
+    pub fn remove_watchpoint(&mut self, watchpoint: &Watchpoint) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints.retain(|other| other != watchpoint);
+        count != self.watchpoints.len()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pause_reason.is_some()
+    }
+
+    pub fn pause_reason(&self) -> Option<&PauseReason> {
+        self.pause_reason.as_ref()
+    }
+
+    /// Address of the instruction being executed, or about to be executed when paused between two instructions
+    pub fn instruction_address(&self) -> Option<u16> {
+        self.instruction.map(|instruction| instruction.pc)
+    }
+
+    /// Pause before the next instruction, or right away if the CPU is halted
+    pub fn pause(&mut self) {
+        if !self.is_paused() {
+            self.mode = Mode::Pause;
+        }
+    }
+
+    pub fn resume(&mut self) {
+        self.resume_with(Mode::Run);
+    }
+
+    /// Execute the current instruction, pausing before the next one
+    pub fn step_into(&mut self) {
+        self.resume_with(Mode::StepInto);
+    }
+
+    /// Like `step_into`, but run the whole function when the current instruction is a CALL or a RST
+    pub fn step_over(&mut self) {
+        let mode = match self.instruction {
+            Some(instruction) => match instruction.call_size() {
+                Some(size) => Mode::StepOver {
+                    pc: instruction.pc.wrapping_add(size),
+                    sp: instruction.sp,
+                },
+                None => Mode::StepInto,
+            },
+            None => Mode::StepInto,
+        };
+
+        self.resume_with(mode);
+    }
+
+    /// Run until the current function returns to its caller
+    pub fn step_out(&mut self) {
+        let mode = match self.instruction {
+            Some(instruction) => Mode::StepOut { sp: instruction.sp },
+            None => Mode::StepInto,
+        };
+
+        self.resume_with(mode);
+    }
+
+    /// Run until the CPU dispatches an interrupt, pausing before the first instruction of its handler
+    pub fn run_to_next_interrupt(&mut self) {
+        self.resume_with(Mode::NextInterrupt);
+    }
+
+    fn resume_with(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.pause_reason = None;
+    }
+
+    /// Follow the M-cycle of the last `Gameboy::tick`, if any, the Gameboy must not be ticked while paused
+    pub fn observe(&mut self, gameboy: &Gameboy) {
+        if self.is_paused() {
+            return;
+        }
+
+        if let Some(memory_operation) = gameboy.last_memory_operation {
+            self.pause_reason = self.check(gameboy, memory_operation);
+        }
+
+        // The next tick is the M-cycle decoding the fetched opcode, unless an interrupt dispatch drops it
+        if self.pause_reason.is_none() && gameboy.clock.is_m_cycle() {
+            if let Some(instruction) = self.fetched.take() {
+                if !gameboy.cpu.will_dispatch_interrupt(&gameboy.interrupt) {
+                    self.pause_reason = self.check_instruction(gameboy, instruction);
+                }
+            }
+        }
+
+        if self.pause_reason.is_some() {
+            self.mode = Mode::Run;
+        }
+    }
+
+    /// Tick the Gameboy until the execution is paused, at most the given number of T-cycles
+    pub fn run(&mut self, gameboy: &mut Gameboy, max_cycles: usize) -> Option<&PauseReason> {
+        for _ in 0..max_cycles {
+            if self.is_paused() {
+                break;
+            }
+
+            gameboy.tick();
+            self.observe(gameboy);
+        }
+
+        self.pause_reason()
+    }
+
+    fn check(
+        &mut self,
+        gameboy: &Gameboy,
+        memory_operation: MemoryOperation,
+    ) -> Option<PauseReason> {
+        let cpu = &gameboy.cpu;
+
+        if cpu.is_dispatching_interrupt() {
+            self.dispatching_interrupt = true;
+        }
+
+        if cpu.at_instruction_boundary() {
+            self.fetched = Some(Instruction {
+                pc: cpu.registers().pc(),
+                opcode: gameboy.bus.data(),
+                sp: cpu.registers().sp(),
+            });
+
+            return None;
+        }
+
+        if cpu.is_halted() && self.mode == Mode::Pause {
+            return Some(PauseReason::Requested);
+        }
+
+        let (address, access) = match memory_operation {
+            MemoryOperation::Read {
+                address,
+                kind: ReadKind::Data,
+            }
+            | MemoryOperation::ReadIncDec { address } => {
+                (address, Access::Read(gameboy.bus.data()))
+            }
+            MemoryOperation::Write { address, value } => (address, Access::Write(value)),
+            _ => return None,
+        };
+
+        self.check_watchpoints(gameboy, access, address)
+    }
+
+    fn check_instruction(
+        &mut self,
+        gameboy: &Gameboy,
+        instruction: Instruction,
+    ) -> Option<PauseReason> {
+        let previous = self.instruction.replace(instruction);
+
+        let interrupt = std::mem::take(&mut self.dispatching_interrupt)
+            .then(|| Interrupt::from_vector(instruction.pc));
+
+        let bank = gameboy.bank(instruction.pc);
+        if let Some(breakpoint) = self
+            .breakpoints
+            .iter()
+            .find(|breakpoint| breakpoint.matches(instruction.pc, bank))
+        {
+            return Some(PauseReason::Breakpoint(*breakpoint));
+        }
+
+        if let Some(reason) = self.check_watchpoints(gameboy, Access::Execute, instruction.pc) {
+            return Some(reason);
+        }
+
+        match self.mode {
+            Mode::Run => None,
+            Mode::Pause => Some(PauseReason::Requested),
+            Mode::StepInto => Some(PauseReason::Step),
+            Mode::StepOver { pc, sp } => {
+                (instruction.pc == pc && instruction.sp >= sp).then_some(PauseReason::Step)
+            }
+            Mode::StepOut { sp } => {
+                let returned = previous.is_some_and(|previous| previous.is_return());
+                (returned && instruction.sp > sp).then_some(PauseReason::Step)
+            }
+            Mode::NextInterrupt => interrupt.map(PauseReason::Interrupt),
+        }
+    }
+
+    fn check_watchpoints(
+        &self,
+        gameboy: &Gameboy,
+        access: Access,
+        address: u16,
+    ) -> Option<PauseReason> {
+        let bank = gameboy.bank(address);
+
+        self.watchpoints
+            .iter()
+            .find(|watchpoint| watchpoint.matches(access, address, bank))
+            .map(|watchpoint| PauseReason::Watchpoint {
+                watchpoint: watchpoint.clone(),
+                access,
+                address,
+                bank,
+                pc: self
+                    .instruction
+                    .map_or(address, |instruction| instruction.pc),
+                register: io_register_name(address),
+                label: self
+                    .symbols
+                    .as_ref()
+                    .and_then(|symbols| symbols.describe(bank, address)),
+            })
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,260 @@
+use std::ops::{BitOr, BitOrAssign};
+use std::{fs, io, path::Path};
+
+use thiserror::Error;
+
+use super::super::{
+    bus::{MemoryOperation, ReadKind},
+    Gameboy,
+};
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("code/data log size mismatch (got '{size}', expected '{expected}')")]
+    SizeMismatch { size: usize, expected: usize },
+
+    #[error("failed to read the code/data log")]
+    ReadError(#[source] io::Error),
+
+    #[error("failed to write the code/data log")]
+    WriteError(#[source] io::Error),
+}
+
+const ROM_BANK_SIZE: usize = 0x4000;
+
+/// How a byte of the ROM has been accessed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Flags(u8);
+
+impl Flags {
+    pub const NONE: Self = Self(0x00);
+    /// Executed, as an opcode or an operand
+    pub const CODE: Self = Self(0x01);
+    /// Read by an instruction
+    pub const DATA: Self = Self(0x02);
+    /// Reached by a taken jump, call, return or interrupt
+    pub const JUMP_TARGET: Self = Self(0x04);
+    /// Copied to OAM by the DMA
+    pub const DMA: Self = Self(0x08);
+    /// First byte of an instruction
+    pub const OPCODE: Self = Self(0x10);
+
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub fn contains(&self, flags: Self) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    pub fn intersects(&self, flags: Self) -> bool {
+        self.0 & flags.0 != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for Flags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Flags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Number of ROM bytes accessed in each way
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Coverage {
+    pub size: usize,
+    /// Bytes accessed in any way
+    pub accessed: usize,
+    pub code: usize,
+    pub data: usize,
+    pub dma: usize,
+}
+
+/// Code/Data Log: how each byte of the ROM has been accessed, in the banks it has been accessed from.
+///
+/// The log is saved as one byte of flags per ROM byte, and logs of several sessions of the same ROM can be merged.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CodeDataLog {
+    flags: Vec<Flags>,
+    /// Address of the last opcode or operand fetched, to detect the jumps
+    code_address: Option<u16>,
+    was_dispatching: bool,
+}
+
+impl CodeDataLog {
+    pub fn new(rom_size: usize) -> Self {
+        Self {
+            flags: vec![Flags::NONE; rom_size],
+            code_address: None,
+            was_dispatching: false,
+        }
+    }
+
+    /// Empty log for the ROM of the cartridge inserted in the Gameboy
+    pub fn for_gameboy(gameboy: &Gameboy) -> Self {
+        Self::new(gameboy.cartridge.rom().len())
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            flags: bytes.iter().copied().map(Flags::from_bits).collect(),
+            ..Self::new(0)
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.flags.iter().map(Flags::bits).collect()
+    }
+
+    pub fn load<P: ?Sized + AsRef<Path>>(path: &P) -> Result<Self, Error> {
+        Ok(Self::from_bytes(&fs::read(path).map_err(Error::ReadError)?))
+    }
+
+    pub fn save<P: ?Sized + AsRef<Path>>(&self, path: &P) -> Result<(), Error> {
+        fs::write(path, self.to_bytes()).map_err(Error::WriteError)
+    }
+
+    /// Add the accesses logged in another session of the same ROM
+    pub fn merge(&mut self, other: &Self) -> Result<(), Error> {
+        if other.flags.len() != self.flags.len() {
+            return Err(Error::SizeMismatch {
+                size: other.flags.len(),
+                expected: self.flags.len(),
+            });
+        }
+
+        for (flags, other) in self.flags.iter_mut().zip(&other.flags) {
+            *flags |= *other;
+        }
+
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        self.flags.len()
+    }
+
+    /// Flags of a byte by its offset in the ROM
+    pub fn flags(&self, offset: usize) -> Flags {
+        self.flags.get(offset).copied().unwrap_or_default()
+    }
+
+    /// Flags of a byte by its bank and its address in the CPU address space
+    pub fn flags_at(&self, bank: usize, address: u16) -> Flags {
+        self.flags(rom_offset(bank, address))
+    }
+
+    /// Offsets of the bytes with all the given flags
+    pub fn offsets_with(&self, flags: Flags) -> impl Iterator<Item = usize> + '_ {
+        self.flags
+            .iter()
+            .enumerate()
+            .filter(move |(_, byte_flags)| byte_flags.contains(flags))
+            .map(|(offset, _)| offset)
+    }
+
+    pub fn coverage(&self) -> Coverage {
+        self.coverage_of(&self.flags)
+    }
+
+    pub fn bank_coverage(&self, bank: usize) -> Coverage {
+        let start = (bank * ROM_BANK_SIZE).min(self.flags.len());
+        let end = ((bank + 1) * ROM_BANK_SIZE).min(self.flags.len());
+
+        self.coverage_of(&self.flags[start..end])
+    }
+
+    fn coverage_of(&self, flags: &[Flags]) -> Coverage {
+        let count = |kind: Flags| flags.iter().filter(|flags| flags.intersects(kind)).count();
+
+        Coverage {
+            size: flags.len(),
+            accessed: flags.iter().filter(|flags| !flags.is_empty()).count(),
+            code: count(Flags::CODE),
+            data: count(Flags::DATA),
+            dma: count(Flags::DMA),
+        }
+    }
+
+    /// Log the ROM accesses of the M-cycle of the last `Gameboy::tick`, if any
+    pub fn observe(&mut self, gameboy: &Gameboy) {
+        if let Some(memory_operation) = gameboy.last_memory_operation {
+            self.record(gameboy, memory_operation);
+        }
+    }
+
+    fn record(&mut self, gameboy: &Gameboy, memory_operation: MemoryOperation) {
+        let dispatching = std::mem::replace(
+            &mut self.was_dispatching,
+            gameboy.cpu.is_dispatching_interrupt(),
+        );
+
+        match memory_operation {
+            MemoryOperation::Read { address, kind } => {
+                let mut flags = match kind {
+                    ReadKind::Opcode => Flags::CODE | Flags::OPCODE,
+                    ReadKind::Operand => Flags::CODE,
+                    ReadKind::Data => Flags::DATA,
+                };
+
+                if kind != ReadKind::Data {
+                    // The opcode following HALT is fetched again when the CPU wakes up
+                    let sequential = self.code_address.is_some_and(|code_address| {
+                        address == code_address || address == code_address.wrapping_add(1)
+                    });
+
+                    if kind == ReadKind::Opcode && (dispatching || !sequential) {
+                        flags |= Flags::JUMP_TARGET;
+                    }
+                    self.code_address = Some(address);
+                }
+
+                self.log(gameboy, address, flags);
+            }
+            MemoryOperation::ReadIncDec { address } => self.log(gameboy, address, Flags::DATA),
+            // The DMA copies 0xA0 bytes from the page written to the DMA register
+            MemoryOperation::Write {
+                address: 0xFF46,
+                value,
+            } => {
+                let source = (value as u16) << 8;
+                for address in source..source + 0xA0 {
+                    self.log(gameboy, address, Flags::DMA);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn log(&mut self, gameboy: &Gameboy, address: u16, flags: Flags) {
+        if address >= 0x8000 || gameboy.is_boot_rom_mapped(address) {
+            return;
+        }
+
+        if let Some(byte_flags) = self
+            .flags
+            .get_mut(rom_offset(gameboy.bank(address), address))
+        {
+            *byte_flags |= flags;
+        }
+    }
+}
+
+fn rom_offset(bank: usize, address: u16) -> usize {
+    bank * ROM_BANK_SIZE + (address as usize % ROM_BANK_SIZE)
+}
@@ -0,0 +1,228 @@
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use super::super::cpu::registers::Registers;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Error {
+    #[error("unknown register '{0}'")]
+    UnknownRegister(String),
+}
+
+/// Register of the CPU, the 8-bit registers being set with the low byte of the value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Register {
+    A,
+    F,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    AF,
+    BC,
+    DE,
+    HL,
+    SP,
+    PC,
+}
+
+impl Register {
+    pub const ALL: [Self; 14] = [
+        Self::A,
+        Self::F,
+        Self::B,
+        Self::C,
+        Self::D,
+        Self::E,
+        Self::H,
+        Self::L,
+        Self::AF,
+        Self::BC,
+        Self::DE,
+        Self::HL,
+        Self::SP,
+        Self::PC,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::A => "A",
+            Self::F => "F",
+            Self::B => "B",
+            Self::C => "C",
+            Self::D => "D",
+            Self::E => "E",
+            Self::H => "H",
+            Self::L => "L",
+            Self::AF => "AF",
+            Self::BC => "BC",
+            Self::DE => "DE",
+            Self::HL => "HL",
+            Self::SP => "SP",
+            Self::PC => "PC",
+        }
+    }
+
+    pub fn is_16_bit(&self) -> bool {
+        matches!(
+            self,
+            Self::AF | Self::BC | Self::DE | Self::HL | Self::SP | Self::PC
+        )
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Register {
+    type Err = Error;
+
+    /// Parse the name of the register, ignoring the case
+    fn from_str(name: &str) -> Result<Self, Error> {
+        Self::ALL
+            .into_iter()
+            .find(|register| register.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| Error::UnknownRegister(name.to_string()))
+    }
+}
+
+/// Snapshot of the CPU, given by `Gameboy::cpu_state` and restored with `Gameboy::set_cpu_state`.
+///
+/// Between two instructions PC is the address of the next instruction, in the middle of an instruction it has
+/// already been incremented past the bytes fetched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CpuState {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    /// Interrupt Master Enable, a pending EI not being taken into account
+    pub ime: bool,
+    pub halted: bool,
+    /// Whether the CPU is between two instructions, the registers can only be restored consistently there
+    pub at_instruction_boundary: bool,
+}
+
+impl CpuState {
+    pub(in super::super) fn new(
+        registers: &Registers,
+        ime: bool,
+        halted: bool,
+        at_boundary: bool,
+    ) -> Self {
+        Self {
+            a: registers.a(),
+            f: registers.f(),
+            b: registers.b(),
+            c: registers.c(),
+            d: registers.d(),
+            e: registers.e(),
+            h: registers.h(),
+            l: registers.l(),
+            sp: registers.sp(),
+            pc: registers.pc(),
+            ime,
+            halted,
+            at_instruction_boundary: at_boundary,
+        }
+    }
+
+    pub fn register(&self, register: Register) -> u16 {
+        let pair = |msb: u8, lsb: u8| u16::from_be_bytes([msb, lsb]);
+
+        match register {
+            Register::A => self.a.into(),
+            Register::F => self.f.into(),
+            Register::B => self.b.into(),
+            Register::C => self.c.into(),
+            Register::D => self.d.into(),
+            Register::E => self.e.into(),
+            Register::H => self.h.into(),
+            Register::L => self.l.into(),
+            Register::AF => pair(self.a, self.f),
+            Register::BC => pair(self.b, self.c),
+            Register::DE => pair(self.d, self.e),
+            Register::HL => pair(self.h, self.l),
+            Register::SP => self.sp,
+            Register::PC => self.pc,
+        }
+    }
+
+    pub fn zero_flag(&self) -> bool {
+        self.f & 0x80 != 0
+    }
+
+    pub fn subtract_flag(&self) -> bool {
+        self.f & 0x40 != 0
+    }
+
+    pub fn half_carry_flag(&self) -> bool {
+        self.f & 0x20 != 0
+    }
+
+    pub fn carry_flag(&self) -> bool {
+        self.f & 0x10 != 0
+    }
+}
+
+impl fmt::Display for CpuState {
+    /// Registers and flags, like `AF:01B0 BC:0013 DE:00D8 HL:014D SP:FFFE PC:0100 ZNHC:1011 IME:0`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "AF:{:04X} BC:{:04X} DE:{:04X} HL:{:04X} SP:{:04X} PC:{:04X} ZNHC:{}{}{}{} IME:{}",
+            self.register(Register::AF),
+            self.register(Register::BC),
+            self.register(Register::DE),
+            self.register(Register::HL),
+            self.sp,
+            self.pc,
+            self.zero_flag() as u8,
+            self.subtract_flag() as u8,
+            self.half_carry_flag() as u8,
+            self.carry_flag() as u8,
+            self.ime as u8,
+        )?;
+
+        if self.halted {
+            write!(f, " HALTED")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Set a register, the 8-bit registers being set with the low byte of the value
+pub(in super::super) fn set_register(registers: &mut Registers, register: Register, value: u16) {
+    let [_, byte] = value.to_be_bytes();
+
+    match register {
+        Register::A => registers.set_a(byte),
+        Register::F => registers.set_f(byte),
+        Register::B => registers.set_b(byte),
+        Register::C => registers.set_c(byte),
+        Register::D => registers.set_d(byte),
+        Register::E => registers.set_e(byte),
+        Register::H => registers.set_h(byte),
+        Register::L => registers.set_l(byte),
+        Register::AF => registers.set_af(value),
+        Register::BC => registers.set_bc(value),
+        Register::DE => registers.set_de(value),
+        Register::HL => registers.set_hl(value),
+        Register::SP => registers.set_sp(value),
+        Register::PC => registers.set_pc(value),
+    }
+}
@@ -0,0 +1,488 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use thiserror::Error;
+
+use super::super::{bus::MemoryOperation, clock, Gameboy};
+use super::trace::{Entry, Format, Sink, Tracer};
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("invalid trace at line {line}: '{text}'")]
+    InvalidLine { line: usize, text: String },
+
+    #[error("failed to read the trace")]
+    ReadError(#[from] io::Error),
+}
+
+/// Instruction executed by a run, with the memory writes and the duration when the source knows them
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Step {
+    pub entry: Entry,
+    /// T-cycles until the next instruction, including the interrupt dispatch following the instruction
+    pub cycles: Option<usize>,
+    /// Memory writes of the instruction, including the interrupt dispatch following the instruction
+    pub writes: Option<Vec<(u16, u8)>>,
+}
+
+impl Step {
+    /// Parse a line in the Gameboy Doctor format, followed by the optional fields `CY:<cycles>` and
+    /// `W:<address>=<value>,...` written by `Display`. Anything after a `;` is ignored.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.split(';').next().unwrap_or_default();
+
+        let mut entry = Entry {
+            a: 0,
+            f: 0,
+            b: 0,
+            c: 0,
+            d: 0,
+            e: 0,
+            h: 0,
+            l: 0,
+            sp: 0,
+            pc: 0,
+            pcmem: [0; 4],
+            bank: 0,
+            t_cycle: 0,
+            label: None,
+        };
+        let mut cycles = None;
+        let mut writes = None;
+        let mut field_count = 0;
+
+        let byte = |value: &str| u8::from_str_radix(value, 16).ok();
+        let word = |value: &str| u16::from_str_radix(value, 16).ok();
+
+        for field in text.split_whitespace() {
+            let (key, value) = field.split_once(':')?;
+
+            match key {
+                "A" => entry.a = byte(value)?,
+                "F" => entry.f = byte(value)?,
+                "B" => entry.b = byte(value)?,
+                "C" => entry.c = byte(value)?,
+                "D" => entry.d = byte(value)?,
+                "E" => entry.e = byte(value)?,
+                "H" => entry.h = byte(value)?,
+                "L" => entry.l = byte(value)?,
+                "SP" => entry.sp = word(value)?,
+                "PC" => entry.pc = word(value)?,
+                "PCMEM" => {
+                    let bytes: Vec<u8> = value.split(',').map(byte).collect::<Option<_>>()?;
+                    entry.pcmem = bytes.try_into().ok()?;
+                }
+                "CY" => {
+                    cycles = Some(value.parse().ok()?);
+                    continue;
+                }
+                "W" => {
+                    writes = Some(
+                        value
+                            .split(',')
+                            .filter(|write| !write.is_empty())
+                            .map(|write| {
+                                let (address, value) = write.split_once('=')?;
+                                Some((word(address)?, byte(value)?))
+                            })
+                            .collect::<Option<_>>()?,
+                    );
+                    continue;
+                }
+                _ => return None,
+            }
+
+            field_count += 1;
+        }
+
+        (field_count == 11).then_some(Self {
+            entry,
+            cycles,
+            writes,
+        })
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.entry)?;
+
+        if let Some(cycles) = self.cycles {
+            write!(f, " CY:{}", cycles)?;
+        }
+
+        if let Some(writes) = &self.writes {
+            write!(f, " W:{}", format_writes(writes))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn format_writes(writes: &[(u16, u8)]) -> String {
+    writes
+        .iter()
+        .map(|(address, value)| format!("{:04X}={:02X}", address, value))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Instructions executed by a run, one after the other
+pub trait Source {
+    /// Next instruction, `None` once the run is over
+    fn next_step(&mut self) -> Result<Option<Step>, Error>;
+}
+
+/// Trace written by a previous run, like a Gameboy Doctor log or a trace recorded by another version of ugbe
+pub struct TraceReader<R: BufRead> {
+    lines: io::Lines<R>,
+    line: usize,
+}
+
+impl TraceReader<BufReader<File>> {
+    pub fn open<P: ?Sized + AsRef<Path>>(path: &P) -> Result<Self, Error> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> TraceReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+            line: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Source for TraceReader<R> {
+    fn next_step(&mut self) -> Result<Option<Step>, Error> {
+        for text in self.lines.by_ref() {
+            let text = text?;
+            self.line += 1;
+
+            if text.trim().is_empty() {
+                continue;
+            }
+
+            return match Step::parse(&text) {
+                Some(step) => Ok(Some(step)),
+                None => Err(Error::InvalidLine {
+                    line: self.line,
+                    text,
+                }),
+            };
+        }
+
+        Ok(None)
+    }
+}
+
+/// Entries traced by the Gameboy, waiting to be turned into steps
+struct EntryQueue(VecDeque<Entry>);
+
+impl Sink for EntryQueue {
+    fn write(&mut self, entry: &Entry, _line: &str) {
+        self.0.push_back(entry.clone());
+    }
+}
+
+/// Value of LY in the Gameboy Doctor logs, the first line of the VBlank
+const DOCTOR_LY: u8 = 0x90;
+
+/// Run of a Gameboy, with the memory writes and the duration of each instruction.
+///
+/// The run is over when no instruction has been executed for a second, like a CPU stopped or halted without
+/// any interrupt to wake it up.
+pub struct GameboySource {
+    gameboy: Gameboy,
+    pending: Option<Step>,
+}
+
+impl GameboySource {
+    pub fn new(mut gameboy: Gameboy) -> Self {
+        // Only the entries are used, the lines are not formatted
+        let tracer = Tracer::new(Box::new(EntryQueue(VecDeque::new())))
+            .set_format(Format::Custom(Box::new(|_| String::new())));
+        gameboy.start_trace(tracer);
+
+        Self {
+            gameboy,
+            pending: None,
+        }
+    }
+
+    /// Read LY as 0x90 like the Gameboy Doctor logs, which are recorded without emulating the PPU timings.
+    /// Otherwise the first poll of LY would be reported as a divergence.
+    pub fn set_doctor_mode(mut self, doctor_mode: bool) -> Self {
+        self.gameboy
+            .ppu
+            .set_stubbed_ly(doctor_mode.then_some(DOCTOR_LY));
+        self
+    }
+
+    pub fn gameboy(&self) -> &Gameboy {
+        &self.gameboy
+    }
+
+    pub fn into_gameboy(mut self) -> Gameboy {
+        self.gameboy.stop_trace();
+        self.gameboy.ppu.set_stubbed_ly(None);
+        self.gameboy
+    }
+
+    fn take_entry(&mut self) -> Option<Entry> {
+        self.gameboy
+            .tracer_mut()
+            .and_then(|tracer| tracer.sink_mut::<EntryQueue>())
+            .and_then(|queue| queue.0.pop_front())
+    }
+
+    fn record_write(&mut self) {
+        if let (Some(MemoryOperation::Write { address, value }), Some(step)) =
+            (self.gameboy.last_memory_operation, &mut self.pending)
+        {
+            step.writes
+                .get_or_insert_with(Vec::new)
+                .push((address, value));
+        }
+    }
+}
+
+impl Source for GameboySource {
+    fn next_step(&mut self) -> Result<Option<Step>, Error> {
+        for _ in 0..clock::FREQUENCY {
+            self.gameboy.tick();
+
+            // The instruction is traced before the memory operation of the M-cycle it has been decoded in
+            if let Some(entry) = self.take_entry() {
+                let t_cycle = entry.t_cycle;
+                let previous = self.pending.replace(Step {
+                    entry,
+                    cycles: None,
+                    writes: Some(Vec::new()),
+                });
+
+                if let Some(mut step) = previous {
+                    step.cycles = Some(t_cycle.wrapping_sub(step.entry.t_cycle));
+                    self.record_write();
+                    return Ok(Some(step));
+                }
+            }
+
+            self.record_write();
+        }
+
+        Ok(self.pending.take())
+    }
+}
+
+/// Difference between the two runs, for the fields known by both sources
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Difference {
+    Register {
+        name: &'static str,
+        left: u16,
+        right: u16,
+    },
+    Flags {
+        left: u8,
+        right: u8,
+    },
+    /// Bytes at PC, the code executed differs
+    Memory {
+        left: [u8; 4],
+        right: [u8; 4],
+    },
+    Writes {
+        left: Vec<(u16, u8)>,
+        right: Vec<(u16, u8)>,
+    },
+    Cycles {
+        left: usize,
+        right: usize,
+    },
+}
+
+fn format_flags(f: u8) -> String {
+    ['Z', 'N', 'H', 'C']
+        .iter()
+        .enumerate()
+        .map(|(idx, flag)| match f & (0x80 >> idx) {
+            0 => '-',
+            _ => *flag,
+        })
+        .collect()
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Register { name, left, right } if name.len() == 1 => {
+                write!(f, "{}: {:02X} != {:02X}", name, left, right)
+            }
+            Self::Register { name, left, right } => {
+                write!(f, "{}: {:04X} != {:04X}", name, left, right)
+            }
+            Self::Flags { left, right } => write!(
+                f,
+                "F: {:02X} ({}) != {:02X} ({})",
+                left,
+                format_flags(*left),
+                right,
+                format_flags(*right)
+            ),
+            Self::Memory { left, right } => write!(f, "PCMEM: {:02X?} != {:02X?}", left, right),
+            Self::Writes { left, right } => write!(
+                f,
+                "writes: [{}] != [{}]",
+                format_writes(left),
+                format_writes(right)
+            ),
+            Self::Cycles { left, right } => write!(f, "cycles: {} != {}", left, right),
+        }
+    }
+}
+
+/// Differences between two steps, ignoring the fields unknown to one of the sources
+pub fn compare(left: &Step, right: &Step) -> Vec<Difference> {
+    let (l, r) = (&left.entry, &right.entry);
+    let mut differences = Vec::new();
+
+    let registers = [
+        ("A", l.a as u16, r.a as u16),
+        ("B", l.b as u16, r.b as u16),
+        ("C", l.c as u16, r.c as u16),
+        ("D", l.d as u16, r.d as u16),
+        ("E", l.e as u16, r.e as u16),
+        ("H", l.h as u16, r.h as u16),
+        ("L", l.l as u16, r.l as u16),
+        ("SP", l.sp, r.sp),
+        ("PC", l.pc, r.pc),
+    ];
+    for (name, left, right) in registers {
+        if left != right {
+            differences.push(Difference::Register { name, left, right });
+        }
+    }
+
+    if l.f != r.f {
+        differences.push(Difference::Flags {
+            left: l.f,
+            right: r.f,
+        });
+    }
+
+    if l.pcmem != r.pcmem {
+        differences.push(Difference::Memory {
+            left: l.pcmem,
+            right: r.pcmem,
+        });
+    }
+
+    if let (Some(left), Some(right)) = (&left.writes, &right.writes) {
+        if left != right {
+            differences.push(Difference::Writes {
+                left: left.clone(),
+                right: right.clone(),
+            });
+        }
+    }
+
+    if let (Some(left), Some(right)) = (left.cycles, right.cycles) {
+        if left != right {
+            differences.push(Difference::Cycles { left, right });
+        }
+    }
+
+    differences
+}
+
+/// First instruction where two runs differ, with the instructions around it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Divergence {
+    /// Number of instructions executed identically before the divergence
+    pub index: usize,
+    pub differences: Vec<Difference>,
+    /// Last instructions before the divergence, as executed by the left run
+    pub before: Vec<Step>,
+    /// The diverging instruction and the following ones, for each run
+    pub left: Vec<Step>,
+    pub right: Vec<Step>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Divergence at instruction {}:", self.index)?;
+        for difference in &self.differences {
+            writeln!(f, "    {}", difference)?;
+        }
+        writeln!(f)?;
+
+        let first = self.index - self.before.len();
+        for (idx, step) in self.before.iter().enumerate() {
+            writeln!(f, "  {:>8} {}", first + idx, step)?;
+        }
+        for (idx, step) in self.left.iter().enumerate() {
+            writeln!(f, "< {:>8} {}", self.index + idx, step)?;
+        }
+        for (idx, step) in self.right.iter().enumerate() {
+            writeln!(f, "> {:>8} {}", self.index + idx, step)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Run the two sources side by side and stop at the first instruction where they differ, keeping `context`
+/// instructions before and after it.
+///
+/// Return `None` when no difference has been found in `max_steps` instructions or when a run is over.
+pub fn find_divergence(
+    left: &mut dyn Source,
+    right: &mut dyn Source,
+    max_steps: usize,
+    context: usize,
+) -> Result<Option<Divergence>, Error> {
+    let mut before = VecDeque::with_capacity(context);
+
+    for index in 0..max_steps {
+        let (Some(left_step), Some(right_step)) = (left.next_step()?, right.next_step()?) else {
+            return Ok(None);
+        };
+
+        let differences = compare(&left_step, &right_step);
+        if differences.is_empty() {
+            if before.len() == context {
+                before.pop_front();
+            }
+            if context > 0 {
+                before.push_back(left_step);
+            }
+            continue;
+        }
+
+        let mut left_steps = vec![left_step];
+        let mut right_steps = vec![right_step];
+        for _ in 0..context {
+            if let Some(step) = left.next_step()? {
+                left_steps.push(step);
+            }
+            if let Some(step) = right.next_step()? {
+                right_steps.push(step);
+            }
+        }
+
+        return Ok(Some(Divergence {
+            index,
+            differences,
+            before: before.into(),
+            left: left_steps,
+            right: right_steps,
+        }));
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,906 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::OnceLock;
+
+use super::super::cpu::instructions::{
+    InstructionExecutionState, CB_PREFIXED_INSTRUCTIONS_TABLE, INSTRUCTIONS_TABLE,
+};
+use super::super::cpu::registers::Registers;
+use super::cdl::{CodeDataLog, Flags};
+use super::symbols::SymbolTable;
+
+const ROM_BANK_SIZE: usize = 0x4000;
+
+/// Interrupt handlers, where the static disassembly starts in addition to the entry point at 0x0100
+const INTERRUPT_VECTORS: [u16; 5] = [0x40, 0x48, 0x50, 0x58, 0x60];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Register {
+    A,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    AF,
+    BC,
+    DE,
+    HL,
+    SP,
+}
+
+impl Register {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "A" => Self::A,
+            "B" => Self::B,
+            "C" => Self::C,
+            "D" => Self::D,
+            "E" => Self::E,
+            "H" => Self::H,
+            "L" => Self::L,
+            "AF" => Self::AF,
+            "BC" => Self::BC,
+            "DE" => Self::DE,
+            "HL" => Self::HL,
+            "SP" => Self::SP,
+            _ => return None,
+        })
+    }
+
+    fn str(&self) -> &'static str {
+        match self {
+            Self::A => "a",
+            Self::B => "b",
+            Self::C => "c",
+            Self::D => "d",
+            Self::E => "e",
+            Self::H => "h",
+            Self::L => "l",
+            Self::AF => "af",
+            Self::BC => "bc",
+            Self::DE => "de",
+            Self::HL => "hl",
+            Self::SP => "sp",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Condition {
+    NZ,
+    Z,
+    NC,
+    C,
+}
+
+impl Condition {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "NZ" => Self::NZ,
+            "Z" => Self::Z,
+            "NC" => Self::NC,
+            "C" => Self::C,
+            _ => return None,
+        })
+    }
+
+    fn str(&self) -> &'static str {
+        match self {
+            Self::NZ => "nz",
+            Self::Z => "z",
+            Self::NC => "nc",
+            Self::C => "c",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operand {
+    Register(Register),
+    Condition(Condition),
+    /// Bit tested or changed by `BIT`, `RES` and `SET`
+    Bit(u8),
+    Immediate8(u8),
+    Immediate16(u16),
+    /// Signed offset added to SP by `ADD SP, e8`
+    Offset(i8),
+    /// SP plus a signed offset, used by `LD HL, SP+e8`
+    StackOffset(i8),
+    /// Target of a jump, a call or a restart
+    Address(u16),
+    /// Memory at the address held by the register
+    Indirect(Register),
+    /// Memory at the address held by the register, incremented after the access
+    IndirectIncrement(Register),
+    /// Memory at the address held by the register, decremented after the access
+    IndirectDecrement(Register),
+    /// Memory at an absolute address
+    Memory(u16),
+    /// Memory at 0xFF00 plus an immediate
+    HighMemory(u8),
+    /// Memory at 0xFF00 plus C
+    HighMemoryC,
+}
+
+fn fmt_offset(offset: i8) -> String {
+    if offset < 0 {
+        format!("-${:02X}", offset.unsigned_abs())
+    } else {
+        format!("${:02X}", offset)
+    }
+}
+
+impl Operand {
+    fn render(&self, label: &dyn Fn(u16) -> Option<String>) -> String {
+        match self {
+            Self::Register(register) => register.str().into(),
+            Self::Condition(condition) => condition.str().into(),
+            Self::Bit(bit) => bit.to_string(),
+            Self::Immediate8(value) => format!("${:02X}", value),
+            Self::Immediate16(value) => format!("${:04X}", value),
+            Self::Offset(offset) => fmt_offset(*offset),
+            Self::StackOffset(offset) if *offset < 0 => {
+                format!("sp - ${:02X}", offset.unsigned_abs())
+            }
+            Self::StackOffset(offset) => format!("sp + ${:02X}", offset),
+            Self::Address(address) => {
+                label(*address).unwrap_or_else(|| format!("${:04X}", address))
+            }
+            Self::Indirect(register) => format!("[{}]", register.str()),
+            Self::IndirectIncrement(register) => format!("[{}+]", register.str()),
+            Self::IndirectDecrement(register) => format!("[{}-]", register.str()),
+            Self::Memory(address) => match label(*address) {
+                Some(label) => format!("[{}]", label),
+                None => format!("[${:04X}]", address),
+            },
+            Self::HighMemory(offset) => match label(0xFF00 | *offset as u16) {
+                Some(label) => format!("[{}]", label),
+                None => format!("[$FF{:02X}]", offset),
+            },
+            Self::HighMemoryC => "[c]".into(),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(&|_| None))
+    }
+}
+
+/// How the execution continues after an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Flow {
+    /// The next instruction is executed
+    Next,
+    Jump {
+        target: u16,
+        conditional: bool,
+    },
+    /// Call of a function with `CALL` or `RST`
+    Call {
+        target: u16,
+        conditional: bool,
+    },
+    Return {
+        conditional: bool,
+    },
+    /// `JP HL`, the target is only known when executing it
+    IndirectJump,
+    /// Invalid opcode, the CPU locks up
+    Lock,
+}
+
+impl Flow {
+    /// Check if the instruction following this one can be executed after it
+    pub fn falls_through(&self) -> bool {
+        match self {
+            Self::Next | Self::Call { .. } => true,
+            Self::Jump { conditional, .. } | Self::Return { conditional } => *conditional,
+            Self::IndirectJump | Self::Lock => false,
+        }
+    }
+}
+
+/// Operand of the generated tables, before reading the immediate values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum OperandKind {
+    Fixed(Operand),
+    Immediate8,
+    Immediate16,
+    Offset,
+    StackOffset,
+    Memory,
+    HighMemory,
+    /// Absolute target of `JP` and `CALL`
+    Target,
+    /// Relative target of `JR`
+    RelativeTarget,
+}
+
+impl OperandKind {
+    fn size(&self) -> u16 {
+        match self {
+            Self::Fixed(_) => 0,
+            Self::Immediate16 | Self::Memory | Self::Target => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Description of an opcode, built from the instructions executed by the CPU
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Opcode {
+    mnemonic: String,
+    operands: Vec<OperandKind>,
+    cycles: u8,
+    branch_cycles: u8,
+}
+
+impl Opcode {
+    fn new(opcode: u8, cb_prefixed: bool) -> Self {
+        let instruction = match cb_prefixed {
+            true => CB_PREFIXED_INSTRUCTIONS_TABLE[opcode as usize],
+            false => INSTRUCTIONS_TABLE[opcode as usize],
+        };
+
+        // STOP is emulated as a NOP, but it is encoded with a second byte
+        if !cb_prefixed && opcode == 0x10 {
+            return Self {
+                mnemonic: "STOP".into(),
+                operands: vec![OperandKind::Immediate8],
+                cycles: 1,
+                branch_cycles: 1,
+            };
+        }
+
+        let raw_desc = instruction.raw_desc();
+        if raw_desc.starts_with("INVALID") {
+            return Self {
+                mnemonic: "INVALID".into(),
+                operands: Vec::new(),
+                cycles: 1,
+                branch_cycles: 1,
+            };
+        }
+
+        let (mnemonic, operands) = match raw_desc.split_once(' ') {
+            Some((mnemonic, operands)) => (mnemonic, operands.split(", ").collect()),
+            None => (raw_desc.as_ref(), Vec::new()),
+        };
+
+        let is_branch = matches!(mnemonic, "JP" | "JR" | "CALL" | "RET");
+        let operands = operands
+            .iter()
+            .enumerate()
+            .map(|(idx, operand)| {
+                // The condition C is the first operand of the branches taking an address
+                let is_condition = is_branch && (mnemonic == "RET" || idx + 1 < operands.len());
+                OperandKind::parse(mnemonic, operand, is_condition)
+                    .unwrap_or_else(|| panic!("unexpected operand in '{}'", raw_desc))
+            })
+            .collect();
+
+        // Count the memory operations of the instruction with the conditions being false then true, in addition
+        // to the fetches of the opcode and of the CB prefix
+        let prefix_cycles = 1 + cb_prefixed as u8;
+        let count_cycles = |flags: u8| {
+            let mut registers = Registers::new();
+            registers.set_f(flags);
+
+            let mut execution = instruction.create_execution();
+            let mut cycles = prefix_cycles;
+            loop {
+                match execution.next(&mut registers, 0) {
+                    InstructionExecutionState::YieldMemoryOperation(_) => cycles += 1,
+                    InstructionExecutionState::YieldCpuOperation(_) => {}
+                    InstructionExecutionState::Complete => break cycles,
+                }
+            }
+        };
+
+        let (false_cycles, true_cycles) = (count_cycles(0x50), count_cycles(0xA0));
+
+        Self {
+            mnemonic: mnemonic.to_string(),
+            operands,
+            cycles: false_cycles.min(true_cycles),
+            branch_cycles: false_cycles.max(true_cycles),
+        }
+    }
+
+    fn length(&self) -> u16 {
+        1 + self.operands.iter().map(OperandKind::size).sum::<u16>()
+    }
+}
+
+impl OperandKind {
+    fn parse(mnemonic: &str, operand: &str, is_condition: bool) -> Option<Self> {
+        if is_condition {
+            return Some(Self::Fixed(Operand::Condition(Condition::parse(operand)?)));
+        }
+
+        if let Some(register) = Register::parse(operand) {
+            return Some(Self::Fixed(Operand::Register(register)));
+        }
+
+        if let Some(address) = operand.strip_prefix('$') {
+            let address = u16::from_str_radix(address, 16).ok()?;
+            return Some(Self::Fixed(Operand::Address(address)));
+        }
+
+        if let Ok(bit) = operand.parse() {
+            return Some(Self::Fixed(Operand::Bit(bit)));
+        }
+
+        if let Some(deref) = operand
+            .strip_prefix('(')
+            .and_then(|operand| operand.strip_suffix(')'))
+        {
+            return Some(match deref {
+                "u16" => Self::Memory,
+                "FF00+u8" => Self::HighMemory,
+                "FF00+C" => Self::Fixed(Operand::HighMemoryC),
+                _ => {
+                    let (register, operand): (_, fn(Register) -> Operand) =
+                        if let Some(register) = deref.strip_suffix('+') {
+                            (register, Operand::IndirectIncrement)
+                        } else if let Some(register) = deref.strip_suffix('-') {
+                            (register, Operand::IndirectDecrement)
+                        } else {
+                            (deref, Operand::Indirect)
+                        };
+
+                    Self::Fixed(operand(Register::parse(register)?))
+                }
+            });
+        }
+
+        match (mnemonic, operand) {
+            ("JP" | "CALL", "u16") => Some(Self::Target),
+            ("JR", "i8") => Some(Self::RelativeTarget),
+            (_, "u8") => Some(Self::Immediate8),
+            (_, "u16") => Some(Self::Immediate16),
+            (_, "i8") => Some(Self::Offset),
+            (_, "SP+i8") => Some(Self::StackOffset),
+            _ => None,
+        }
+    }
+}
+
+fn opcodes() -> &'static [Vec<Opcode>; 2] {
+    static OPCODES: OnceLock<[Vec<Opcode>; 2]> = OnceLock::new();
+
+    OPCODES.get_or_init(|| {
+        [false, true].map(|cb_prefixed| {
+            (0..=0xFF)
+                .map(|opcode| Opcode::new(opcode, cb_prefixed))
+                .collect()
+        })
+    })
+}
+
+/// Instruction decoded from memory
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Instruction {
+    address: u16,
+    bytes: Vec<u8>,
+    mnemonic: &'static str,
+    operands: Vec<Operand>,
+    cycles: u8,
+    branch_cycles: u8,
+    flow: Flow,
+}
+
+impl Instruction {
+    /// Address of the first byte of the instruction
+    pub fn address(&self) -> u16 {
+        self.address
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn length(&self) -> u16 {
+        self.bytes.len() as u16
+    }
+
+    /// Mnemonic in uppercase, `INVALID` for the opcodes locking the CPU
+    pub fn mnemonic(&self) -> &'static str {
+        self.mnemonic
+    }
+
+    pub fn operands(&self) -> &[Operand] {
+        &self.operands
+    }
+
+    /// Duration in M-cycles, when the condition of a conditional branch is false
+    pub fn cycles(&self) -> u8 {
+        self.cycles
+    }
+
+    /// Duration in M-cycles when the branch is taken, the same as `cycles` for the other instructions
+    pub fn branch_cycles(&self) -> u8 {
+        self.branch_cycles
+    }
+
+    pub fn flow(&self) -> Flow {
+        self.flow
+    }
+
+    /// Address jumped to or called, if known without executing the instruction
+    pub fn branch_target(&self) -> Option<u16> {
+        match self.flow {
+            Flow::Jump { target, .. } | Flow::Call { target, .. } => Some(target),
+            _ => None,
+        }
+    }
+
+    /// RGBDS syntax of the instruction, using the given function to name the addresses
+    pub fn render(&self, label: &dyn Fn(u16) -> Option<String>) -> String {
+        let mnemonic = match self.mnemonic {
+            "INVALID" => return format!("db ${:02X}", self.bytes[0]),
+            // RGBDS only emits `STOP` followed by a 0x00
+            "STOP" if self.bytes[1] != 0x00 => {
+                return format!("db ${:02X}, ${:02X}", self.bytes[0], self.bytes[1])
+            }
+            "STOP" => return "stop".into(),
+            "LD" if self.operands.iter().any(|operand| {
+                matches!(operand, Operand::HighMemory(_) | Operand::HighMemoryC)
+            }) =>
+            {
+                "ldh".into()
+            }
+            mnemonic => mnemonic.to_ascii_lowercase(),
+        };
+
+        let operands: Vec<_> = self
+            .operands
+            .iter()
+            .map(|operand| operand.render(label))
+            .collect();
+
+        if operands.is_empty() {
+            mnemonic
+        } else {
+            format!("{} {}", mnemonic, operands.join(", "))
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(&|_| None))
+    }
+}
+
+/// Decode the instruction at the start of the bytes, located at the given address. Return `None` if the bytes
+/// end before the instruction.
+pub fn decode(bytes: &[u8], address: u16) -> Option<Instruction> {
+    let (cb_prefixed, opcode_size) = match bytes.first()? {
+        0xCB => (true, 1),
+        _ => (false, 0),
+    };
+
+    let opcode = &opcodes()[cb_prefixed as usize][*bytes.get(opcode_size)? as usize];
+    let length = opcode_size as u16 + opcode.length();
+    let bytes = bytes.get(..length as usize)?.to_vec();
+
+    let immediate = &bytes[1 + opcode_size..];
+    let immediate_u8 = || immediate[0];
+    let immediate_u16 = || u16::from_le_bytes([immediate[0], immediate[1]]);
+    let next_address = address.wrapping_add(length);
+
+    let operands: Vec<_> = opcode
+        .operands
+        .iter()
+        .map(|operand| match operand {
+            OperandKind::Fixed(operand) => *operand,
+            OperandKind::Immediate8 => Operand::Immediate8(immediate_u8()),
+            OperandKind::Immediate16 => Operand::Immediate16(immediate_u16()),
+            OperandKind::Offset => Operand::Offset(immediate_u8() as i8),
+            OperandKind::StackOffset => Operand::StackOffset(immediate_u8() as i8),
+            OperandKind::Memory => Operand::Memory(immediate_u16()),
+            OperandKind::HighMemory => Operand::HighMemory(immediate_u8()),
+            OperandKind::Target => Operand::Address(immediate_u16()),
+            OperandKind::RelativeTarget => {
+                Operand::Address(next_address.wrapping_add_signed(immediate_u8() as i8 as i16))
+            }
+        })
+        .collect();
+
+    let conditional = operands
+        .iter()
+        .any(|operand| matches!(operand, Operand::Condition(_)));
+    let target = operands.iter().find_map(|operand| match operand {
+        Operand::Address(address) => Some(*address),
+        _ => None,
+    });
+
+    let flow = match (opcode.mnemonic.as_str(), target) {
+        ("JP" | "JR", Some(target)) => Flow::Jump {
+            target,
+            conditional,
+        },
+        ("JP", None) => Flow::IndirectJump,
+        ("CALL" | "RST", Some(target)) => Flow::Call {
+            target,
+            conditional,
+        },
+        ("RET" | "RETI", _) => Flow::Return { conditional },
+        ("INVALID", _) => Flow::Lock,
+        _ => Flow::Next,
+    };
+
+    Some(Instruction {
+        address,
+        bytes,
+        mnemonic: &opcode.mnemonic,
+        operands,
+        cycles: opcode.cycles,
+        branch_cycles: opcode.branch_cycles,
+        flow,
+    })
+}
+
+/// Decode the instructions one after the other, the bytes not forming a whole instruction at the end are ignored
+pub fn disassemble(bytes: &[u8], address: u16) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+
+    while let Some(instruction) = decode(&bytes[offset..], address.wrapping_add(offset as u16)) {
+        offset += instruction.bytes.len();
+        instructions.push(instruction);
+    }
+
+    instructions
+}
+
+/// Decode a ROM bank as if it was mapped in memory, at 0x0000 for the bank 0 and at 0x4000 for the others
+pub fn disassemble_bank(rom: &[u8], bank: usize) -> Vec<Instruction> {
+    let start = (bank * ROM_BANK_SIZE).min(rom.len());
+    let end = ((bank + 1) * ROM_BANK_SIZE).min(rom.len());
+
+    disassemble(&rom[start..end], bank_address(bank, 0))
+}
+
+fn bank_address(bank: usize, offset: usize) -> u16 {
+    let base = if bank == 0 { 0x0000 } else { 0x4000 };
+    base + (offset % ROM_BANK_SIZE) as u16
+}
+
+/// Static disassembly of a whole ROM, following the jumps and calls from the entry point and the interrupt
+/// handlers. The bytes not reached are considered as data.
+///
+/// The bank switches aren't followed: the code of the bank 0 jumping in 0x4000-0x7FFF is considered to target
+/// the bank 1, and the code of the other banks to stay in their own bank.
+#[derive(Debug, Clone)]
+pub struct RomDisassembly<'a> {
+    rom: &'a [u8],
+    /// Instructions indexed by their offset in the ROM
+    instructions: BTreeMap<usize, Instruction>,
+    /// Offsets in the ROM of the instructions jumped to or called
+    labels: BTreeSet<usize>,
+    symbols: Option<&'a SymbolTable>,
+}
+
+impl<'a> RomDisassembly<'a> {
+    pub fn new(rom: &'a [u8]) -> Self {
+        Self::traverse(rom, None)
+    }
+
+    /// Disassembly also starting from the instructions executed in a logged session, the bytes only read as data
+    /// not being decoded as instructions
+    pub fn from_code_data_log(rom: &'a [u8], log: &CodeDataLog) -> Self {
+        Self::traverse(rom, Some(log))
+    }
+
+    fn traverse(rom: &'a [u8], log: Option<&CodeDataLog>) -> Self {
+        let mut disassembly = Self {
+            rom,
+            instructions: BTreeMap::new(),
+            labels: BTreeSet::new(),
+            symbols: None,
+        };
+
+        let mut covered = vec![false; rom.len()];
+        let mut targets = Vec::new();
+        let mut queue: Vec<_> = std::iter::once(0x100)
+            .chain(INTERRUPT_VECTORS)
+            .map(|address| (0, address))
+            .collect();
+
+        let is_data = |offset: usize| {
+            log.is_some_and(|log| {
+                let flags = log.flags(offset);
+                flags.contains(Flags::DATA) && !flags.contains(Flags::CODE)
+            })
+        };
+
+        if let Some(log) = log {
+            queue.extend(
+                log.offsets_with(Flags::OPCODE)
+                    .filter(|offset| *offset < rom.len())
+                    .map(|offset| {
+                        let bank = offset / ROM_BANK_SIZE;
+                        (bank, bank_address(bank, offset))
+                    }),
+            );
+            targets.extend(log.offsets_with(Flags::JUMP_TARGET));
+        }
+
+        while let Some((mut bank, mut address)) = queue.pop() {
+            while let Some((next_bank, offset)) = disassembly.rom_offset(bank, address) {
+                bank = next_bank;
+
+                if covered[offset] {
+                    break;
+                }
+
+                let bank_end = ((bank + 1) * ROM_BANK_SIZE).min(rom.len());
+                let Some(instruction) = decode(&rom[offset..bank_end], address) else {
+                    break;
+                };
+
+                let end = offset + instruction.bytes.len();
+                if covered[offset..end].iter().any(|covered| *covered) || (offset..end).any(is_data)
+                {
+                    break;
+                }
+                covered[offset..end].fill(true);
+
+                if let Some(target) = instruction.branch_target() {
+                    if let Some((target_bank, target_offset)) = disassembly.rom_offset(bank, target)
+                    {
+                        targets.push(target_offset);
+                        queue.push((target_bank, target));
+                    }
+                }
+
+                let falls_through = instruction.flow.falls_through();
+                address = address.wrapping_add(instruction.length());
+                disassembly.instructions.insert(offset, instruction);
+
+                if !falls_through || address >= 0x8000 {
+                    break;
+                }
+            }
+        }
+
+        // Jumps in the middle of an instruction can't be labelled
+        disassembly.labels = targets
+            .into_iter()
+            .filter(|offset| disassembly.instructions.contains_key(offset))
+            .collect();
+
+        disassembly
+    }
+
+    /// Name the addresses with the labels of the program instead of the generated `label_bank_address`
+    pub fn set_symbols(self, symbols: &'a SymbolTable) -> Self {
+        Self {
+            symbols: Some(symbols),
+            ..self
+        }
+    }
+
+    /// Bank and offset in the ROM of an address accessed by code running in the given bank
+    fn rom_offset(&self, bank: usize, address: u16) -> Option<(usize, usize)> {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            0x4000..=0x7FFF => bank.max(1),
+            _ => return None,
+        };
+
+        let offset = bank * ROM_BANK_SIZE + (address as usize % ROM_BANK_SIZE);
+        (offset < self.rom.len()).then_some((bank, offset))
+    }
+
+    /// Instructions found in a bank, in the order of their addresses
+    pub fn instructions(&self, bank: usize) -> impl Iterator<Item = &Instruction> {
+        self.instructions
+            .range(bank * ROM_BANK_SIZE..(bank + 1) * ROM_BANK_SIZE)
+            .map(|(_, instruction)| instruction)
+    }
+
+    /// Check if the offset is within the operands of an instruction, where no label can be defined
+    fn is_in_instruction(&self, offset: usize) -> bool {
+        self.instructions
+            .range(..offset)
+            .next_back()
+            .is_some_and(|(start, instruction)| offset < start + instruction.bytes.len())
+    }
+
+    /// Label defined at an offset of the ROM
+    fn label(&self, offset: usize) -> Option<String> {
+        let bank = offset / ROM_BANK_SIZE;
+        let address = bank_address(bank, offset);
+
+        match self
+            .symbols
+            .and_then(|symbols| symbols.label(bank, address))
+        {
+            Some(label) if !self.is_in_instruction(offset) => Some(label.to_string()),
+            _ if self.labels.contains(&offset) => {
+                Some(format!("label_{:03X}_{:04X}", bank, address))
+            }
+            _ => None,
+        }
+    }
+
+    /// Label of an address outside of the ROM, defined as a constant in the listing
+    fn ram_label(&self, address: u16) -> Option<&'a str> {
+        let bank = match address {
+            0xD000..=0xDFFF => 1,
+            _ => 0,
+        };
+
+        // The local labels can't be defined as constants
+        self.symbols?
+            .label(bank, address)
+            .filter(|label| !label.contains('.'))
+    }
+}
+
+impl fmt::Display for RomDisassembly<'_> {
+    /// RGBDS source assembling back to the ROM
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let constants = RefCell::new(BTreeMap::new());
+        let mut lines = Vec::new();
+
+        for (bank, bank_bytes) in self.rom.chunks(ROM_BANK_SIZE).enumerate() {
+            if bank == 0 {
+                lines.push("SECTION \"ROM Bank $000\", ROM0[$0000]".to_string());
+            } else {
+                lines.push(format!(
+                    "\nSECTION \"ROM Bank ${:03X}\", ROMX[$4000], BANK[${:X}]",
+                    bank, bank
+                ));
+            }
+
+            let label = |address| match self.rom_offset(bank, address) {
+                Some((_, offset)) => self.label(offset),
+                None if address >= 0x8000 => {
+                    let label = self.ram_label(address)?;
+                    constants.borrow_mut().insert(label, address);
+                    Some(label.to_string())
+                }
+                None => None,
+            };
+
+            let mut offset = bank * ROM_BANK_SIZE;
+            let bank_end = offset + bank_bytes.len();
+            while offset < bank_end {
+                if let Some(label) = self.label(offset) {
+                    lines.push(format!("{}:", label));
+                }
+
+                if let Some(instruction) = self.instructions.get(&offset) {
+                    lines.push(format!("    {}", instruction.render(&label)));
+                    offset += instruction.bytes.len();
+                    continue;
+                }
+
+                // Data up to the next instruction or label, 16 bytes per line
+                let next_instruction = self
+                    .instructions
+                    .range(offset..bank_end)
+                    .next()
+                    .map_or(bank_end, |(next, _)| *next);
+                let data_end = (offset + 1..next_instruction.min(offset + 16))
+                    .find(|next| self.label(*next).is_some())
+                    .unwrap_or(next_instruction.min(offset + 16));
+
+                let data: Vec<_> = self.rom[offset..data_end]
+                    .iter()
+                    .map(|byte| format!("${:02X}", byte))
+                    .collect();
+                lines.push(format!("    db {}", data.join(", ")));
+                offset = data_end;
+            }
+        }
+
+        let constants = constants.into_inner();
+        for (label, address) in &constants {
+            writeln!(f, "DEF {} EQU ${:04X}", label, address)?;
+        }
+        if !constants.is_empty() {
+            writeln!(f)?;
+        }
+
+        for line in lines {
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, disassemble, Flow, RomDisassembly};
+    use crate::gameboy::tests::{build_test_rom, DEBUGGER_PROGRAM};
+
+    #[test]
+    fn disassembler_decode() {
+        let expected: &[(&[u8], &str, u8, u8)] = &[
+            (&[0x3E, 0x12], "ld a, $12", 2, 2),
+            (&[0x20, 0xFE], "jr nz, $0150", 2, 3),
+            (&[0xCD, 0x60, 0x01], "call $0160", 6, 6),
+            (&[0xC0], "ret nz", 2, 5),
+            (&[0xC9], "ret", 4, 4),
+            (&[0xD9], "reti", 4, 4),
+            (&[0xFF], "rst $0038", 4, 4),
+            (&[0xE0, 0x07], "ldh [$FF07], a", 3, 3),
+            (&[0xE2], "ldh [c], a", 2, 2),
+            (&[0x2A], "ld a, [hl+]", 2, 2),
+            (&[0x08, 0x00, 0xC0], "ld [$C000], sp", 5, 5),
+            (&[0xF8, 0xFD], "ld hl, sp - $03", 3, 3),
+            (&[0xE8, 0x04], "add sp, $04", 4, 4),
+            (&[0x07], "rlca", 1, 1),
+            (&[0xCB, 0x7F], "bit 7, a", 2, 2),
+            (&[0xCB, 0x06], "rlc [hl]", 4, 4),
+            (&[0x10, 0x00], "stop", 1, 1),
+            (&[0xD3], "db $D3", 1, 1),
+        ];
+
+        for (bytes, text, cycles, branch_cycles) in expected {
+            let instruction = decode(bytes, 0x150).unwrap();
+            assert_eq!(instruction.to_string(), *text);
+            assert_eq!(instruction.bytes(), *bytes);
+            assert_eq!(instruction.cycles(), *cycles, "{}", text);
+            assert_eq!(instruction.branch_cycles(), *branch_cycles, "{}", text);
+        }
+
+        assert_eq!(
+            decode(&[0x20, 0xFE], 0x150).unwrap().flow(),
+            Flow::Jump {
+                target: 0x150,
+                conditional: true
+            }
+        );
+        assert_eq!(decode(&[0xFF], 0x150).unwrap().branch_target(), Some(0x38));
+        assert_eq!(decode(&[0xD3], 0x150).unwrap().flow(), Flow::Lock);
+        assert_eq!(decode(&[0xCD, 0x60], 0x150), None);
+
+        // Every opcode of the tables can be decoded
+        for opcode in 0..=0xFF {
+            assert!(decode(&[opcode, 0x00, 0x00], 0x0000).is_some());
+            assert!(decode(&[0xCB, opcode], 0x0000).is_some());
+        }
+
+        let instructions = disassemble(&DEBUGGER_PROGRAM, 0x150);
+        assert_eq!(instructions[2].to_string(), "call $0160");
+        assert_eq!(instructions[4].address(), 0x15B);
+    }
+
+    #[test]
+    fn disassembler_rom() {
+        let rom = build_test_rom(&DEBUGGER_PROGRAM);
+        let disassembly = RomDisassembly::new(&rom);
+        let listing = disassembly.to_string();
+
+        assert!(listing.starts_with("SECTION \"ROM Bank $000\", ROM0[$0000]\n"));
+        assert!(listing.contains("\nSECTION \"ROM Bank $001\", ROMX[$4000], BANK[$1]\n"));
+        assert!(listing.contains("    nop\n    jp label_000_0150\n    db $CE, $ED, $66, $66,"));
+        assert!(listing.contains("    call label_000_0160\n    ld a, [$C000]\n"));
+        assert!(listing.contains("label_000_015B:\n    jr label_000_015B\n    db $00, $00, $00\n"));
+        assert!(listing.contains("label_000_0160:\n    nop\n    inc a\n    ret\n"));
+
+        // All the bytes of the ROM are either code or data
+        let code_size: usize = (0..2)
+            .flat_map(|bank| disassembly.instructions(bank))
+            .map(|instruction| instruction.bytes().len())
+            .sum();
+        let data_size: usize = listing
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("db "))
+            .map(|data| data.split(", ").count())
+            .sum();
+        assert_eq!(code_size + data_size, rom.len());
+    }
+}
@@ -0,0 +1,428 @@
+use super::super::spu::Spu;
+
+fn bit(value: u8, pos: u8) -> bool {
+    (value >> pos) & 1 != 0
+}
+
+/// P1: selection of the buttons and state of the selected ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Joypad {
+    pub buttons_selected: bool,
+    pub directions_selected: bool,
+    /// Low nibble of P1, a bit is 0 when its button is pressed
+    pub lines: u8,
+}
+
+impl From<u8> for Joypad {
+    fn from(value: u8) -> Self {
+        Self {
+            buttons_selected: !bit(value, 5),
+            directions_selected: !bit(value, 4),
+            lines: value & 0x0F,
+        }
+    }
+}
+
+/// SC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SerialControl {
+    pub transfer_enabled: bool,
+    pub internal_clock: bool,
+}
+
+impl From<u8> for SerialControl {
+    fn from(value: u8) -> Self {
+        Self {
+            transfer_enabled: bit(value, 7),
+            internal_clock: bit(value, 0),
+        }
+    }
+}
+
+/// TAC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimerControl {
+    pub enabled: bool,
+    pub clock_select: u8,
+}
+
+impl TimerControl {
+    /// T-cycles between two increments of TIMA
+    pub fn period(&self) -> usize {
+        match self.clock_select {
+            0b00 => 1024,
+            0b01 => 16,
+            0b10 => 64,
+            _ => 256,
+        }
+    }
+}
+
+impl From<u8> for TimerControl {
+    fn from(value: u8) -> Self {
+        Self {
+            enabled: bit(value, 2),
+            clock_select: value & 0b11,
+        }
+    }
+}
+
+/// IE and IF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Interrupts {
+    pub vblank: bool,
+    pub stat: bool,
+    pub timer: bool,
+    pub serial: bool,
+    pub joypad: bool,
+}
+
+impl From<u8> for Interrupts {
+    fn from(value: u8) -> Self {
+        Self {
+            vblank: bit(value, 0),
+            stat: bit(value, 1),
+            timer: bit(value, 2),
+            serial: bit(value, 3),
+            joypad: bit(value, 4),
+        }
+    }
+}
+
+/// NRx2: volume envelope of the pulse and noise channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Envelope {
+    pub initial_volume: u8,
+    pub increase: bool,
+    /// 0 disables the envelope
+    pub pace: u8,
+}
+
+impl Envelope {
+    /// The DAC of the channel is off when the envelope can only produce silence
+    pub fn dac_enabled(&self) -> bool {
+        self.initial_volume != 0 || self.increase
+    }
+}
+
+impl From<u8> for Envelope {
+    fn from(value: u8) -> Self {
+        Self {
+            initial_volume: value >> 4,
+            increase: bit(value, 3),
+            pace: value & 0b111,
+        }
+    }
+}
+
+/// NR10: frequency sweep of the channel 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sweep {
+    pub pace: u8,
+    pub decrease: bool,
+    pub step: u8,
+}
+
+impl From<u8> for Sweep {
+    fn from(value: u8) -> Self {
+        Self {
+            pace: (value >> 4) & 0b111,
+            decrease: bit(value, 3),
+            step: value & 0b111,
+        }
+    }
+}
+
+/// NR10-NR14 and NR21-NR24
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PulseChannel {
+    /// Only the channel 1 has a sweep
+    pub sweep: Option<Sweep>,
+    /// Duty cycle, 0 for 12.5% up to 3 for 75%
+    pub duty: u8,
+    pub envelope: Envelope,
+    /// 11-bit period of NRx3 and NRx4, as updated by the sweep
+    pub period: u16,
+    /// Length steps left, out of 64
+    pub length_timer: u16,
+    pub length_enabled: bool,
+}
+
+/// NR30-NR34
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WaveChannel {
+    pub dac_enabled: bool,
+    /// 0 for mute, 1 for 100%, 2 for 50% and 3 for 25%
+    pub output_level: u8,
+    /// 11-bit period of NR33 and NR34
+    pub period: u16,
+    /// Length steps left, out of 256
+    pub length_timer: u16,
+    pub length_enabled: bool,
+}
+
+/// NR41-NR44
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NoiseChannel {
+    pub envelope: Envelope,
+    pub clock_shift: u8,
+    /// The LFSR is 7 bits wide instead of 15
+    pub short_lfsr: bool,
+    pub clock_divider: u8,
+    /// Length steps left, out of 64
+    pub length_timer: u16,
+    pub length_enabled: bool,
+}
+
+/// NR50: master volume and VIN panning
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MasterVolume {
+    pub vin_left: bool,
+    pub left_volume: u8,
+    pub vin_right: bool,
+    pub right_volume: u8,
+}
+
+impl From<u8> for MasterVolume {
+    fn from(value: u8) -> Self {
+        Self {
+            vin_left: bit(value, 7),
+            left_volume: (value >> 4) & 0b111,
+            vin_right: bit(value, 3),
+            right_volume: value & 0b111,
+        }
+    }
+}
+
+/// NR51: the channels 1 to 4 sent to each side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Panning {
+    pub left: [bool; 4],
+    pub right: [bool; 4],
+}
+
+impl From<u8> for Panning {
+    fn from(value: u8) -> Self {
+        Self {
+            left: std::array::from_fn(|channel| bit(value, channel as u8 + 4)),
+            right: std::array::from_fn(|channel| bit(value, channel as u8)),
+        }
+    }
+}
+
+/// NR52
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SoundControl {
+    pub enabled: bool,
+    /// Whether the channels 1 to 4 are playing
+    pub channels_on: [bool; 4],
+}
+
+impl From<u8> for SoundControl {
+    fn from(value: u8) -> Self {
+        Self {
+            enabled: bit(value, 7),
+            channels_on: std::array::from_fn(|channel| bit(value, channel as u8)),
+        }
+    }
+}
+
+/// LCDC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LcdControl {
+    pub lcd_enabled: bool,
+    /// The window uses the tile map at 0x9C00 instead of 0x9800
+    pub window_tile_map_9c00: bool,
+    pub window_enabled: bool,
+    /// The background and the window use the tiles at 0x8000 instead of 0x8800
+    pub tile_data_8000: bool,
+    /// The background uses the tile map at 0x9C00 instead of 0x9800
+    pub bg_tile_map_9c00: bool,
+    /// The sprites are 8x16 instead of 8x8
+    pub tall_sprites: bool,
+    pub sprites_enabled: bool,
+    pub bg_window_enabled: bool,
+}
+
+impl From<u8> for LcdControl {
+    fn from(value: u8) -> Self {
+        Self {
+            lcd_enabled: bit(value, 7),
+            window_tile_map_9c00: bit(value, 6),
+            window_enabled: bit(value, 5),
+            tile_data_8000: bit(value, 4),
+            bg_tile_map_9c00: bit(value, 3),
+            tall_sprites: bit(value, 2),
+            sprites_enabled: bit(value, 1),
+            bg_window_enabled: bit(value, 0),
+        }
+    }
+}
+
+/// Mode of the PPU, as given by STAT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PpuMode {
+    HBlank,
+    VBlank,
+    OamScan,
+    Drawing,
+}
+
+/// STAT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LcdStatus {
+    pub lyc_interrupt: bool,
+    pub oam_scan_interrupt: bool,
+    pub vblank_interrupt: bool,
+    pub hblank_interrupt: bool,
+    pub lyc_equal: bool,
+    pub mode: PpuMode,
+}
+
+impl From<u8> for LcdStatus {
+    fn from(value: u8) -> Self {
+        Self {
+            lyc_interrupt: bit(value, 6),
+            oam_scan_interrupt: bit(value, 5),
+            vblank_interrupt: bit(value, 4),
+            hblank_interrupt: bit(value, 3),
+            lyc_equal: bit(value, 2),
+            mode: match value & 0b11 {
+                0 => PpuMode::HBlank,
+                1 => PpuMode::VBlank,
+                2 => PpuMode::OamScan,
+                _ => PpuMode::Drawing,
+            },
+        }
+    }
+}
+
+/// BGP, OBP0 and OBP1: shade of each of the 4 colors, from 0 (white) to 3 (black)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Palette(pub [u8; 4]);
+
+impl From<u8> for Palette {
+    fn from(value: u8) -> Self {
+        Self(std::array::from_fn(|color| (value >> (color * 2)) & 0b11))
+    }
+}
+
+/// Decoded view of the I/O registers, given by `Gameboy::io_registers`.
+///
+/// The values are the ones read by the CPU, except for the write-only length timers and periods of the sound channels
+/// which are taken from the state of the SPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IoRegisters {
+    pub p1: Joypad,
+    pub sb: u8,
+    pub sc: SerialControl,
+    pub div: u8,
+    pub tima: u8,
+    pub tma: u8,
+    pub tac: TimerControl,
+    pub interrupt_flags: Interrupts,
+    pub channel1: PulseChannel,
+    pub channel2: PulseChannel,
+    pub channel3: WaveChannel,
+    pub channel4: NoiseChannel,
+    pub nr50: MasterVolume,
+    pub nr51: Panning,
+    pub nr52: SoundControl,
+    pub wave_ram: [u8; 16],
+    pub lcdc: LcdControl,
+    pub stat: LcdStatus,
+    pub scy: u8,
+    pub scx: u8,
+    pub ly: u8,
+    pub lyc: u8,
+    pub bgp: Palette,
+    pub obp0: Palette,
+    pub obp1: Palette,
+    pub wy: u8,
+    pub wx: u8,
+    pub boot_rom_enabled: bool,
+    /// RP, the infrared port
+    pub rp: u8,
+    pub interrupt_enable: Interrupts,
+}
+
+impl IoRegisters {
+    /// Decode the registers from the value read at their address, and the write-only sound bits from the SPU
+    pub fn read(spu: &Spu, mut read: impl FnMut(u16) -> u8) -> Self {
+        let nr43 = read(0xFF22);
+        let periods = spu.periods();
+        let length_timers = spu.length_timers();
+
+        Self {
+            p1: read(0xFF00).into(),
+            sb: read(0xFF01),
+            sc: read(0xFF02).into(),
+            div: read(0xFF04),
+            tima: read(0xFF05),
+            tma: read(0xFF06),
+            tac: read(0xFF07).into(),
+            interrupt_flags: read(0xFF0F).into(),
+            channel1: PulseChannel {
+                sweep: Some(read(0xFF10).into()),
+                duty: read(0xFF11) >> 6,
+                envelope: read(0xFF12).into(),
+                period: periods[0],
+                length_timer: length_timers[0],
+                length_enabled: bit(read(0xFF14), 6),
+            },
+            channel2: PulseChannel {
+                sweep: None,
+                duty: read(0xFF16) >> 6,
+                envelope: read(0xFF17).into(),
+                period: periods[1],
+                length_timer: length_timers[1],
+                length_enabled: bit(read(0xFF19), 6),
+            },
+            channel3: WaveChannel {
+                dac_enabled: bit(read(0xFF1A), 7),
+                output_level: (read(0xFF1C) >> 5) & 0b11,
+                period: periods[2],
+                length_timer: length_timers[2],
+                length_enabled: bit(read(0xFF1E), 6),
+            },
+            channel4: NoiseChannel {
+                envelope: read(0xFF21).into(),
+                clock_shift: nr43 >> 4,
+                short_lfsr: bit(nr43, 3),
+                clock_divider: nr43 & 0b111,
+                length_timer: length_timers[3],
+                length_enabled: bit(read(0xFF23), 6),
+            },
+            nr50: read(0xFF24).into(),
+            nr51: read(0xFF25).into(),
+            nr52: read(0xFF26).into(),
+            wave_ram: std::array::from_fn(|offset| read(0xFF30 + offset as u16)),
+            lcdc: read(0xFF40).into(),
+            stat: read(0xFF41).into(),
+            scy: read(0xFF42),
+            scx: read(0xFF43),
+            ly: read(0xFF44),
+            lyc: read(0xFF45),
+            bgp: read(0xFF47).into(),
+            obp0: read(0xFF48).into(),
+            obp1: read(0xFF49).into(),
+            wy: read(0xFF4A),
+            wx: read(0xFF4B),
+            boot_rom_enabled: bit(read(0xFF50), 0),
+            rp: read(0xFF56),
+            interrupt_enable: read(0xFFFF).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LcdStatus, PpuMode};
+
+    #[test]
+    fn lcd_status() {
+        let stat = LcdStatus::from(0b0100_0110);
+        assert!(stat.lyc_interrupt && stat.lyc_equal && !stat.hblank_interrupt);
+        assert_eq!(stat.mode, PpuMode::OamScan);
+    }
+}
@@ -0,0 +1,302 @@
+use std::ops::{Deref, DerefMut, RangeInclusive};
+
+use thiserror::Error;
+
+use super::super::Gameboy;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Error {
+    #[error("offset '{offset:#06X}' of the bank '{bank}' is out of the {region}")]
+    OutOfBounds {
+        region: Region,
+        bank: usize,
+        offset: usize,
+    },
+
+    #[error("the {0} is locked by the PPU")]
+    Locked(Region),
+
+    #[error("the {0} is read-only")]
+    ReadOnly(Region),
+
+    #[error("writing the I/O register '{0:#06X}' would have side effects")]
+    IoRegister(u16),
+
+    #[error("no memory is mapped at '{0:#06X}'")]
+    Unmapped(u16),
+}
+
+/// Memory of the Gameboy, accessed by bank and offset instead of through the CPU address space
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Region {
+    BootRom,
+    /// Cartridge ROM, in banks of 0x4000 bytes
+    Rom,
+    VideoRam,
+    /// Cartridge RAM, in banks of 0x2000 bytes
+    CartridgeRam,
+    /// Work RAM, in banks of 0x1000 bytes
+    WorkRam,
+    Oam,
+    HighRam,
+}
+
+impl Region {
+    pub const ALL: [Self; 7] = [
+        Self::BootRom,
+        Self::Rom,
+        Self::VideoRam,
+        Self::CartridgeRam,
+        Self::WorkRam,
+        Self::Oam,
+        Self::HighRam,
+    ];
+}
+
+impl std::fmt::Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::BootRom => "boot ROM",
+            Self::Rom => "ROM",
+            Self::VideoRam => "VRAM",
+            Self::CartridgeRam => "cartridge RAM",
+            Self::WorkRam => "WRAM",
+            Self::Oam => "OAM",
+            Self::HighRam => "HRAM",
+        })
+    }
+}
+
+/// The PPU doesn't lock the reads and the writes at the same time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read,
+    Write,
+}
+
+/// Inspector of the memory, created with `Gameboy::memory`, or `Gameboy::memory_mut` to also patch it.
+///
+/// Nothing done through the inspector has the side effects of a CPU access: reading an I/O register doesn't
+/// acknowledge anything and writing to the ROM patches it instead of talking to the MBC. The VRAM and the OAM are
+/// inaccessible while the PPU locks them, like they are for the CPU, unless the locking is bypassed.
+pub struct Memory<G> {
+    gameboy: G,
+    ppu_locking: bool,
+}
+
+impl<G: Deref<Target = Gameboy>> Memory<G> {
+    pub(in super::super) fn new(gameboy: G) -> Self {
+        Self {
+            gameboy,
+            ppu_locking: true,
+        }
+    }
+
+    /// Allow to access the VRAM and the OAM while the PPU locks them
+    pub fn set_ppu_locking(self, ppu_locking: bool) -> Self {
+        Self {
+            ppu_locking,
+            ..self
+        }
+    }
+
+    /// Region, bank and offset of the memory mapped at the given address, `None` for the I/O registers, the
+    /// interrupt enable register and the unmapped areas
+    pub fn locate(&self, address: u16) -> Option<(Region, usize, usize)> {
+        if self.gameboy.is_boot_rom_mapped(address) {
+            return Some((Region::BootRom, 0, address as usize));
+        }
+
+        let cartridge = &self.gameboy.cartridge;
+        let located = match address {
+            0x0000..=0x3FFF => (Region::Rom, cartridge.rom_bank_0(), address as usize),
+            0x4000..=0x7FFF => (
+                Region::Rom,
+                cartridge.rom_bank_n(),
+                address as usize - 0x4000,
+            ),
+            0x8000..=0x9FFF => (Region::VideoRam, 0, address as usize - 0x8000),
+            0xA000..=0xBFFF if cartridge.ram().is_some() => (
+                Region::CartridgeRam,
+                cartridge.ram_bank(),
+                address as usize - 0xA000,
+            ),
+            0xC000..=0xCFFF => (Region::WorkRam, 0, address as usize - 0xC000),
+            0xD000..=0xDFFF => (Region::WorkRam, 1, address as usize - 0xD000),
+            0xE000..=0xEFFF => (Region::WorkRam, 0, address as usize - 0xE000),
+            0xF000..=0xFDFF => (Region::WorkRam, 1, address as usize - 0xF000),
+            0xFE00..=0xFE9F => (Region::Oam, 0, address as usize - 0xFE00),
+            0xFF80..=0xFFFE => (Region::HighRam, 0, address as usize - 0xFF80),
+            _ => return None,
+        };
+
+        Some(located)
+    }
+
+    pub fn bank_count(&self, region: Region) -> usize {
+        match region {
+            Region::BootRom => self.gameboy.boot_rom.is_some().into(),
+            Region::Rom => self.gameboy.cartridge.rom().len().div_ceil(0x4000),
+            Region::CartridgeRam => self
+                .gameboy
+                .cartridge
+                .ram()
+                .map_or(0, |ram| ram.len().div_ceil(0x2000)),
+            Region::WorkRam => 2,
+            Region::VideoRam | Region::Oam | Region::HighRam => 1,
+        }
+    }
+
+    /// Size of a bank of the region, the cartridge RAM can be smaller than a bank
+    pub fn bank_size(&self, region: Region) -> usize {
+        match region {
+            Region::BootRom => self
+                .gameboy
+                .boot_rom
+                .as_ref()
+                .map_or(0, |boot_rom| boot_rom.size()),
+            Region::Rom => 0x4000,
+            Region::VideoRam => 0x2000,
+            Region::CartridgeRam => self
+                .gameboy
+                .cartridge
+                .ram()
+                .map_or(0, |ram| ram.len().min(0x2000)),
+            Region::WorkRam => 0x1000,
+            Region::Oam => 0xA0,
+            Region::HighRam => 0x7F,
+        }
+    }
+
+    /// Read a byte as the CPU would see it, the I/O registers included
+    pub fn peek(&self, address: u16) -> u8 {
+        match self.locate(address) {
+            Some((region @ (Region::VideoRam | Region::Oam), bank, offset))
+                if !self.ppu_locking =>
+            {
+                self.peek_region(region, bank, offset).unwrap_or(0xFF)
+            }
+            _ => self.gameboy.read_byte(address),
+        }
+    }
+
+    pub fn peek_region(&self, region: Region, bank: usize, offset: usize) -> Result<u8, Error> {
+        let index = self.index(region, bank, offset, Access::Read)?;
+        let gameboy = &*self.gameboy;
+
+        Ok(match region {
+            Region::BootRom => gameboy
+                .boot_rom
+                .as_ref()
+                .map_or(0xFF, |boot_rom| boot_rom[index as u16]),
+            Region::Rom => gameboy.cartridge.rom()[index],
+            Region::VideoRam => gameboy.ppu.vram()[index],
+            Region::CartridgeRam => gameboy.cartridge.ram().map_or(0xFF, |ram| ram[index]),
+            Region::WorkRam => gameboy.work_ram[index as u16],
+            Region::Oam => gameboy.ppu.oam().read_byte(index as u16),
+            Region::HighRam => gameboy.high_ram[index as u16],
+        })
+    }
+
+    /// Copy of a whole bank, for the cartridge RAM only the part that exists
+    pub fn dump(&self, region: Region, bank: usize) -> Result<Vec<u8>, Error> {
+        (0..self.bank_size(region))
+            .map(|offset| self.peek_region(region, bank, offset))
+            .collect()
+    }
+
+    /// Bytes of the CPU address space, as given by `peek`
+    pub fn dump_range(&self, range: RangeInclusive<u16>) -> Vec<u8> {
+        range.map(|address| self.peek(address)).collect()
+    }
+
+    /// Index of the byte in the storage of the region, also checking the PPU locking of the given access
+    fn index(
+        &self,
+        region: Region,
+        bank: usize,
+        offset: usize,
+        access: Access,
+    ) -> Result<usize, Error> {
+        let out_of_bounds = Error::OutOfBounds {
+            region,
+            bank,
+            offset,
+        };
+
+        if bank >= self.bank_count(region) || offset >= self.bank_size(region) {
+            return Err(out_of_bounds);
+        }
+
+        let index = bank * self.bank_size(region) + offset;
+        let size = match region {
+            Region::Rom => self.gameboy.cartridge.rom().len(),
+            Region::CartridgeRam => self.gameboy.cartridge.ram().map_or(0, <[u8]>::len),
+            _ => usize::MAX,
+        };
+        if index >= size {
+            return Err(out_of_bounds);
+        }
+
+        let ppu = &self.gameboy.ppu;
+        let locked = match (region, access) {
+            (Region::VideoRam, Access::Read) => ppu.vram_locked(),
+            (Region::VideoRam, Access::Write) => ppu.vram_write_locked(),
+            (Region::Oam, Access::Read) => ppu.oam_locked(),
+            (Region::Oam, Access::Write) => ppu.oam_write_locked(),
+            _ => false,
+        };
+        if self.ppu_locking && locked {
+            return Err(Error::Locked(region));
+        }
+
+        Ok(index)
+    }
+}
+
+impl<G: DerefMut<Target = Gameboy>> Memory<G> {
+    /// Write a byte to the memory mapped at the given address.
+    ///
+    /// The ROM and the cartridge RAM are written in the banks currently mapped, even when the RAM is disabled, and
+    /// the I/O registers are refused.
+    pub fn poke(&mut self, address: u16, value: u8) -> Result<(), Error> {
+        match self.locate(address) {
+            Some((region, bank, offset)) => self.poke_region(region, bank, offset, value),
+            None => match address {
+                0xFF00..=0xFF7F => Err(Error::IoRegister(address)),
+                0xFFFF => {
+                    self.gameboy.interrupt.set_enable(value);
+                    Ok(())
+                }
+                _ => Err(Error::Unmapped(address)),
+            },
+        }
+    }
+
+    pub fn poke_region(
+        &mut self,
+        region: Region,
+        bank: usize,
+        offset: usize,
+        value: u8,
+    ) -> Result<(), Error> {
+        let index = self.index(region, bank, offset, Access::Write)?;
+        let gameboy = &mut *self.gameboy;
+
+        match region {
+            Region::BootRom => return Err(Error::ReadOnly(region)),
+            Region::Rom => gameboy.cartridge.mut_rom()[index] = value,
+            Region::VideoRam => gameboy.ppu.mut_vram()[index] = value,
+            Region::CartridgeRam => {
+                if let Some(ram) = gameboy.cartridge.mut_ram() {
+                    ram[index] = value;
+                }
+            }
+            Region::WorkRam => gameboy.work_ram[index as u16] = value,
+            Region::Oam => gameboy.ppu.mut_oam().write_byte(index as u16, value),
+            Region::HighRam => gameboy.high_ram[index as u16] = value,
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,458 @@
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use super::super::{screen, Gameboy};
+use super::symbols::SymbolTable;
+
+/// M-cycles of a frame, 154 lines of 114 M-cycles
+pub const FRAME_M_CYCLES: usize = 17556;
+
+/// M-cycles of the VBlank period, 10 lines of 114 M-cycles
+pub const VBLANK_M_CYCLES: usize = 1140;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Location {
+    pub bank: usize,
+    pub address: u16,
+}
+
+impl Location {
+    pub fn new(bank: usize, address: u16) -> Self {
+        Self { bank, address }
+    }
+
+    fn name(&self, symbols: Option<&SymbolTable>) -> String {
+        symbols
+            .and_then(|symbols| symbols.describe(self.bank, self.address))
+            .unwrap_or_else(|| self.to_string())
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:03X}:{:04X}", self.bank, self.address)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct LocationStats {
+    /// M-cycles of the instructions at this location, including their opcode fetch
+    pub m_cycles: usize,
+    pub executions: usize,
+}
+
+/// Maximum M-cycles per frame for a routine, including the routines it calls
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Budget {
+    pub routine: Location,
+    pub m_cycles: usize,
+}
+
+impl Budget {
+    pub fn new(bank: usize, address: u16, m_cycles: usize) -> Self {
+        Self {
+            routine: Location::new(bank, address),
+            m_cycles,
+        }
+    }
+
+    /// The VBlank interrupt handler must be done before the end of the VBlank period to safely access VRAM
+    pub fn vblank() -> Self {
+        Self::new(0, 0x40, VBLANK_M_CYCLES)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FrameStats {
+    pub m_cycles: usize,
+    pub halted_m_cycles: usize,
+    /// M-cycles used by the routines with a budget during the frame, in the order the budgets were added
+    pub budget_m_cycles: Vec<usize>,
+}
+
+impl FrameStats {
+    fn new(budget_count: usize) -> Self {
+        Self {
+            m_cycles: 0,
+            halted_m_cycles: 0,
+            budget_m_cycles: vec![0; budget_count],
+        }
+    }
+}
+
+/// Frame in which a routine used more M-cycles than its budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Overrun {
+    pub frame: usize,
+    pub routine: Location,
+    pub m_cycles: usize,
+    pub budget: usize,
+}
+
+/// Routine entered with a call or an interrupt, left once SP goes above the return address pushed when entering it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct CallFrame {
+    routine: Location,
+    /// `None` for the routine running when the profiling started
+    sp: Option<u16>,
+}
+
+/// M-cycles spent with a call stack
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Stack {
+    routines: Vec<Location>,
+    /// Indexes of the budgets of the routines in the stack
+    budgets: Vec<usize>,
+    running: usize,
+    halted: usize,
+    dispatching: usize,
+}
+
+fn is_call(opcode: u8) -> bool {
+    // CALL, CALL cc and RST
+    matches!(opcode, 0xCD | 0xC4 | 0xCC | 0xD4 | 0xDC) || opcode & 0xC7 == 0xC7
+}
+
+/// Attribute the M-cycles of the CPU to the executed instructions and to the call stacks.
+///
+/// The call stack is built from the CALL, RST and interrupt dispatches, the routines being left once SP goes above
+/// their return address, like after RET and RETI. The frames end at each VBlank event of the PPU.
+///
+/// The profiler is fed with `Profiler::observe` after each `Gameboy::tick`.
+pub struct Profiler {
+    symbols: Option<SymbolTable>,
+    budgets: Vec<Budget>,
+    locations: HashMap<Location, LocationStats>,
+    call_stack: Vec<CallFrame>,
+    stacks: Vec<Stack>,
+    stack_ids: HashMap<Vec<Location>, usize>,
+    stack_id: usize,
+    /// SP at the start of the previous instruction
+    previous_sp: Option<u16>,
+    was_dispatching: bool,
+    /// Instruction fetched during the previous M-cycle and its stack, the fetch is aborted by an interrupt dispatch
+    fetch: Option<(Location, usize)>,
+    m_cycles: usize,
+    halted_m_cycles: usize,
+    frame: FrameStats,
+    frames: Vec<FrameStats>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        let mut profiler = Self {
+            symbols: None,
+            budgets: Vec::new(),
+            locations: HashMap::new(),
+            call_stack: Vec::new(),
+            stacks: Vec::new(),
+            stack_ids: HashMap::new(),
+            stack_id: 0,
+            previous_sp: None,
+            was_dispatching: false,
+            fetch: None,
+            m_cycles: 0,
+            halted_m_cycles: 0,
+            frame: FrameStats::new(0),
+            frames: Vec::new(),
+        };
+
+        profiler.update_stack_id();
+        profiler
+    }
+
+    /// Name the routines of the reports with the symbols
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = Some(symbols);
+    }
+
+    pub fn add_budget(&mut self, budget: Budget) {
+        let idx = self.budgets.len();
+        self.budgets.push(budget);
+
+        for stack in &mut self.stacks {
+            if stack.routines.contains(&budget.routine) {
+                stack.budgets.push(idx);
+            }
+        }
+        self.frame.budget_m_cycles.push(0);
+    }
+
+    pub fn budgets(&self) -> &[Budget] {
+        &self.budgets
+    }
+
+    /// Forget the statistics, keeping the call stack, the symbols and the budgets
+    pub fn reset(&mut self) {
+        self.locations.clear();
+        for stack in &mut self.stacks {
+            stack.running = 0;
+            stack.halted = 0;
+            stack.dispatching = 0;
+        }
+        self.m_cycles = 0;
+        self.halted_m_cycles = 0;
+        self.frame = FrameStats::new(self.budgets.len());
+        self.frames.clear();
+    }
+
+    pub fn m_cycles(&self) -> usize {
+        self.m_cycles
+    }
+
+    pub fn halted_m_cycles(&self) -> usize {
+        self.halted_m_cycles
+    }
+
+    pub fn location(&self, bank: usize, address: u16) -> LocationStats {
+        self.locations
+            .get(&Location::new(bank, address))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn locations(&self) -> impl Iterator<Item = (&Location, &LocationStats)> {
+        self.locations.iter()
+    }
+
+    /// Routines of the current call stack, from the outermost one
+    pub fn call_stack(&self) -> impl Iterator<Item = Location> + '_ {
+        self.call_stack.iter().map(|frame| frame.routine)
+    }
+
+    /// Complete frames, the frame in progress is not included
+    pub fn frames(&self) -> &[FrameStats] {
+        &self.frames
+    }
+
+    /// Frames in which a routine went over its budget
+    pub fn overruns(&self) -> impl Iterator<Item = Overrun> + '_ {
+        self.frames
+            .iter()
+            .enumerate()
+            .flat_map(move |(frame, stats)| {
+                self.budgets
+                    .iter()
+                    .zip(&stats.budget_m_cycles)
+                    .filter(|(budget, m_cycles)| **m_cycles > budget.m_cycles)
+                    .map(move |(budget, m_cycles)| Overrun {
+                        frame,
+                        routine: budget.routine,
+                        m_cycles: *m_cycles,
+                        budget: budget.m_cycles,
+                    })
+            })
+    }
+
+    /// Attribute the M-cycle of the last `Gameboy::tick`, if any, and end the frame on VBlank
+    pub fn observe(&mut self, gameboy: &Gameboy) {
+        if gameboy.last_memory_operation.is_some() {
+            self.attribute(gameboy);
+        }
+
+        if let Some(screen::Event::VBlank) = gameboy.last_screen_event {
+            let frame = std::mem::replace(&mut self.frame, FrameStats::new(self.budgets.len()));
+            self.frames.push(frame);
+        }
+    }
+
+    fn attribute(&mut self, gameboy: &Gameboy) {
+        let cpu = &gameboy.cpu;
+
+        if self.call_stack.is_empty() {
+            let address = cpu.instruction_address();
+            self.call_stack.push(CallFrame {
+                routine: Location::new(gameboy.bank(address), address),
+                sp: None,
+            });
+            self.update_stack_id();
+        }
+
+        // The M-cycle of an aborted fetch is the first M-cycle of the interrupt dispatch
+        if let Some((location, stack_id)) = self.fetch.take() {
+            if cpu.is_dispatching_interrupt() {
+                if let Some(stats) = self.locations.get_mut(&location) {
+                    stats.m_cycles -= 1;
+                    stats.executions -= 1;
+                }
+                self.stacks[stack_id].running -= 1;
+                self.stacks[stack_id].dispatching += 1;
+            }
+        }
+
+        let stack = &mut self.stacks[self.stack_id];
+        if cpu.is_halted() {
+            stack.halted += 1;
+            self.halted_m_cycles += 1;
+            self.frame.halted_m_cycles += 1;
+        } else if cpu.is_dispatching_interrupt() {
+            stack.dispatching += 1;
+        } else {
+            if cpu.at_instruction_boundary() {
+                self.enter_instruction(gameboy);
+            }
+
+            let address = cpu.instruction_address();
+            let location = Location::new(gameboy.bank(address), address);
+            self.locations.entry(location).or_default().m_cycles += 1;
+            self.stacks[self.stack_id].running += 1;
+
+            if cpu.at_instruction_boundary() {
+                self.fetch = Some((location, self.stack_id));
+            }
+        }
+
+        for idx in &self.stacks[self.stack_id].budgets {
+            self.frame.budget_m_cycles[*idx] += 1;
+        }
+        self.m_cycles += 1;
+        self.frame.m_cycles += 1;
+        self.was_dispatching = cpu.is_dispatching_interrupt();
+    }
+
+    /// Update the call stack when the opcode of an instruction is fetched
+    fn enter_instruction(&mut self, gameboy: &Gameboy) {
+        let cpu = &gameboy.cpu;
+        let sp = cpu.registers().sp();
+        let address = cpu.instruction_address();
+        let location = Location::new(gameboy.bank(address), address);
+
+        let call_stack_size = self.call_stack.len();
+        while let Some(CallFrame {
+            sp: Some(frame_sp), ..
+        }) = self.call_stack.last()
+        {
+            if *frame_sp >= sp {
+                break;
+            }
+            self.call_stack.pop();
+        }
+
+        // The opcode of the previous instruction is still known while the next one is fetched
+        let called = is_call(cpu.opcode())
+            && self
+                .previous_sp
+                .map(|previous_sp| previous_sp.wrapping_sub(2))
+                == Some(sp);
+        if self.was_dispatching || called {
+            self.call_stack.push(CallFrame {
+                routine: location,
+                sp: Some(sp),
+            });
+        }
+
+        if self.call_stack.len() != call_stack_size || self.was_dispatching || called {
+            self.update_stack_id();
+        }
+
+        self.locations.entry(location).or_default().executions += 1;
+        self.previous_sp = Some(sp);
+    }
+
+    fn update_stack_id(&mut self) {
+        let routines: Vec<Location> = self.call_stack.iter().map(|frame| frame.routine).collect();
+
+        self.stack_id = match self.stack_ids.get(&routines) {
+            Some(id) => *id,
+            None => {
+                let id = self.stacks.len();
+                let budgets = self
+                    .budgets
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, budget)| routines.contains(&budget.routine))
+                    .map(|(idx, _)| idx)
+                    .collect();
+
+                self.stack_ids.insert(routines.clone(), id);
+                self.stacks.push(Stack {
+                    routines,
+                    budgets,
+                    running: 0,
+                    halted: 0,
+                    dispatching: 0,
+                });
+                id
+            }
+        };
+    }
+
+    /// Table of the instructions sorted by M-cycles, with their label when symbols are available
+    pub fn flat_table(&self) -> String {
+        let mut locations: Vec<_> = self.locations.iter().collect();
+        locations.sort_by(|(l1, s1), (l2, s2)| s2.m_cycles.cmp(&s1.m_cycles).then(l1.cmp(l2)));
+
+        let percent = |m_cycles: usize| match self.m_cycles {
+            0 => 0.0,
+            total => m_cycles as f64 * 100.0 / total as f64,
+        };
+
+        let mut table = String::new();
+        let _ = writeln!(
+            table,
+            "{:<10} {:<32} {:>12} {:>7} {:>12}",
+            "Location", "Label", "M-cycles", "%", "Executions"
+        );
+        for (location, stats) in locations {
+            let label = self
+                .symbols
+                .as_ref()
+                .and_then(|symbols| symbols.describe(location.bank, location.address))
+                .unwrap_or_default();
+
+            let _ = writeln!(
+                table,
+                "{:<10} {:<32} {:>12} {:>7.2} {:>12}",
+                location.to_string(),
+                label,
+                stats.m_cycles,
+                percent(stats.m_cycles),
+                stats.executions
+            );
+        }
+        let _ = writeln!(
+            table,
+            "{:<10} {:<32} {:>12} {:>7.2}",
+            "halted",
+            "",
+            self.halted_m_cycles,
+            percent(self.halted_m_cycles)
+        );
+
+        table
+    }
+
+    /// Call stacks in the folded format of the flamegraph tools, `outer;inner m_cycles` per line. The time spent
+    /// halted and dispatching interrupts is put under the `[halted]` and `[interrupt dispatch]` pseudo routines.
+    pub fn folded_stacks(&self) -> String {
+        let mut lines: Vec<String> = Vec::new();
+
+        for stack in &self.stacks {
+            let name = stack
+                .routines
+                .iter()
+                .map(|routine| routine.name(self.symbols.as_ref()))
+                .collect::<Vec<_>>()
+                .join(";");
+
+            for (suffix, m_cycles) in [
+                ("", stack.running),
+                (";[halted]", stack.halted),
+                (";[interrupt dispatch]", stack.dispatching),
+            ] {
+                if m_cycles > 0 {
+                    lines.push(format!("{}{} {}", name, suffix, m_cycles));
+                }
+            }
+        }
+
+        lines.sort();
+        lines.into_iter().map(|line| line + "\n").collect()
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::{Bound, RangeInclusive};
+use std::{fs, io, path::Path};
+
+use thiserror::Error;
+
+use super::super::Gameboy;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("invalid symbol at line {line}: '{text}'")]
+    InvalidSymbol { line: usize, text: String },
+
+    #[error("failed to read the symbol file")]
+    ReadError(#[from] io::Error),
+}
+
+/// Start of the memory areas, a label only applies to the addresses following it in the same area
+const AREAS: [u16; 10] = [
+    0x0000, 0x4000, 0x8000, 0xA000, 0xC000, 0xD000, 0xE000, 0xFE00, 0xFF00, 0xFF80,
+];
+
+fn area(address: u16) -> RangeInclusive<u16> {
+    let idx = AREAS.partition_point(|start| *start <= address) - 1;
+    let end = AREAS.get(idx + 1).map_or(0xFFFF, |next| next - 1);
+
+    AREAS[idx]..=end
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Symbol {
+    name: String,
+    bank: usize,
+    address: u16,
+}
+
+impl Symbol {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn bank(&self) -> usize {
+        self.bank
+    }
+
+    pub fn address(&self) -> u16 {
+        self.address
+    }
+}
+
+/// Labels of a program built with RGBDS, loaded from the `.sym` file written by `rgblink -n` or the `.map` file
+/// written by `rgblink -m`
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+    by_name: HashMap<String, usize>,
+    by_location: BTreeMap<(usize, u16), usize>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a `.map` file, or a `.sym` file for any other extension
+    pub fn load<P: ?Sized + AsRef<Path>>(path: &P) -> Result<Self, Error> {
+        let text = fs::read_to_string(path)?;
+
+        match path.as_ref().extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("map") => Self::from_map(&text),
+            _ => Self::from_sym(&text),
+        }
+    }
+
+    /// Parse the lines `bank:address name` of a `.sym` file
+    pub fn from_sym(text: &str) -> Result<Self, Error> {
+        let mut symbols = Self::new();
+
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() || line.starts_with('[') {
+                continue;
+            }
+
+            let invalid = || Error::InvalidSymbol {
+                line: idx + 1,
+                text: line.to_string(),
+            };
+
+            let (location, name) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let (bank, address) = location.split_once(':').ok_or_else(invalid)?;
+
+            symbols.add_symbol(
+                usize::from_str_radix(bank, 16).map_err(|_| invalid())?,
+                u16::from_str_radix(address, 16).map_err(|_| invalid())?,
+                name.trim(),
+            );
+        }
+
+        Ok(symbols)
+    }
+
+    /// Parse the symbols listed in the sections of a `.map` file, under the `ROMX bank #1:`-like headers
+    pub fn from_map(text: &str) -> Result<Self, Error> {
+        let mut symbols = Self::new();
+        let mut bank = 0;
+
+        for (idx, line) in text.lines().enumerate() {
+            let invalid = || Error::InvalidSymbol {
+                line: idx + 1,
+                text: line.trim().to_string(),
+            };
+
+            if !line.starts_with(char::is_whitespace) {
+                let lowercase = line.to_ascii_lowercase();
+                if let Some((_, header_bank)) = lowercase.split_once("bank #") {
+                    let digits: String = header_bank
+                        .chars()
+                        .take_while(char::is_ascii_digit)
+                        .collect();
+                    bank = digits.parse().map_err(|_| invalid())?;
+                }
+                continue;
+            }
+
+            let Some((address, name)) = line.trim().split_once(" = ") else {
+                continue;
+            };
+            let Some(address) = address.strip_prefix('$') else {
+                continue;
+            };
+
+            symbols.add_symbol(
+                bank,
+                u16::from_str_radix(address, 16).map_err(|_| invalid())?,
+                name.trim(),
+            );
+        }
+
+        Ok(symbols)
+    }
+
+    /// Add a symbol, the first symbol added at a location being used as its label
+    pub fn add_symbol(&mut self, bank: usize, address: u16, name: &str) {
+        let idx = self.symbols.len();
+
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            bank,
+            address,
+        });
+        self.by_name.entry(name.to_string()).or_insert(idx);
+        self.by_location.entry((bank, address)).or_insert(idx);
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.by_name.get(name).map(|idx| &self.symbols[*idx])
+    }
+
+    /// Label at a location
+    pub fn label(&self, bank: usize, address: u16) -> Option<&str> {
+        self.by_location
+            .get(&(bank, address))
+            .map(|idx| self.symbols[*idx].name.as_str())
+    }
+
+    /// Label at an address, in the bank currently mapped by the Gameboy
+    pub fn label_at(&self, gameboy: &Gameboy, address: u16) -> Option<&str> {
+        self.label(gameboy.bank(address), address)
+    }
+
+    /// Closest label before a location in the same memory area, with the offset from it
+    pub fn nearest_label(&self, bank: usize, address: u16) -> Option<(&str, u16)> {
+        let area = area(address);
+
+        self.by_location
+            .range((bank, *area.start())..=(bank, address))
+            .next_back()
+            .map(|((_, label_address), idx)| {
+                (self.symbols[*idx].name.as_str(), address - label_address)
+            })
+    }
+
+    /// Describe a location as `label` or `label+offset`
+    pub fn describe(&self, bank: usize, address: u16) -> Option<String> {
+        self.nearest_label(bank, address)
+            .map(|(label, offset)| match offset {
+                0 => label.to_string(),
+                offset => format!("{}+{}", label, offset),
+            })
+    }
+
+    /// Bank and addresses from a symbol to the byte before the next symbol, to watch a variable or a table
+    pub fn range(&self, name: &str) -> Option<(usize, RangeInclusive<u16>)> {
+        let symbol = self.symbol(name)?;
+        let area_end = *area(symbol.address).end();
+
+        let end = self
+            .by_location
+            .range((
+                Bound::Excluded((symbol.bank, symbol.address)),
+                Bound::Included((symbol.bank, area_end)),
+            ))
+            .next()
+            .map_or(area_end, |((_, address), _)| address - 1);
+
+        Some((symbol.bank, symbol.address..=end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SymbolTable;
+    use crate::gameboy::tests::{DEBUGGER_PROGRAM_MAP, DEBUGGER_PROGRAM_SYM};
+
+    #[test]
+    fn symbol_table() {
+        assert!(SymbolTable::from_sym("00:01XX Main").is_err());
+
+        for symbols in [
+            SymbolTable::from_sym(DEBUGGER_PROGRAM_SYM).unwrap(),
+            SymbolTable::from_map(DEBUGGER_PROGRAM_MAP).unwrap(),
+        ] {
+            assert_eq!(symbols.symbols().len(), 4);
+
+            let main_loop = symbols.symbol("Main.loop").unwrap();
+            assert_eq!((main_loop.bank(), main_loop.address()), (0, 0x15B));
+
+            assert_eq!(symbols.label(0, 0x160), Some("IncrementA"));
+            assert_eq!(symbols.label(1, 0x160), None);
+            assert_eq!(symbols.describe(0, 0x15C).as_deref(), Some("Main.loop+1"));
+            assert_eq!(symbols.describe(0, 0x4000), None);
+            assert_eq!(symbols.range("Main"), Some((0, 0x150..=0x15A)));
+            assert_eq!(symbols.range("wValue"), Some((0, 0xC000..=0xCFFF)));
+        }
+    }
+}
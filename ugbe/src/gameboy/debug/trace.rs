@@ -0,0 +1,260 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc;
+
+use thiserror::Error;
+
+use super::{disassembler, symbols::SymbolTable};
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("failed to create the trace file")]
+    CreateError(#[from] io::Error),
+}
+
+/// State of the CPU at the start of an instruction, before its execution
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Entry {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    /// Address of the opcode, including the 0xCB prefix
+    pub pc: u16,
+    /// The 4 bytes at PC, as read by the CPU
+    pub pcmem: [u8; 4],
+    /// Bank mapped at PC
+    pub bank: usize,
+    /// T-cycle count of the clock when the instruction has been decoded
+    pub t_cycle: usize,
+    /// Label of PC as `label` or `label+offset`, only set when the tracer has symbols
+    pub label: Option<String>,
+}
+
+impl fmt::Display for Entry {
+    /// Format used by Gameboy Doctor, `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,50,01`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [m0, m1, m2, m3] = self.pcmem;
+
+        write!(
+            f,
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            self.a, self.f, self.b, self.c, self.d, self.e, self.h, self.l, self.sp, self.pc, m0, m1, m2, m3
+        )
+    }
+}
+
+/// Format of the lines written by the tracer
+pub enum Format {
+    /// Gameboy Doctor format, as given by the `Display` implementation of `Entry`
+    GameboyDoctor,
+    /// Gameboy Doctor format followed by the bank, the label and the disassembly of the instruction, like
+    /// `... PCMEM:3E,12,EA,00 ; 000:Main ld a, $12`
+    Annotated,
+    Custom(Box<dyn Fn(&Entry) -> String + Send>),
+}
+
+impl Format {
+    fn format(&self, entry: &Entry) -> String {
+        match self {
+            Self::GameboyDoctor => entry.to_string(),
+            Self::Annotated => {
+                let instruction = disassembler::decode(&entry.pcmem, entry.pc)
+                    .map(|instruction| instruction.to_string())
+                    .unwrap_or_default();
+
+                match &entry.label {
+                    Some(label) => {
+                        format!("{} ; {:03X}:{} {}", entry, entry.bank, label, instruction)
+                    }
+                    None => format!(
+                        "{} ; {:03X}:{:04X} {}",
+                        entry, entry.bank, entry.pc, instruction
+                    ),
+                }
+            }
+            Self::Custom(format) => format(entry),
+        }
+    }
+}
+
+/// Destination of the lines written by the tracer
+pub trait Sink: Any + Send {
+    /// Write the line formatted from the entry
+    fn write(&mut self, entry: &Entry, line: &str);
+}
+
+/// Write the lines to a file or any other writer, one per line
+pub struct WriterSink<W: Write + Send + 'static> {
+    writer: W,
+    error: Option<io::Error>,
+}
+
+impl WriterSink<BufWriter<File>> {
+    pub fn create<P: ?Sized + AsRef<Path>>(path: &P) -> Result<Self, Error> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write + Send + 'static> WriterSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            error: None,
+        }
+    }
+
+    /// First write error, nothing is written after it
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Send + 'static> Sink for WriterSink<W> {
+    fn write(&mut self, _entry: &Entry, line: &str) {
+        if self.error.is_none() {
+            self.error = writeln!(self.writer, "{}", line).err();
+        }
+    }
+}
+
+/// Keep the last lines in memory, to look at what happened before a crash or a breakpoint
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RingBuffer {
+    lines: VecDeque<String>,
+    capacity: usize,
+}
+
+impl RingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Lines from the oldest to the most recent
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().map(String::as_str)
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+}
+
+impl Sink for RingBuffer {
+    fn write(&mut self, _entry: &Entry, line: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line.to_string());
+    }
+}
+
+/// Send the lines to another thread, the lines are dropped once the receiver is disconnected
+#[derive(Debug, Clone)]
+pub struct ChannelSink {
+    sender: mpsc::Sender<String>,
+}
+
+impl ChannelSink {
+    pub fn new(sender: mpsc::Sender<String>) -> Self {
+        Self { sender }
+    }
+
+    pub fn channel() -> (Self, mpsc::Receiver<String>) {
+        let (sender, receiver) = mpsc::channel();
+        (Self::new(sender), receiver)
+    }
+}
+
+impl Sink for ChannelSink {
+    fn write(&mut self, _entry: &Entry, line: &str) {
+        let _ = self.sender.send(line.to_string());
+    }
+}
+
+/// Write a line per executed instruction, started with `Gameboy::start_trace`.
+///
+/// The interrupt dispatches are not instructions and are not traced, the first instruction of the handler is.
+pub struct Tracer {
+    format: Format,
+    sink: Box<dyn Sink>,
+    symbols: Option<SymbolTable>,
+    line_count: usize,
+}
+
+impl Tracer {
+    /// Tracer writing to the sink in the Gameboy Doctor format
+    pub fn new(sink: Box<dyn Sink>) -> Self {
+        Self {
+            format: Format::GameboyDoctor,
+            sink,
+            symbols: None,
+            line_count: 0,
+        }
+    }
+
+    pub fn set_format(self, format: Format) -> Self {
+        Self { format, ..self }
+    }
+
+    /// Fill the label of the entries with the symbols
+    pub fn set_symbols(self, symbols: SymbolTable) -> Self {
+        Self {
+            symbols: Some(symbols),
+            ..self
+        }
+    }
+
+    pub fn sink<T: Sink>(&self) -> Option<&T> {
+        let sink: &dyn Any = self.sink.as_ref();
+        sink.downcast_ref()
+    }
+
+    pub fn sink_mut<T: Sink>(&mut self) -> Option<&mut T> {
+        let sink: &mut dyn Any = self.sink.as_mut();
+        sink.downcast_mut()
+    }
+
+    pub fn into_sink(self) -> Box<dyn Sink> {
+        self.sink
+    }
+
+    /// Number of lines written since the tracer has been created
+    pub fn line_count(&self) -> usize {
+        self.line_count
+    }
+
+    pub(in super::super) fn trace(&mut self, mut entry: Entry) {
+        if let Some(symbols) = &self.symbols {
+            entry.label = symbols.describe(entry.bank, entry.pc);
+        }
+
+        let line = self.format.format(&entry);
+        self.sink.write(&entry, &line);
+        self.line_count += 1;
+    }
+}
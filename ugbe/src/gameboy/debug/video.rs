@@ -0,0 +1,384 @@
+use thiserror::Error;
+
+use super::super::{
+    screen::{Color, ColorPalette, Screen},
+    Gameboy,
+};
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Error {
+    #[error("the VRAM bank '{0}' doesn't exist")]
+    Bank(usize),
+
+    #[error("the VRAM bank '{0}' of the CGB isn't emulated")]
+    UnemulatedBank(usize),
+
+    #[error("the sprite '{0}' doesn't exist, the OAM holds 40 sprites")]
+    Sprite(usize),
+}
+
+pub const TILE_COUNT: usize = 384;
+pub const SPRITE_COUNT: usize = 40;
+
+/// Tiles per row in the image of the tile data
+const TILES_PER_ROW: usize = 16;
+
+/// Buffer of colors, row by row
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, color: Color) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![color; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    /// Outline of a rectangle wrapping around the edges of the image, like the viewport in a tile map
+    fn draw_wrapping_rectangle(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        color: Color,
+    ) {
+        for offset in 0..width {
+            self.set_pixel((x + offset) % self.width, y % self.height, color);
+            self.set_pixel(
+                (x + offset) % self.width,
+                (y + height - 1) % self.height,
+                color,
+            );
+        }
+        for offset in 0..height {
+            self.set_pixel(x % self.width, (y + offset) % self.height, color);
+            self.set_pixel(
+                (x + width - 1) % self.width,
+                (y + offset) % self.height,
+                color,
+            );
+        }
+    }
+}
+
+/// One of the two 32x32 tile maps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TileMap {
+    Map9800,
+    Map9C00,
+}
+
+impl TileMap {
+    /// Offset of the map in the VRAM
+    fn offset(&self) -> usize {
+        match self {
+            Self::Map9800 => 0x1800,
+            Self::Map9C00 => 0x1C00,
+        }
+    }
+}
+
+/// Sprite of the OAM, with its raw position: a sprite is displayed at `(x - 8, y - 16)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sprite {
+    pub index: usize,
+    pub y: u8,
+    pub x: u8,
+    pub tile: u8,
+    /// Uses OBP1 instead of OBP0
+    pub obp1: bool,
+    pub x_flip: bool,
+    pub y_flip: bool,
+    /// The background and window colors 1-3 are drawn over the sprite
+    pub behind_bg: bool,
+    /// The sprite is 8x16 instead of 8x8
+    pub tall: bool,
+}
+
+impl Sprite {
+    /// Whether the sprite is in the visible part of the screen, it might still be hidden by the 10 sprites per line
+    /// limit
+    pub fn is_on_screen(&self) -> bool {
+        let height = if self.tall { 16 } else { 8 };
+
+        (1..Screen::WIDTH as u8 + 8).contains(&self.x)
+            && (17 - height..Screen::HEIGHT as u8 + 16).contains(&self.y)
+    }
+}
+
+/// Renders the content of the VRAM and the OAM, created with `Gameboy::video`.
+///
+/// The memory is read regardless of the PPU locking and the rendering uses the current LCDC, palettes and scroll
+/// registers. The colors are the ones of the screen palette, the SGB colorization being ignored.
+pub struct Video<'a> {
+    gameboy: &'a Gameboy,
+    color_palette: ColorPalette,
+    viewport_color: Color,
+    window_color: Color,
+}
+
+impl<'a> Video<'a> {
+    pub(in super::super) fn new(gameboy: &'a Gameboy) -> Self {
+        Self {
+            gameboy,
+            color_palette: gameboy.screen().color_palette().clone(),
+            viewport_color: Color::new(31, 0, 0),
+            window_color: Color::new(0, 0, 31),
+        }
+    }
+
+    pub fn set_color_palette(self, color_palette: ColorPalette) -> Self {
+        Self {
+            color_palette,
+            ..self
+        }
+    }
+
+    /// Colors of the outlines drawn by `tile_map_with_overlay`
+    pub fn set_overlay_colors(self, viewport_color: Color, window_color: Color) -> Self {
+        Self {
+            viewport_color,
+            window_color,
+            ..self
+        }
+    }
+
+    /// Number of VRAM banks of the model, 2 on CGB even though only the first one is emulated
+    pub fn vram_bank_count(&self) -> usize {
+        match self.gameboy.model.is_cgb() {
+            true => 2,
+            false => 1,
+        }
+    }
+
+    /// The 384 tiles of a VRAM bank, 16 tiles per row, the colors 0 to 3 being drawn as the shades 0 to 3.
+    ///
+    /// The VRAM banking of the CGB isn't emulated: the bank 1 of the CGB and AGB models is refused with
+    /// `Error::UnemulatedBank`, the tiles it would hold are in the bank 0 like on DMG.
+    pub fn tiles(&self, bank: usize) -> Result<Image, Error> {
+        if bank >= self.vram_bank_count() {
+            return Err(Error::Bank(bank));
+        }
+        if bank > 0 {
+            return Err(Error::UnemulatedBank(bank));
+        }
+
+        let rows = TILE_COUNT / TILES_PER_ROW;
+        let mut image = Image::new(TILES_PER_ROW * 8, rows * 8, self.color_palette.shade(0));
+
+        for tile in 0..TILE_COUNT {
+            self.draw_tile(
+                &mut image,
+                tile * 16,
+                (tile % TILES_PER_ROW) * 8,
+                (tile / TILES_PER_ROW) * 8,
+                0b1110_0100,
+            );
+        }
+
+        Ok(image)
+    }
+
+    /// The 256x256 pixels of a tile map, with the tile data selected by LCDC and the colors of BGP
+    pub fn tile_map(&self, map: TileMap) -> Image {
+        let vram = self.gameboy.ppu.vram();
+        let tile_data_8000 = self.gameboy.ppu.read_lcdc() & 0b1_0000 != 0;
+        let bgp = self.gameboy.ppu.read_bgp();
+
+        let mut image = Image::new(256, 256, self.color_palette.shade(0));
+        for row in 0..32 {
+            for column in 0..32 {
+                let tile_no = vram[map.offset() + row * 32 + column];
+                let tile_offset = match tile_data_8000 {
+                    true => tile_no as usize * 16,
+                    false => (0x1000 + (tile_no as i8 as isize) * 16) as usize,
+                };
+
+                self.draw_tile(&mut image, tile_offset, column * 8, row * 8, bgp);
+            }
+        }
+
+        image
+    }
+
+    /// The tile map with the outline of the screen when the background uses it, wrapping around at the edges, and
+    /// the outline of the visible part of the window when the window is enabled and uses it
+    pub fn tile_map_with_overlay(&self, map: TileMap) -> Image {
+        let mut image = self.tile_map(map);
+        let ppu = &self.gameboy.ppu;
+        let lcdc = ppu.read_lcdc();
+
+        let bg_map = if lcdc & 0b1000 != 0 {
+            TileMap::Map9C00
+        } else {
+            TileMap::Map9800
+        };
+        if bg_map == map {
+            image.draw_wrapping_rectangle(
+                ppu.read_scx().into(),
+                ppu.read_scy().into(),
+                Screen::WIDTH,
+                Screen::HEIGHT,
+                self.viewport_color,
+            );
+        }
+
+        let window_map = if lcdc & 0b100_0000 != 0 {
+            TileMap::Map9C00
+        } else {
+            TileMap::Map9800
+        };
+        let window_x = ppu.read_wx() as usize;
+        let window_y = ppu.read_wy() as usize;
+        let window_enabled = lcdc & 0b10_0000 != 0;
+
+        if window_enabled
+            && window_map == map
+            && window_x < Screen::WIDTH + 7
+            && window_y < Screen::HEIGHT
+        {
+            let width = (Screen::WIDTH + 7 - window_x.max(7)).min(256);
+            let height = Screen::HEIGHT - window_y;
+            image.draw_wrapping_rectangle(0, 0, width, height, self.window_color);
+        }
+
+        image
+    }
+
+    /// The 40 sprites of the OAM
+    pub fn sprites(&self) -> Vec<Sprite> {
+        (0..SPRITE_COUNT)
+            .map(|index| self.sprite(index).unwrap())
+            .collect()
+    }
+
+    pub fn sprite(&self, index: usize) -> Result<Sprite, Error> {
+        if index >= SPRITE_COUNT {
+            return Err(Error::Sprite(index));
+        }
+
+        let oam = self.gameboy.ppu.oam();
+        let byte = |offset: usize| oam.read_byte((index * 4 + offset) as u16);
+        let attributes = byte(3);
+
+        Ok(Sprite {
+            index,
+            y: byte(0),
+            x: byte(1),
+            tile: byte(2),
+            obp1: attributes & 0b1_0000 != 0,
+            x_flip: attributes & 0b10_0000 != 0,
+            y_flip: attributes & 0b100_0000 != 0,
+            behind_bg: attributes & 0b1000_0000 != 0,
+            tall: self.gameboy.ppu.read_lcdc() & 0b100 != 0,
+        })
+    }
+
+    /// The tiles of a sprite, flipped and with the colors of its palette, the color 0 included
+    pub fn sprite_image(&self, sprite: &Sprite) -> Image {
+        let palette = match sprite.obp1 {
+            true => self.gameboy.ppu.read_obp1(),
+            false => self.gameboy.ppu.read_obp0(),
+        };
+
+        let height = if sprite.tall { 16 } else { 8 };
+        let tile = if sprite.tall {
+            sprite.tile & 0xFE
+        } else {
+            sprite.tile
+        };
+
+        let mut tiles = Image::new(8, height, self.color_palette.shade(0));
+        self.draw_tile(&mut tiles, tile as usize * 16, 0, 0, palette);
+        if sprite.tall {
+            self.draw_tile(&mut tiles, (tile as usize + 1) * 16, 0, 8, palette);
+        }
+
+        let mut image = tiles.clone();
+        for y in 0..height {
+            for x in 0..8 {
+                let source_x = if sprite.x_flip { 7 - x } else { x };
+                let source_y = if sprite.y_flip { height - 1 - y } else { y };
+                image.set_pixel(x, y, tiles.pixel(source_x, source_y));
+            }
+        }
+
+        image
+    }
+
+    /// The 40 sprites, 8 per row in the order of the OAM
+    pub fn sprites_image(&self) -> Image {
+        let tall = self.gameboy.ppu.read_lcdc() & 0b100 != 0;
+        let height = if tall { 16 } else { 8 };
+        let mut image = Image::new(
+            8 * 8,
+            SPRITE_COUNT / 8 * height,
+            self.color_palette.shade(0),
+        );
+
+        for sprite in self.sprites() {
+            let sprite_image = self.sprite_image(&sprite);
+            let (left, top) = ((sprite.index % 8) * 8, (sprite.index / 8) * height);
+
+            for y in 0..height {
+                for x in 0..8 {
+                    image.set_pixel(left + x, top + y, sprite_image.pixel(x, y));
+                }
+            }
+        }
+
+        image
+    }
+
+    /// Draw the tile at the given offset of the VRAM, with the shades of a palette register
+    fn draw_tile(
+        &self,
+        image: &mut Image,
+        tile_offset: usize,
+        left: usize,
+        top: usize,
+        palette: u8,
+    ) {
+        let vram = self.gameboy.ppu.vram();
+
+        for y in 0..8 {
+            let low = vram[tile_offset + y * 2];
+            let high = vram[tile_offset + y * 2 + 1];
+
+            for x in 0..8 {
+                let bit = 7 - x;
+                let color = (((high >> bit) & 1) << 1) | ((low >> bit) & 1);
+                let shade = (palette >> (color * 2)) & 0b11;
+
+                image.set_pixel(left + x, top + y, self.color_palette.shade(shade));
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Device facing the infrared port of a CGB.
+///
+/// The times are given in T-cycles emulated since the Gameboy has been built, so that the light pulses keep their
+/// duration whatever the speed the emulators are running at.
+pub trait InfraredLink: Any + Send {
+    /// Called when the LED of the Gameboy is switched on or off
+    fn set_light(&mut self, cycle: u64, light: bool);
+
+    /// Check if the Gameboy receives light
+    fn light(&self, cycle: u64) -> bool;
+}
+
+/// Infrared port of the CGB, controlled through the RP register
+pub struct Infrared {
+    enabled: bool,
+    led: bool,
+    read_enable: u8,
+    cycles: u64,
+    link: Option<Box<dyn InfraredLink>>,
+}
+
+impl std::fmt::Debug for Infrared {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Infrared")
+            .field("enabled", &self.enabled)
+            .field("led", &self.led)
+            .field("read_enable", &self.read_enable)
+            .field("link", &self.link.is_some())
+            .finish()
+    }
+}
+
+impl Infrared {
+    /// The port only exists on CGB models, RP isn't mapped otherwise
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            led: false,
+            read_enable: 0,
+            cycles: 0,
+            link: None,
+        }
+    }
+
+    pub fn connect(&mut self, mut link: Box<dyn InfraredLink>) -> Option<Box<dyn InfraredLink>> {
+        link.set_light(self.cycles, self.led);
+        self.link.replace(link)
+    }
+
+    pub fn disconnect(&mut self) -> Option<Box<dyn InfraredLink>> {
+        self.link.take()
+    }
+
+    pub fn link<T: InfraredLink>(&self) -> Option<&T> {
+        let link: &dyn Any = self.link.as_deref()?;
+        link.downcast_ref()
+    }
+
+    pub fn link_mut<T: InfraredLink>(&mut self) -> Option<&mut T> {
+        let link: &mut dyn Any = self.link.as_deref_mut()?;
+        link.downcast_mut()
+    }
+
+    pub fn tick(&mut self) {
+        self.cycles += 1;
+    }
+
+    pub fn read_rp(&self) -> u8 {
+        if !self.enabled {
+            return 0xFF;
+        }
+
+        // The signal bit reads 0 when light is received, it is only updated when the reading is enabled
+        let light = self.read_enable == 0b11
+            && match &self.link {
+                Some(link) => link.light(self.cycles),
+                None => false,
+            };
+
+        (self.read_enable << 6) | 0b0011_1100 | ((!light as u8) << 1) | (self.led as u8)
+    }
+
+    pub fn write_rp(&mut self, value: u8) {
+        if !self.enabled {
+            return;
+        }
+
+        self.read_enable = value >> 6;
+
+        let led = value & 0b1 != 0;
+        if led != self.led {
+            self.led = led;
+
+            if let Some(link) = &mut self.link {
+                link.set_light(self.cycles, led);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Beam {
+    /// Light state changes waiting for the receiving side to reach their time
+    changes: VecDeque<(u64, bool)>,
+    light: bool,
+}
+
+/// One end of an infrared connection between two Gameboys facing each other, created with `InfraredPair::new`.
+///
+/// Each side sees the light emitted by the other one delayed to its own emulated time: a pulse emitted at a given
+/// cycle is only received once the receiving Gameboy reached the same cycle.
+#[derive(Debug)]
+pub struct InfraredPair {
+    beams: Arc<Mutex<[Beam; 2]>>,
+    side: usize,
+}
+
+impl InfraredPair {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> (Self, Self) {
+        let beams = Arc::new(Mutex::new([Beam::default(), Beam::default()]));
+
+        (
+            Self {
+                beams: beams.clone(),
+                side: 0,
+            },
+            Self { beams, side: 1 },
+        )
+    }
+}
+
+impl InfraredLink for InfraredPair {
+    fn set_light(&mut self, cycle: u64, light: bool) {
+        let mut beams = self.beams.lock().unwrap();
+        beams[self.side].changes.push_back((cycle, light));
+    }
+
+    fn light(&self, cycle: u64) -> bool {
+        let mut beams = self.beams.lock().unwrap();
+        let beam = &mut beams[1 - self.side];
+
+        while let Some(&(at, light)) = beam.changes.front() {
+            if at > cycle {
+                break;
+            }
+
+            beam.light = light;
+            beam.changes.pop_front();
+        }
+
+        beam.light
+    }
+}
@@ -1,4 +1,5 @@
 use super::components::{InterruptKind, InterruptLine};
+use super::sgb;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Button {
@@ -18,12 +19,17 @@ pub enum ButtonState {
     Up,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Joypad {
     buttons_state: [ButtonState; 8],
 
     data: u8,
     last_data: u8,
+
+    sgb_receiver: Option<sgb::PacketReceiver>,
+    sgb_command: Option<sgb::Command>,
+    player_count: u8,
+    current_player: u8,
 }
 
 impl Joypad {
@@ -32,9 +38,26 @@ impl Joypad {
             buttons_state: [ButtonState::Up; 8],
             data: 0xFF,
             last_data: 0xFF,
+            sgb_receiver: None,
+            sgb_command: None,
+            player_count: 1,
+            current_player: 0,
         }
     }
 
+    /// On SGB the writes to P1 are also used to send command packets to the SNES
+    pub(super) fn new_sgb() -> Self {
+        Self {
+            sgb_receiver: Some(sgb::PacketReceiver::new()),
+            ..Self::new()
+        }
+    }
+
+    /// Take the last SGB command received, multiplayer requests are directly handled by the joypad
+    pub(super) fn take_sgb_command(&mut self) -> Option<sgb::Command> {
+        self.sgb_command.take()
+    }
+
     fn use_direction_buttons(&self) -> bool {
         (self.data >> 4) & 0b0001 == 0
     }
@@ -64,6 +87,17 @@ impl Joypad {
     fn update_inputs(&mut self) {
         self.data = (self.data & 0xF0) | 0xF;
 
+        // When multiple players are enabled on SGB, the current joypad is read with both lines unselected
+        if self.player_count > 1 && !self.use_direction_buttons() && !self.use_action_buttons() {
+            self.data = (self.data & 0xF0) | (0xF - self.current_player);
+            return;
+        }
+
+        // Only the first joypad is connected
+        if self.current_player != 0 {
+            return;
+        }
+
         if self.use_direction_buttons() {
             let nibble = {
                 let mut nibble = 0;
@@ -145,10 +179,36 @@ impl Joypad {
     }
 
     pub(super) fn write_p1(&mut self, value: u8) {
+        let previous_data = self.data;
         self.data = (self.data & 0b1100_1111) | (value & 0b0011_0000);
 
+        if let Some(sgb_receiver) = &mut self.sgb_receiver {
+            if let Some(command) = sgb_receiver.write(value) {
+                self.sgb_command(command);
+            }
+
+            // The next joypad is selected on a rising edge of P15
+            if self.player_count > 1 && (!previous_data & self.data) & 0b0010_0000 != 0 {
+                self.current_player = (self.current_player + 1) % self.player_count;
+            }
+        }
+
         self.update_inputs();
     }
+
+    fn sgb_command(&mut self, command: sgb::Command) {
+        match command.code() {
+            sgb::CommandCode::MLT_REQ => {
+                self.player_count = match command.param(0) & 0b11 {
+                    0b01 => 2,
+                    0b11 => 4,
+                    _ => 1,
+                };
+                self.current_player = 0;
+            }
+            _ => self.sgb_command = Some(command),
+        }
+    }
 }
 
 impl Default for Joypad {
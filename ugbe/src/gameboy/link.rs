@@ -0,0 +1,57 @@
+pub mod four_player;
+
+use super::{screen, spu, Gameboy};
+
+/// Two Gameboys connected with a link cable.
+///
+/// Both Gameboys are stepped in lockstep, one T-cycle at a time, so that the one using its internal clock drives the
+/// serial transfer of the other one bit by bit, like on the hardware.
+pub struct LinkedPair {
+    gameboys: [Gameboy; 2],
+}
+
+impl LinkedPair {
+    pub fn new(mut first: Gameboy, mut second: Gameboy) -> Self {
+        first.serial.set_linked(true);
+        second.serial.set_linked(true);
+
+        Self {
+            gameboys: [first, second],
+        }
+    }
+
+    /// Step both Gameboys by one T-cycle and exchange a bit on the cable for each clock pulse
+    pub fn tick(&mut self) -> [(Option<screen::Event>, Option<spu::SampleFrame>); 2] {
+        let results = [self.gameboys[0].tick(), self.gameboys[1].tick()];
+
+        for master in 0..2 {
+            if self.gameboys[master].serial.take_clock_pulse() {
+                let slave = 1 - master;
+
+                let master_bit = self.gameboys[master].serial.output_bit();
+                let slave_bit = self.gameboys[slave].serial_shift(master_bit);
+                self.gameboys[master].serial_shift(slave_bit);
+            }
+        }
+
+        results
+    }
+
+    pub fn first(&mut self) -> &mut Gameboy {
+        &mut self.gameboys[0]
+    }
+
+    pub fn second(&mut self) -> &mut Gameboy {
+        &mut self.gameboys[1]
+    }
+
+    /// Unplug the link cable and give back both Gameboys
+    pub fn unlink(self) -> (Gameboy, Gameboy) {
+        let [mut first, mut second] = self.gameboys;
+
+        first.serial.set_linked(false);
+        second.serial.set_linked(false);
+
+        (first, second)
+    }
+}
@@ -0,0 +1,263 @@
+use thiserror::Error;
+
+use super::super::{screen, spu, Gameboy};
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Error {
+    #[error("the adapter only has 4 ports (got {0} Gameboys)")]
+    TooManyGameboys(usize),
+}
+
+/// Number of ports of the adapter
+pub const PORT_COUNT: usize = 4;
+
+const PING_HEADER: u8 = 0xFE;
+const PING_ACK: u8 = 0x88;
+const START_TRANSMISSION: u8 = 0xAA;
+const TRANSMISSION_HEADER: u8 = 0xCC;
+
+/// Number of T-cycles between two bytes sent during the ping phase
+const PING_BYTE_INTERVAL: usize = 0x4000;
+
+/// Number of T-cycles between two bytes sent during the transmission phase with the fastest rate, each step of
+/// the rate requested by player 1 adds `TRANSMISSION_RATE_STEP` T-cycles
+const TRANSMISSION_BYTE_INTERVAL: usize = 0xA00;
+const TRANSMISSION_RATE_STEP: usize = 0x100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// The adapter looks for connected Gameboys by sending ping packets
+    Ping,
+    /// Player 1 asked to start the transmission, the adapter sends the transmission headers
+    Transition,
+    /// The adapter collects a packet from each player and broadcasts them to all the players
+    Transmission,
+}
+
+/// Protocol of the DMG-07 adapter, which always clocks the transfers of the Gameboys.
+///
+/// During the ping phase the adapter repeatedly sends `0xFE` followed by three status bytes holding the connected
+/// players in the upper nibble and the ID of the receiving player in the lower one. The Gameboys answer with
+/// `0x88 0x88` to be marked as connected, followed by the transmission rate and the size of the packets. Player 1
+/// ends the ping phase by answering `0xAA`, the adapter then sends `0xCC` four times before the transmission phase.
+///
+/// During the transmission phase each packet is `4 * size` bytes: the players send their data in the first `size`
+/// bytes while receiving the data the adapter collected from all the players during the previous packet. The
+/// adapter goes back to the ping phase when all the connected players only sent `0xFF`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Protocol {
+    phase: Phase,
+    index: usize,
+    connected: [bool; PORT_COUNT],
+    /// Answers received during the current ping packet
+    ping_answers: [[u8; 4]; PORT_COUNT],
+    rate: u8,
+    size: usize,
+    packet: Vec<u8>,
+    next_packet: Vec<u8>,
+}
+
+impl Protocol {
+    fn new() -> Self {
+        Self {
+            phase: Phase::Ping,
+            index: 0,
+            connected: [false; PORT_COUNT],
+            ping_answers: [[0; 4]; PORT_COUNT],
+            rate: 0,
+            size: 1,
+            packet: Vec::new(),
+            next_packet: Vec::new(),
+        }
+    }
+
+    fn byte_interval(&self) -> usize {
+        match self.phase {
+            Phase::Ping | Phase::Transition => PING_BYTE_INTERVAL,
+            Phase::Transmission => {
+                TRANSMISSION_BYTE_INTERVAL + (self.rate & 0x0F) as usize * TRANSMISSION_RATE_STEP
+            }
+        }
+    }
+
+    fn outgoing(&self, port: usize) -> u8 {
+        match self.phase {
+            Phase::Ping if self.index == 0 => PING_HEADER,
+            Phase::Ping => {
+                let connected = self
+                    .connected
+                    .iter()
+                    .enumerate()
+                    .fold(0, |status, (idx, connected)| {
+                        status | ((*connected as u8) << (4 + idx))
+                    });
+                connected | (port as u8 + 1)
+            }
+            Phase::Transition => TRANSMISSION_HEADER,
+            Phase::Transmission => self.packet[self.index],
+        }
+    }
+
+    fn received(&mut self, incoming: [Option<u8>; PORT_COUNT]) {
+        match self.phase {
+            Phase::Ping => {
+                for (answers, byte) in self.ping_answers.iter_mut().zip(incoming) {
+                    answers[self.index] = byte.unwrap_or(0x00);
+                }
+
+                self.index += 1;
+                if self.index == 4 {
+                    self.ping_completed();
+                }
+            }
+            Phase::Transition => {
+                self.index += 1;
+                if self.index == 4 {
+                    self.start_transmission();
+                }
+            }
+            Phase::Transmission => {
+                if self.index < self.size {
+                    for (port, byte) in incoming.iter().enumerate() {
+                        if self.connected[port] {
+                            self.next_packet[port * self.size + self.index] = byte.unwrap_or(0x00);
+                        }
+                    }
+                }
+
+                self.index += 1;
+                if self.index == self.packet.len() {
+                    self.transmission_completed();
+                }
+            }
+        }
+    }
+
+    fn ping_completed(&mut self) {
+        self.index = 0;
+
+        // The players connected during the previous pings take part in the transmission
+        if self.connected[0] && self.ping_answers[0].contains(&START_TRANSMISSION) {
+            self.phase = Phase::Transition;
+            return;
+        }
+
+        for (connected, answers) in self.connected.iter_mut().zip(&self.ping_answers) {
+            *connected = answers[0] == PING_ACK && answers[1] == PING_ACK;
+        }
+
+        if self.connected[0] {
+            self.rate = self.ping_answers[0][2];
+            self.size = (self.ping_answers[0][3] as usize).max(1);
+        }
+    }
+
+    fn start_transmission(&mut self) {
+        self.phase = Phase::Transmission;
+        self.index = 0;
+        self.packet = vec![0x00; PORT_COUNT * self.size];
+        self.next_packet = vec![0x00; PORT_COUNT * self.size];
+    }
+
+    fn transmission_completed(&mut self) {
+        self.index = 0;
+
+        let size = self.size;
+        let restart = self
+            .next_packet
+            .chunks_exact(size)
+            .zip(self.connected)
+            .filter(|(_, connected)| *connected)
+            .all(|(data, _)| data.iter().all(|byte| *byte == 0xFF));
+
+        if restart {
+            self.phase = Phase::Ping;
+            self.connected = [false; PORT_COUNT];
+        } else {
+            self.packet = std::mem::replace(&mut self.next_packet, vec![0x00; PORT_COUNT * size]);
+        }
+    }
+}
+
+/// Four Player Adapter (DMG-07) connecting up to four Gameboys stepped in lockstep
+pub struct FourPlayerAdapter {
+    gameboys: Vec<Gameboy>,
+    protocol: Protocol,
+    cycles: usize,
+}
+
+impl FourPlayerAdapter {
+    /// Plug the Gameboys in the ports of the adapter, starting with player 1
+    pub fn new(mut gameboys: Vec<Gameboy>) -> Result<Self, Error> {
+        if gameboys.len() > PORT_COUNT {
+            return Err(Error::TooManyGameboys(gameboys.len()));
+        }
+
+        for gameboy in &mut gameboys {
+            gameboy.serial.set_linked(true);
+        }
+
+        Ok(Self {
+            gameboys,
+            protocol: Protocol::new(),
+            cycles: 0,
+        })
+    }
+
+    /// Step all the Gameboys by one T-cycle, the adapter sends a byte to all the players at once
+    pub fn tick(
+        &mut self,
+    ) -> [Option<(Option<screen::Event>, Option<spu::SampleFrame>)>; PORT_COUNT] {
+        let results = std::array::from_fn(|idx| self.gameboys.get_mut(idx).map(Gameboy::tick));
+
+        // The adapter doesn't drive the line of a Gameboy trying to use its internal clock
+        for gameboy in &mut self.gameboys {
+            if gameboy.serial.take_clock_pulse() {
+                gameboy.serial_shift(0b1);
+            }
+        }
+
+        self.cycles += 1;
+        if self.cycles >= self.protocol.byte_interval() {
+            self.cycles = 0;
+
+            let mut incoming = [None; PORT_COUNT];
+            for (port, gameboy) in self.gameboys.iter_mut().enumerate() {
+                incoming[port] = Some(gameboy.serial_exchange(self.protocol.outgoing(port)));
+            }
+
+            self.protocol.received(incoming);
+        }
+
+        results
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.gameboys.len()
+    }
+
+    /// Gameboy of a player, starting at 0 for player 1
+    pub fn player(&mut self, idx: usize) -> &mut Gameboy {
+        &mut self.gameboys[idx]
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.protocol.phase
+    }
+
+    /// Players that answered the last ping
+    pub fn connected_players(&self) -> [bool; PORT_COUNT] {
+        self.protocol.connected
+    }
+
+    /// Unplug the Gameboys from the adapter
+    pub fn unplug(self) -> Vec<Gameboy> {
+        let mut gameboys = self.gameboys;
+
+        for gameboy in &mut gameboys {
+            gameboy.serial.set_linked(false);
+        }
+
+        gameboys
+    }
+}
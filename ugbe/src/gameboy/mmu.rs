@@ -10,12 +10,34 @@ impl MMU {
             boot_rom_enabled: true,
         }
     }
+
+    pub fn is_boot_rom_enabled(&self) -> bool {
+        self.boot_rom_enabled
+    }
+
+    /// Return the boot ROM if it is enabled and mapped at this address, the CGB boot ROM leaves a hole in
+    /// 0x100-0x1FF to let the cartridge header be read
+    fn mapped_boot_rom<'ctx>(
+        &self,
+        ctx: &super::components::MMUReadContext<'ctx>,
+        address: u16,
+    ) -> Option<&'ctx crate::bootrom::BootRom> {
+        match ctx.boot_rom {
+            Some(boot_rom) if self.boot_rom_enabled && boot_rom.is_mapped(address) => {
+                Some(boot_rom)
+            }
+            _ => None,
+        }
+    }
 }
 
 impl super::components::Mmu for MMU {
-    fn read_byte(&self, ctx: &super::components::MMUContext, address: u16) -> u8 {
+    fn read_byte(&self, ctx: &super::components::MMUReadContext, address: u16) -> u8 {
+        if let Some(boot_rom) = self.mapped_boot_rom(ctx, address) {
+            return boot_rom[address];
+        }
+
         match address {
-            0x0..=0xFF if self.boot_rom_enabled => ctx.boot_rom[address as u8],
             0x0..=0x3FFF => ctx.cartridge.read_rom_bank_0(address),
             0x4000..=0x7FFF => ctx.cartridge.read_rom_bank_n(address - 0x4000),
             0x8000..=0x9FFF => ctx.ppu.read_vram_byte(address - 0x8000),
@@ -24,7 +46,10 @@ impl super::components::Mmu for MMU {
             0xD000..=0xDFFF => ctx.work_ram[address - 0xD000 + 0x1000], // Banked in CGB
             0xE000..=0xEFFF => ctx.work_ram[address - 0xE000],
             0xFE00..=0xFE9F => ctx.ppu.read_oam_byte(address - 0xFE00),
+            0xFEA0..=0xFEFF => ctx.ppu.read_unusable_byte(address),
             0xFF00 => ctx.joypad.read_p1(),
+            0xFF01 => ctx.serial.read_sb(),
+            0xFF02 => ctx.serial.read_sc(),
             0xFF04 => ctx.timer.read_div(),
             0xFF05 => ctx.timer.read_tima(),
             0xFF06 => ctx.timer.read_tma(),
@@ -69,6 +94,7 @@ impl super::components::Mmu for MMU {
             0xFF49 => ctx.ppu.read_obp1(),
             0xFF4A => ctx.ppu.read_wy(),
             0xFF4B => ctx.ppu.read_wx(),
+            0xFF56 => ctx.infrared.read_rp(),
             0xFF50 => {
                 if self.boot_rom_enabled {
                     0xFF
@@ -86,8 +112,11 @@ impl super::components::Mmu for MMU {
     }
 
     fn write_byte(&mut self, ctx: &mut super::components::MMUContext, address: u16, value: u8) {
+        if self.mapped_boot_rom(&ctx.as_read(), address).is_some() {
+            return;
+        }
+
         match address {
-            0x0..=0xFF if self.boot_rom_enabled => {}
             0x0..=0x7FFF => ctx.cartridge.write_rom(address, value),
             0x8000..=0x9FFF => ctx.ppu.write_vram_byte(address - 0x8000, value),
             0xA000..=0xBFFF => ctx.cartridge.write_ram(address - 0xA000, value),
@@ -96,6 +125,8 @@ impl super::components::Mmu for MMU {
             0xE000..=0xEFFF => ctx.work_ram[address - 0xE000] = value,
             0xFE00..=0xFE9F => ctx.ppu.write_oam_byte(address - 0xFE00, value),
             0xFF00 => ctx.joypad.write_p1(value),
+            0xFF01 => ctx.serial.write_sb(value),
+            0xFF02 => ctx.serial.write_sc(value),
             0xFF04 => ctx.timer.write_div(value),
             0xFF05 => ctx.timer.write_tima(value),
             0xFF06 => ctx.timer.write_tma(value),
@@ -135,7 +166,7 @@ impl super::components::Mmu for MMU {
                 // TODO: Properly do the DMA
                 let src = (value as u16) * 0x100;
                 for i in 0..=0x9f {
-                    let value = self.read_byte(ctx, src + i);
+                    let value = self.read_byte(&ctx.as_read(), src + i);
                     ctx.ppu.write_oam_byte(i, value);
                 }
             }
@@ -145,6 +176,7 @@ impl super::components::Mmu for MMU {
             0xFF4A => ctx.ppu.write_wy(value),
             0xFF4B => ctx.ppu.write_wx(value),
             0xFF50 => self.boot_rom_enabled = value & 0x1 == 0x0,
+            0xFF56 => ctx.infrared.write_rp(value),
             0xFF80..=0xFFFE => ctx.high_ram[address - 0xFF80] = value,
             0xFFFF => ctx.interrupt.set_enable(value),
             _ => {
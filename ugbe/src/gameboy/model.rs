@@ -0,0 +1,106 @@
+use super::screen;
+
+/// Hardware model being emulated, each model comes with its own boot ROM, its own register values after the
+/// boot and a few quirks.
+///
+/// Only the differences between the models are emulated, the CGB specific hardware (VRAM and WRAM banking,
+/// color palettes, double speed, ...) is not available, meaning that the CGB and AGB models behave like a DMG
+/// with the CGB quirks.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Model {
+    /// Early DMG revision, only released in Japan
+    DMG0,
+    /// Original Game Boy
+    #[default]
+    DMG,
+    /// Game Boy Pocket and Game Boy Light
+    MGB,
+    /// Super Game Boy
+    SGB,
+    /// Super Game Boy 2
+    SGB2,
+    /// Game Boy Color
+    CGB,
+    /// Game Boy Advance running a Game Boy cartridge
+    AGB,
+}
+
+impl Model {
+    pub fn is_cgb(&self) -> bool {
+        matches!(self, Self::CGB | Self::AGB)
+    }
+
+    pub fn is_sgb(&self) -> bool {
+        matches!(self, Self::SGB | Self::SGB2)
+    }
+
+    /// Size of the boot ROM expected by this model
+    pub fn boot_rom_size(&self) -> usize {
+        if self.is_cgb() {
+            0x900
+        } else {
+            0x100
+        }
+    }
+
+    /// The OAM corruption bug has been fixed on the CGB
+    pub fn has_oam_bug(&self) -> bool {
+        !self.is_cgb()
+    }
+
+    /// Writing to STAT triggers a spurious STAT interrupt on the DMG, this has been fixed on the CGB
+    pub fn has_stat_write_glitch(&self) -> bool {
+        !self.is_cgb()
+    }
+
+    /// On the DMG the wave RAM can only be accessed while the wave voice is playing during the cycle where the
+    /// voice reads it, and retriggering the voice while it reads the RAM corrupts its first bytes.
+    pub fn has_wave_ram_quirks(&self) -> bool {
+        !self.is_cgb()
+    }
+
+    /// At the end of the OAM scan the DMG locks the VRAM reads and unlocks the OAM writes 6 T-cycles before switching
+    /// to Drawing, the CGB waits for Drawing to lock the VRAM
+    pub fn has_early_vram_read_lock(&self) -> bool {
+        !self.is_cgb()
+    }
+
+    /// Value read from the unusable region 0xFEA0-0xFEFF when the OAM isn't locked by the PPU
+    pub fn unusable_region_value(&self, address: u16) -> u8 {
+        if self.is_cgb() {
+            // Revision E of the CGB and the AGB repeat the upper nibble of the lower byte of the address
+            let nibble = (address as u8) & 0xF0;
+            nibble | (nibble >> 4)
+        } else {
+            0x00
+        }
+    }
+
+    /// Color palette matching the screen of the model, the Pocket uses a black and white screen while the
+    /// other monochrome models have the well-known greenish screen.
+    pub fn color_palette(&self) -> screen::ColorPalette {
+        match self {
+            Self::MGB => screen::ColorPalette::new_pocket(),
+            _ => screen::ColorPalette::new_legacy(),
+        }
+    }
+
+    /// Values of the CPU registers AF, BC, DE and HL once the boot ROM has finished.
+    ///
+    /// A and B are used by games to detect if they are running on a CGB (A = 0x11) or an AGB (B bit 0 set), on
+    /// the DMG and MGB the flags H and C depend on the header checksum of the cartridge.
+    pub fn post_boot_registers(&self, header_checksum: u8) -> [u16; 4] {
+        let dmg_flags = if header_checksum == 0 { 0x80 } else { 0xB0 };
+
+        match self {
+            Self::DMG0 => [0x0100, 0xFF13, 0x00C1, 0x8403],
+            Self::DMG => [0x0100 | dmg_flags, 0x0013, 0x00D8, 0x014D],
+            Self::MGB => [0xFF00 | dmg_flags, 0x0013, 0x00D8, 0x014D],
+            Self::SGB => [0x0100, 0x0014, 0x0000, 0xC060],
+            Self::SGB2 => [0xFF00, 0x0014, 0x0000, 0xC060],
+            Self::CGB => [0x1180, 0x0000, 0xFF56, 0x000D],
+            Self::AGB => [0x1100, 0x0100, 0xFF56, 0x000D],
+        }
+    }
+}
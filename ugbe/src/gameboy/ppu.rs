@@ -3,6 +3,7 @@ use std::fmt::Debug;
 use self::oam::SpriteNo;
 
 use super::components::{InterruptKind, InterruptLine};
+use super::model::Model;
 
 mod color;
 mod fetcher;
@@ -12,6 +13,14 @@ mod registers;
 pub mod screen;
 mod tiling;
 
+/// Kind of access seen by the PPU on the address bus that could trigger the OAM corruption bug
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OamBugAccess {
+    Read,
+    Write,
+    ReadIncDec,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Mode {
     OAMScan {
@@ -21,6 +30,7 @@ pub enum Mode {
         sprite_no: oam::SpriteNo,
         current_sprite: Option<oam::Sprite>,
         win_ly: u8,
+        elapsed_cycles: usize,
     },
     Drawing {
         sprite_buffer: [Option<oam::Sprite>; 10],
@@ -55,6 +65,7 @@ impl Default for Mode {
             sprite_no: SpriteNo::first(),
             current_sprite: None,
             win_ly: 0,
+            elapsed_cycles: 0,
         }
     }
 }
@@ -78,7 +89,10 @@ impl Mode {
                 sprite_no,
                 current_sprite,
                 win_ly,
+                elapsed_cycles,
             } => {
+                *elapsed_cycles += 1;
+
                 // Each check of sprite is done on two T-cycles, as we have 40 sprites in the OAM this mode uses 80 T-cycles.
                 match current_sprite {
                     None => {
@@ -261,13 +275,8 @@ impl Mode {
                 }
 
                 if *lx >= 160 {
-                    *self = Self::switch_from_drawing_to_hblank(
-                        ppu_ctx,
-                        interrupt_line,
-                        *elapsed_cycles,
-                        *wy_match_ly,
-                        *win_ly,
-                    );
+                    *self =
+                        Self::switch_from_drawing_to_hblank(*elapsed_cycles, *wy_match_ly, *win_ly);
 
                     None
                 } else {
@@ -288,12 +297,8 @@ impl Mode {
 
                         Some(screen::Event::VBlank)
                     } else {
-                        *self = Self::switch_from_hblank_to_oam_scan(
-                            ppu_ctx,
-                            interrupt_line,
-                            *wy_match_ly,
-                            *win_ly,
-                        );
+                        *self =
+                            Self::switch_from_hblank_to_oam_scan(ppu_ctx, *wy_match_ly, *win_ly);
 
                         None
                     }
@@ -309,7 +314,7 @@ impl Mode {
 
                 if *elapsed_cycles_line == 456 {
                     if *ly == 153 {
-                        *self = Self::switch_from_vblank_to_oam_scan(ppu_ctx, interrupt_line);
+                        *self = Self::switch_from_vblank_to_oam_scan();
 
                         None
                     } else {
@@ -317,7 +322,7 @@ impl Mode {
                         *elapsed_cycles_line = 0;
 
                         ppu_ctx.ly += 1;
-                        ppu_ctx.check_lyc_compare(interrupt_line);
+                        ppu_ctx.check_lyc_compare();
 
                         None
                     }
@@ -325,7 +330,7 @@ impl Mode {
                     if *elapsed_cycles_line == 4 && *ly == 153 {
                         // Simulate that the VBlank change LY to 0 after 4 T-cycles in the line 153
                         ppu_ctx.ly = 0;
-                        ppu_ctx.check_lyc_compare(interrupt_line);
+                        ppu_ctx.check_lyc_compare();
                     }
 
                     None
@@ -340,6 +345,8 @@ impl Mode {
         wy_match_ly: bool,
         win_ly: u8,
     ) -> Self {
+        ppu_ctx.first_line_after_lcd_on = false;
+
         sprite_buffer.sort_by(|a, b| match a {
             Some(a) => match b {
                 Some(b) => a.x().cmp(&b.x()),
@@ -372,17 +379,7 @@ impl Mode {
         }
     }
 
-    fn switch_from_drawing_to_hblank(
-        ppu_ctx: &mut Context,
-        interrupt_line: &mut dyn InterruptLine,
-        elapsed_cycles: usize,
-        wy_match_ly: bool,
-        win_ly: u8,
-    ) -> Self {
-        if ppu_ctx.stat.hblank_interrupt_enabled() {
-            interrupt_line.request(InterruptKind::Stat);
-        }
-
+    fn switch_from_drawing_to_hblank(elapsed_cycles: usize, wy_match_ly: bool, win_ly: u8) -> Self {
         Self::HBlank {
             elapsed_cycles,
             win_ly,
@@ -395,12 +392,6 @@ impl Mode {
         interrupt_line: &mut dyn InterruptLine,
     ) -> Self {
         interrupt_line.request(InterruptKind::VBlank);
-        if ppu_ctx.stat.vblank_interrupt_enabled() {
-            interrupt_line.request(InterruptKind::Stat);
-        }
-        if ppu_ctx.stat.oam_scanning_interrupt_enabled() {
-            interrupt_line.request(InterruptKind::Stat);
-        }
 
         if ppu_ctx.skip_frame {
             ppu_ctx.skip_frame = false;
@@ -409,7 +400,7 @@ impl Mode {
         }
 
         ppu_ctx.ly += 1;
-        ppu_ctx.check_lyc_compare(interrupt_line);
+        ppu_ctx.check_lyc_compare();
 
         Self::VBlank {
             elapsed_cycles_line: 0,
@@ -419,16 +410,11 @@ impl Mode {
 
     fn switch_from_hblank_to_oam_scan(
         ppu_ctx: &mut Context,
-        interrupt_line: &mut dyn InterruptLine,
         wy_match_ly: bool,
         win_ly: u8,
     ) -> Self {
-        if ppu_ctx.stat.oam_scanning_interrupt_enabled() {
-            interrupt_line.request(InterruptKind::Stat);
-        }
-
         ppu_ctx.ly += 1;
-        ppu_ctx.check_lyc_compare(interrupt_line);
+        ppu_ctx.check_lyc_compare();
 
         Self::OAMScan {
             sprite_buffer: [None; 10],
@@ -437,27 +423,108 @@ impl Mode {
             sprite_no: oam::SpriteNo::first(),
             current_sprite: None,
             win_ly,
+            elapsed_cycles: 0,
         }
     }
 
-    fn switch_from_vblank_to_oam_scan(
-        ppu_ctx: &mut Context,
-        interrupt_line: &mut dyn InterruptLine,
-    ) -> Self {
-        if ppu_ctx.stat.oam_scanning_interrupt_enabled() {
-            interrupt_line.request(InterruptKind::Stat);
+    fn switch_from_vblank_to_oam_scan() -> Self {
+        Self::default()
+    }
+
+    /// Whether one of the sources of the STAT interrupt line related to the current mode is active
+    fn stat_line(&self, ppu_ctx: &Context, stat: registers::Stat) -> bool {
+        match self {
+            Self::HBlank { .. } => stat.hblank_interrupt_enabled(),
+            Self::VBlank {
+                elapsed_cycles_line,
+                ly,
+            } => {
+                // When entering VBlank, the OAM scanning source is also triggered as if it was a normal line
+                stat.vblank_interrupt_enabled()
+                    || (*ly == 144
+                        && *elapsed_cycles_line == 0
+                        && stat.oam_scanning_interrupt_enabled())
+            }
+            Self::OAMScan { .. } => {
+                !ppu_ctx.first_line_after_lcd_on && stat.oam_scanning_interrupt_enabled()
+            }
+            Self::Drawing { .. } => false,
         }
+    }
 
-        Self::default()
+    /// Bits 0-1 of STAT as seen by the CPU
+    fn stat_bits(&self, ppu_ctx: &Context) -> u8 {
+        match self {
+            Self::HBlank { .. } => 0b00,
+            Self::VBlank { .. } => 0b01,
+            // The first line after turning the LCD on doesn't have an OAM scan and stays in HBlank instead
+            Self::OAMScan { .. } if ppu_ctx.first_line_after_lcd_on => 0b00,
+            Self::OAMScan { .. } => 0b10,
+            Self::Drawing { .. } => 0b11,
+        }
+    }
+
+    /// Row of the OAM currently accessed by the PPU if it is scanning the OAM
+    fn oam_scan_row(&self, ppu_ctx: &Context) -> Option<usize> {
+        match self {
+            Self::OAMScan { sprite_no, .. } if !ppu_ctx.first_line_after_lcd_on => {
+                Some(sprite_no.row())
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the CPU is currently unable to read the VRAM.
+    ///
+    /// The VRAM is locked from the first T-cycle of Drawing to the first T-cycle of HBlank, the DMG starts locking
+    /// the reads a few T-cycles earlier, at the end of the OAM scan.
+    fn vram_read_locked(&self, model: Model) -> bool {
+        match self {
+            Self::OAMScan { elapsed_cycles, .. } => {
+                model.has_early_vram_read_lock() && *elapsed_cycles >= Self::EARLY_VRAM_READ_LOCK
+            }
+            Self::Drawing { .. } => true,
+            Self::HBlank { .. } | Self::VBlank { .. } => false,
+        }
+    }
+
+    /// Whether the CPU is currently unable to write the VRAM
+    fn vram_write_locked(&self) -> bool {
+        matches!(self, Self::Drawing { .. })
+    }
+
+    /// Whether the CPU is currently unable to read the OAM, from the first T-cycle of the OAM scan to the first
+    /// T-cycle of HBlank
+    fn oam_read_locked(&self, ppu_ctx: &Context) -> bool {
+        match self {
+            Self::OAMScan { .. } => !ppu_ctx.first_line_after_lcd_on,
+            Self::Drawing { .. } => true,
+            Self::HBlank { .. } | Self::VBlank { .. } => false,
+        }
+    }
+
+    /// Whether the CPU is currently unable to write the OAM, the DMG briefly unlocks the writes when it starts
+    /// locking the VRAM reads at the end of the OAM scan
+    fn oam_write_locked(&self, ppu_ctx: &Context, model: Model) -> bool {
+        match self {
+            Self::OAMScan { .. } if self.vram_read_locked(model) => false,
+            _ => self.oam_read_locked(ppu_ctx),
+        }
     }
+
+    /// T-cycle of the OAM scan, which lasts 80 T-cycles, from which the DMG locks the VRAM reads
+    const EARLY_VRAM_READ_LOCK: usize = 74;
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Context {
     skip_frame: bool,
+    first_line_after_lcd_on: bool,
     lcdc: registers::Lcdc,
     lyc_compare: bool,
     stat: registers::Stat,
+    stat_line: bool,
+    stat_write_glitch: bool,
     scy: u8,
     scx: u8,
     ly: u8,
@@ -476,9 +543,12 @@ impl Context {
     pub fn new(screen_config: screen::Config) -> Self {
         Self {
             skip_frame: false,
+            first_line_after_lcd_on: false,
             lcdc: 0.into(),
             lyc_compare: false,
             stat: 0.into(),
+            stat_line: false,
+            stat_write_glitch: false,
             scy: 0,
             scx: 0,
             ly: 0,
@@ -494,16 +564,8 @@ impl Context {
         }
     }
 
-    pub fn check_lyc_compare(&mut self, interrupt_line: &mut dyn InterruptLine) {
-        if self.ly != self.lyc {
-            self.lyc_compare = false;
-        } else {
-            self.lyc_compare = true;
-
-            if self.stat.lyc_interrupt_enabled() {
-                interrupt_line.request(InterruptKind::Stat);
-            }
-        }
+    pub fn check_lyc_compare(&mut self) {
+        self.lyc_compare = self.ly == self.lyc;
     }
 }
 
@@ -513,6 +575,9 @@ pub struct PPU {
     mode: Mode,
     ctx: Context,
     pending_lcd_event: Option<screen::Event>,
+    memory_locking: bool,
+    model: Model,
+    stubbed_ly: Option<u8>,
 }
 
 impl Debug for PPU {
@@ -528,45 +593,173 @@ impl Debug for PPU {
 }
 
 impl PPU {
-    pub fn new(screen_config: screen::Config) -> Self {
+    pub fn new(screen_config: screen::Config, model: Model, memory_locking: bool) -> Self {
+        let mut ctx = Context::new(screen_config);
+        if model.is_sgb() {
+            ctx.screen.enable_sgb();
+        }
+
         Self {
             mode: Mode::default(),
-            ctx: Context::new(screen_config),
+            ctx,
             pending_lcd_event: None,
+            memory_locking,
+            model,
+            stubbed_ly: None,
+        }
+    }
+
+    pub fn sgb_screen(&self) -> Option<&screen::SgbScreen> {
+        self.ctx.screen.sgb_screen()
+    }
+
+    pub fn sgb_command(&mut self, command: &super::sgb::Command) {
+        self.ctx.screen.sgb_command(command);
+    }
+
+    /// Allow to disable the locking of the VRAM and OAM during PPU modes, this is not what the hardware is doing
+    /// but it can be useful for debugging purposes.
+    pub fn set_memory_locking(&mut self, memory_locking: bool) {
+        self.memory_locking = memory_locking;
+    }
+
+    /// Make the CPU always read the given value from LY instead of the current line, like the Gameboy Doctor logs
+    /// expecting 0x90. The PPU keeps drawing the lines as usual.
+    pub fn set_stubbed_ly(&mut self, stubbed_ly: Option<u8>) {
+        self.stubbed_ly = stubbed_ly;
+    }
+
+    /// On DMG, accessing the OAM or having the IDU put an address of the OAM on the address bus while the PPU is
+    /// scanning the OAM corrupts its content.
+    pub fn oam_bug(&mut self, address: u16, access: OamBugAccess) {
+        if !self.model.has_oam_bug()
+            || !self.ctx.lcdc.lcd_enabled()
+            || !(0xFE00..=0xFEFF).contains(&address)
+        {
+            return;
+        }
+
+        if let Some(row) = self.mode.oam_scan_row(&self.ctx) {
+            match access {
+                OamBugAccess::Read => self.ctx.oam.corrupt_read(row),
+                OamBugAccess::Write => self.ctx.oam.corrupt_write(row),
+                OamBugAccess::ReadIncDec => self.ctx.oam.corrupt_read_inc_dec(row),
+            }
         }
     }
 
+    /// Whether the CPU is currently unable to read the VRAM
+    pub fn vram_locked(&self) -> bool {
+        self.is_locked(self.mode.vram_read_locked(self.model))
+    }
+
+    /// Whether the CPU is currently unable to read the OAM
+    pub fn oam_locked(&self) -> bool {
+        self.is_locked(self.mode.oam_read_locked(&self.ctx))
+    }
+
+    /// Whether the CPU is currently unable to write the VRAM
+    pub fn vram_write_locked(&self) -> bool {
+        self.is_locked(self.mode.vram_write_locked())
+    }
+
+    /// Whether the CPU is currently unable to write the OAM
+    pub fn oam_write_locked(&self) -> bool {
+        self.is_locked(self.mode.oam_write_locked(&self.ctx, self.model))
+    }
+
+    fn is_locked(&self, locked_by_mode: bool) -> bool {
+        self.memory_locking && self.ctx.lcdc.lcd_enabled() && locked_by_mode
+    }
+
     pub fn tick(&mut self, interrupt_line: &mut dyn InterruptLine) -> Option<screen::Event> {
         let screen_event = self.mode.execute(&mut self.ctx, interrupt_line);
+        self.update_stat_line(interrupt_line);
+
         let lcd_event = self.pending_lcd_event.take();
         screen_event.or(lcd_event)
     }
 
+    /// All the sources of the STAT interrupt are ORed together in a single line, the interrupt is only requested on
+    /// a rising edge of this line meaning that a source becoming active while another one is already active won't
+    /// request a new interrupt (this is known as "STAT blocking").
+    fn update_stat_line(&mut self, interrupt_line: &mut dyn InterruptLine) {
+        if !self.ctx.lcdc.lcd_enabled() {
+            self.ctx.stat_line = false;
+            self.ctx.stat_write_glitch = false;
+            return;
+        }
+
+        // On DMG, writing to STAT behaves as if all the sources were enabled during one cycle
+        let stat = if std::mem::take(&mut self.ctx.stat_write_glitch) {
+            registers::Stat::from(0xFF)
+        } else {
+            self.ctx.stat
+        };
+
+        let stat_line = (self.ctx.lyc_compare && stat.lyc_interrupt_enabled())
+            || self.mode.stat_line(&self.ctx, stat);
+
+        if stat_line && !self.ctx.stat_line {
+            interrupt_line.request(InterruptKind::Stat);
+        }
+
+        self.ctx.stat_line = stat_line;
+    }
+
+    /// Content of the VRAM, regardless of the locking
+    pub fn vram(&self) -> &[u8; 0x2000] {
+        &self.ctx.vram
+    }
+
+    pub fn mut_vram(&mut self) -> &mut [u8; 0x2000] {
+        &mut self.ctx.vram
+    }
+
+    /// Content of the OAM, regardless of the locking
+    pub fn oam(&self) -> &oam::Oam {
+        &self.ctx.oam
+    }
+
+    pub fn mut_oam(&mut self) -> &mut oam::Oam {
+        &mut self.ctx.oam
+    }
+
     pub fn read_vram_byte(&self, address: u16) -> u8 {
-        match self.mode {
-            Mode::Drawing { .. } if self.ctx.lcdc.lcd_enabled() => 0xFF,
-            _ => self.ctx.vram[address as usize],
+        if self.vram_locked() {
+            0xFF
+        } else {
+            self.ctx.vram[address as usize]
         }
     }
 
     pub fn write_vram_byte(&mut self, address: u16, value: u8) {
-        match self.mode {
-            Mode::Drawing { .. } if self.ctx.lcdc.lcd_enabled() => {}
-            _ => self.ctx.vram[address as usize] = value,
+        if !self.vram_write_locked() {
+            self.ctx.vram[address as usize] = value
         }
     }
 
     pub fn read_oam_byte(&self, address: u16) -> u8 {
-        match self.mode {
-            Mode::OAMScan { .. } | Mode::Drawing { .. } if self.ctx.lcdc.lcd_enabled() => 0xFF,
-            _ => self.ctx.oam.read_byte(address),
+        if self.oam_locked() {
+            0xFF
+        } else {
+            self.ctx.oam.read_byte(address)
+        }
+    }
+
+    /// Read the unusable region 0xFEA0-0xFEFF which is located right after the OAM, the returned value depends on
+    /// the model.
+    pub fn read_unusable_byte(&self, address: u16) -> u8 {
+        if self.oam_locked() {
+            0xFF
+        } else {
+            self.model.unusable_region_value(address)
         }
     }
 
     pub fn write_oam_byte(&mut self, address: u16, value: u8) {
-        match self.mode {
-            Mode::OAMScan { .. } | Mode::Drawing { .. } if self.ctx.lcdc.lcd_enabled() => {}
-            _ => self.ctx.oam.write_byte(address, value),
+        if !self.oam_write_locked() {
+            self.ctx.oam.write_byte(address, value)
         }
     }
 
@@ -581,6 +774,7 @@ impl PPU {
                 if new_lcdc.lcd_enabled() {
                     self.pending_lcd_event = Some(screen::Event::LCDOn);
                     self.ctx.skip_frame = true;
+                    self.ctx.first_line_after_lcd_on = true;
 
                     self.mode = Mode::default();
                 } else {
@@ -603,12 +797,7 @@ impl PPU {
     }
 
     pub fn read_stat(&self) -> u8 {
-        let mode_bits = match self.mode {
-            Mode::HBlank { .. } => 0b0000,
-            Mode::VBlank { .. } => 0b0001,
-            Mode::OAMScan { .. } => 0b0010,
-            Mode::Drawing { .. } => 0b0011,
-        };
+        let mode_bits = self.mode.stat_bits(&self.ctx);
 
         let coincidence_flag = if self.ctx.lyc_compare { 0b0100 } else { 0b0000 };
 
@@ -620,7 +809,8 @@ impl PPU {
         //   bit 0-1: read only corresponding to the mode
         //   bit 2: read only corresponding to the coincidence flag
         //   bit 7: unused, always set to 1
-        self.ctx.stat = (value & 0b0111_1000).into()
+        self.ctx.stat = (value & 0b0111_1000).into();
+        self.ctx.stat_write_glitch = self.model.has_stat_write_glitch();
     }
 
     pub fn read_scy(&self) -> u8 {
@@ -640,7 +830,7 @@ impl PPU {
     }
 
     pub fn read_ly(&self) -> u8 {
-        self.ctx.ly
+        self.stubbed_ly.unwrap_or(self.ctx.ly)
     }
 
     pub fn write_ly(&mut self, _: u8) {
@@ -652,7 +842,11 @@ impl PPU {
     }
 
     pub fn write_lyc(&mut self, value: u8) {
-        self.ctx.lyc = value
+        self.ctx.lyc = value;
+
+        if self.ctx.lcdc.lcd_enabled() {
+            self.ctx.check_lyc_compare();
+        }
     }
 
     pub fn read_bgp(&self) -> u8 {
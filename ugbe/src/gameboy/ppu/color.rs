@@ -60,3 +60,15 @@ pub enum Color {
     Dmg(DMGColor),
     Off,
 }
+
+impl Color {
+    /// Index of the shade in the DMG palette, a screen turned off is displayed as white
+    pub fn shade(&self) -> u8 {
+        match self {
+            Color::Dmg(DMGColor::White) | Color::Off => 0,
+            Color::Dmg(DMGColor::LightGray) => 1,
+            Color::Dmg(DMGColor::DarkGray) => 2,
+            Color::Dmg(DMGColor::Black) => 3,
+        }
+    }
+}
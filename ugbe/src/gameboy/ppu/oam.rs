@@ -1,5 +1,7 @@
 const SPRITE_COUNT: usize = 40;
 const SPRITE_SIZE: usize = 4;
+const ROW_SIZE: usize = 8;
+const ROW_COUNT: usize = SPRITE_COUNT * SPRITE_SIZE / ROW_SIZE;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct SpriteNo(u8);
@@ -16,6 +18,11 @@ impl SpriteNo {
     pub fn last() -> Self {
         Self((SPRITE_COUNT - 1) as u8)
     }
+
+    /// Row of the OAM containing the sprite, a row being 8 bytes long it contains two sprites
+    pub fn row(&self) -> usize {
+        (self.0 as usize * SPRITE_SIZE) / ROW_SIZE
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -85,6 +92,70 @@ impl Oam {
         self.data[address as usize] = value;
     }
 
+    fn word(&self, row: usize, idx: usize) -> u16 {
+        let address = row * ROW_SIZE + idx * 2;
+        u16::from_le_bytes([self.data[address], self.data[address + 1]])
+    }
+
+    fn set_word(&mut self, row: usize, idx: usize, value: u16) {
+        let address = row * ROW_SIZE + idx * 2;
+        self.data[address..address + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn copy_row(&mut self, src_row: usize, dst_row: usize) {
+        self.data.copy_within(
+            (src_row * ROW_SIZE)..((src_row + 1) * ROW_SIZE),
+            dst_row * ROW_SIZE,
+        );
+    }
+
+    /// Corrupt the row the PPU is accessing following a write (or an IDU operation) during the OAM scan
+    pub fn corrupt_write(&mut self, row: usize) {
+        if row == 0 || row >= ROW_COUNT {
+            return;
+        }
+
+        let a = self.word(row, 0);
+        let b = self.word(row - 1, 0);
+        let c = self.word(row - 1, 2);
+
+        self.set_word(row, 0, ((a ^ c) & (b ^ c)) ^ c);
+        self.data
+            .copy_within((row * ROW_SIZE - 6)..(row * ROW_SIZE), row * ROW_SIZE + 2);
+    }
+
+    /// Corrupt the row the PPU is accessing following a read during the OAM scan
+    pub fn corrupt_read(&mut self, row: usize) {
+        if row == 0 || row >= ROW_COUNT {
+            return;
+        }
+
+        let a = self.word(row, 0);
+        let b = self.word(row - 1, 0);
+        let c = self.word(row - 1, 2);
+
+        self.set_word(row, 0, b | (a & c));
+        self.data
+            .copy_within((row * ROW_SIZE - 6)..(row * ROW_SIZE), row * ROW_SIZE + 2);
+    }
+
+    /// Corrupt the rows around the one the PPU is accessing following a read happening at the same time as the IDU
+    /// is incrementing or decrementing the address during the OAM scan
+    pub fn corrupt_read_inc_dec(&mut self, row: usize) {
+        if (4..ROW_COUNT - 1).contains(&row) {
+            let a = self.word(row - 2, 0);
+            let b = self.word(row - 1, 0);
+            let c = self.word(row, 0);
+            let d = self.word(row - 1, 2);
+
+            self.set_word(row - 1, 0, (b & (a | c | d)) | (a & c & d));
+            self.copy_row(row - 1, row);
+            self.copy_row(row - 1, row - 2);
+        }
+
+        self.corrupt_read(row);
+    }
+
     pub fn sprite(&self, no: SpriteNo) -> Sprite {
         let idx_in_oam = ((no.0 % 40) * 4) as usize;
 
@@ -97,3 +168,99 @@ impl Oam {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Oam, ROW_SIZE};
+
+    /// OAM filled with a pattern, with the given rows before the corrupted one
+    fn oam_with_rows(first_row: usize, rows: &[[u8; ROW_SIZE]]) -> Oam {
+        let mut oam = Oam::new();
+        for (idx, byte) in oam.data.iter_mut().enumerate() {
+            *byte = idx as u8;
+        }
+        for (idx, row) in rows.iter().enumerate() {
+            oam.data[(first_row + idx) * ROW_SIZE..][..ROW_SIZE].copy_from_slice(row);
+        }
+
+        oam
+    }
+
+    fn row(oam: &Oam, row: usize) -> &[u8] {
+        &oam.data[row * ROW_SIZE..][..ROW_SIZE]
+    }
+
+    /// Rows 4 and 5, the first word of row 4 is b = 0xFF00, its third word is c = 0x0F0F and the first word of
+    /// row 5 is a = 0x00FF
+    const ROWS: [[u8; ROW_SIZE]; 2] = [
+        [0x00, 0xFF, 0x11, 0x22, 0x0F, 0x0F, 0x33, 0x44],
+        [0xFF, 0x00, 0x55, 0x66, 0x77, 0x88, 0x99, 0xAA],
+    ];
+
+    #[test]
+    fn write_corruption() {
+        let mut oam = oam_with_rows(4, &ROWS);
+        let untouched = oam.clone();
+        oam.corrupt_write(5);
+
+        // ((a ^ c) & (b ^ c)) ^ c = 0x0F0F, followed by the last three words of row 4
+        assert_eq!(
+            row(&oam, 5),
+            [0x0F, 0x0F, 0x11, 0x22, 0x0F, 0x0F, 0x33, 0x44]
+        );
+        for other in (0..super::ROW_COUNT).filter(|&other| other != 5) {
+            assert_eq!(row(&oam, other), row(&untouched, other));
+        }
+
+        // The first row is never corrupted
+        let mut oam = untouched.clone();
+        oam.corrupt_write(0);
+        assert_eq!(oam, untouched);
+    }
+
+    #[test]
+    fn read_corruption() {
+        let mut oam = oam_with_rows(4, &ROWS);
+        let untouched = oam.clone();
+        oam.corrupt_read(5);
+
+        // b | (a & c) = 0xFF0F, followed by the last three words of row 4
+        assert_eq!(
+            row(&oam, 5),
+            [0x0F, 0xFF, 0x11, 0x22, 0x0F, 0x0F, 0x33, 0x44]
+        );
+        for other in (0..super::ROW_COUNT).filter(|&other| other != 5) {
+            assert_eq!(row(&oam, other), row(&untouched, other));
+        }
+    }
+
+    #[test]
+    fn read_inc_dec_corruption() {
+        // Rows 4 to 6, a = 0xFF00, b = 0x00FF, c = 0x0FF0 and d = 0x8877
+        let rows = [
+            ROWS[0],
+            ROWS[1],
+            [0xF0, 0x0F, 0xBB, 0xCC, 0xDD, 0xEE, 0x12, 0x34],
+        ];
+        let mut oam = oam_with_rows(4, &rows);
+        let untouched = oam.clone();
+        oam.corrupt_read_inc_dec(6);
+
+        // Row 5 starts with (b & (a | c | d)) | (a & c & d) = 0x08F7 and is copied to rows 4 and 6, the read
+        // corruption of row 6 then leaves it unchanged
+        let corrupted = [0xF7, 0x08, 0x55, 0x66, 0x77, 0x88, 0x99, 0xAA];
+        for corrupted_row in 4..=6 {
+            assert_eq!(row(&oam, corrupted_row), corrupted);
+        }
+        for other in (0..super::ROW_COUNT).filter(|other| !(4..=6).contains(other)) {
+            assert_eq!(row(&oam, other), row(&untouched, other));
+        }
+
+        // Close to the start of the OAM, only the read corruption happens
+        let mut oam = oam_with_rows(2, &ROWS);
+        let mut read_only = oam.clone();
+        oam.corrupt_read_inc_dec(3);
+        read_only.corrupt_read(3);
+        assert_eq!(oam, read_only);
+    }
+}
@@ -1,3 +1,5 @@
+mod sgb;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum FrameBlending {
     Interframe(usize),
@@ -39,6 +41,17 @@ impl ColorPalette {
         )
     }
 
+    /// Black and white palette of the Game Boy Pocket screen
+    pub fn new_pocket() -> Self {
+        Self::new(
+            Color::from_rgb24(0xC9, 0xCE, 0xB4),
+            Color::from_rgb24(0xC4, 0xCA, 0xAE),
+            Color::from_rgb24(0x8C, 0x92, 0x7B),
+            Color::from_rgb24(0x4E, 0x53, 0x44),
+            Color::from_rgb24(0x1C, 0x1E, 0x19),
+        )
+    }
+
     pub fn new_legacy() -> Self {
         Self::new(
             Color::from_rgb24(0x8b, 0x92, 0x26),
@@ -49,6 +62,16 @@ impl ColorPalette {
         )
     }
 
+    /// Color of a DMG shade, from 0 (white) to 3 (black)
+    pub fn shade(&self, shade: u8) -> Color {
+        match shade & 0b11 {
+            0 => self.dmg_white,
+            1 => self.dmg_light_gray,
+            2 => self.dmg_dark_gray,
+            _ => self.dmg_black,
+        }
+    }
+
     fn color(&self, color: super::color::Color) -> Color {
         match color {
             super::color::Color::Dmg(dmg_color) => match dmg_color {
@@ -71,14 +94,14 @@ impl Default for ColorPalette {
 #[derive(Default, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Config {
     frame_blending: Option<FrameBlending>,
-    color_palette: ColorPalette,
+    color_palette: Option<ColorPalette>,
 }
 
 impl Config {
     pub fn new(frame_blending: Option<FrameBlending>, color_palette: ColorPalette) -> Self {
         Self {
             frame_blending,
-            color_palette,
+            color_palette: Some(color_palette),
         }
     }
 
@@ -87,7 +110,12 @@ impl Config {
     }
 
     pub fn set_color_palette(&mut self, color_palette: ColorPalette) {
-        self.color_palette = color_palette;
+        self.color_palette = Some(color_palette);
+    }
+
+    /// Use the given palette unless one has been explicitly set
+    pub(crate) fn set_default_color_palette(&mut self, color_palette: ColorPalette) {
+        self.color_palette.get_or_insert(color_palette);
     }
 }
 
@@ -142,10 +170,13 @@ impl Frame {
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Screen {
     config: Config,
+    color_palette: ColorPalette,
     idx_frame: usize,
     frames: Vec<Frame>,
     frame_being_draw: Frame,
     pixels: [Color; Self::WIDTH * Self::HEIGHT],
+    sgb: Option<sgb::Sgb>,
+    sgb_screen: Option<Box<SgbScreen>>,
 }
 
 impl Screen {
@@ -160,12 +191,17 @@ impl Screen {
             None => 1,
         };
 
+        let color_palette = config.color_palette.clone().unwrap_or_default();
+
         let mut screen = Self {
             config,
+            color_palette,
             idx_frame: 0,
             frames: vec![Frame::new(); required_frame_count],
             frame_being_draw: Frame::new(),
             pixels: [Color::new(0, 0, 0); Self::WIDTH * Self::HEIGHT],
+            sgb: None,
+            sgb_screen: None,
         };
 
         screen.off();
@@ -178,14 +214,68 @@ impl Screen {
         self.frame_being_draw.pixels[y * Screen::WIDTH + x] = color;
     }
 
+    /// Colorize the screen as done by the SGB
+    pub(in crate::gameboy) fn enable_sgb(&mut self) {
+        self.sgb = Some(sgb::Sgb::new());
+        self.sgb_screen = Some(Box::new(SgbScreen::new()));
+
+        self.update_pixels();
+        self.update_sgb_screen();
+    }
+
+    /// Screen including the SGB border, only available when emulating a SGB
+    pub fn sgb_screen(&self) -> Option<&SgbScreen> {
+        self.sgb_screen.as_deref()
+    }
+
+    fn update_sgb_screen(&mut self) {
+        if let (Some(sgb), Some(sgb_screen)) = (&self.sgb, &mut self.sgb_screen) {
+            sgb.render_border(&self.pixels, &mut sgb_screen.pixels);
+        }
+    }
+
+    pub(in crate::gameboy) fn sgb_command(&mut self, command: &crate::gameboy::sgb::Command) {
+        if let Some(sgb) = &mut self.sgb {
+            sgb.apply(command);
+        }
+    }
+
     pub(super) fn commit_frame(&mut self) {
         self.idx_frame = (self.idx_frame + 1) % self.frames.len();
         self.frames[self.idx_frame] = std::mem::replace(&mut self.frame_being_draw, Frame::new());
 
-        self.update_pixels()
+        if let Some(sgb) = &mut self.sgb {
+            sgb.frame_committed(&self.frames[self.idx_frame].pixels);
+        }
+
+        self.update_pixels();
+        self.update_sgb_screen();
+    }
+
+    fn color(&self, x: usize, y: usize, color: super::color::Color) -> Color {
+        match &self.sgb {
+            Some(sgb) => sgb.color(x, y, color),
+            None => self.color_palette.color(color),
+        }
     }
 
     pub fn update_pixels(&mut self) {
+        if let Some(sgb) = &self.sgb {
+            match sgb.mask() {
+                sgb::Mask::None => {}
+                sgb::Mask::Freeze => return,
+                sgb::Mask::Black => {
+                    self.pixels = [Color::new(0, 0, 0); Self::WIDTH * Self::HEIGHT];
+                    return;
+                }
+                sgb::Mask::Color0 => {
+                    self.pixels =
+                        [sgb.color(0, 0, super::color::Color::Off); Self::WIDTH * Self::HEIGHT];
+                    return;
+                }
+            }
+        }
+
         match self.config.frame_blending {
             Some(frame_blending) => match frame_blending {
                 FrameBlending::Interframe(frame_count) => {
@@ -208,10 +298,11 @@ impl Screen {
                                             * (1.0 / frame_count as f64));
                                     total_coeff += coeff;
 
-                                    let color = self
-                                        .config
-                                        .color_palette
-                                        .color(self.frames[idx].pixels[y * Self::WIDTH + x]);
+                                    let color = self.color(
+                                        x,
+                                        y,
+                                        self.frames[idx].pixels[y * Self::WIDTH + x],
+                                    );
 
                                     red += color.red() as f64 * coeff;
                                     green += color.green() as f64 * coeff;
@@ -231,10 +322,8 @@ impl Screen {
             None => {
                 for x in 0..Self::WIDTH {
                     for y in 0..Self::HEIGHT {
-                        self.pixels[y * Screen::WIDTH + x] = self
-                            .config
-                            .color_palette
-                            .color(self.frames[0].pixels[y * Self::WIDTH + x]);
+                        self.pixels[y * Screen::WIDTH + x] =
+                            self.color(x, y, self.frames[0].pixels[y * Self::WIDTH + x]);
                     }
                 }
             }
@@ -245,13 +334,41 @@ impl Screen {
         &self.pixels
     }
 
+    pub fn color_palette(&self) -> &ColorPalette {
+        &self.color_palette
+    }
+
     pub(super) fn off(&mut self) {
         for frame in &mut self.frames {
             frame.off();
         }
 
-        self.pixels =
-            [self.config.color_palette.color(super::color::Color::Off); Self::WIDTH * Self::HEIGHT];
+        self.pixels = [self.color(0, 0, super::color::Color::Off); Self::WIDTH * Self::HEIGHT];
+    }
+}
+
+/// Output of the SGB, the Game Boy screen surrounded by the border
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SgbScreen {
+    pixels: [Color; Self::WIDTH * Self::HEIGHT],
+}
+
+impl SgbScreen {
+    pub const WIDTH: usize = sgb::BORDER_TILES_WIDTH * 8;
+    pub const HEIGHT: usize = sgb::BORDER_TILES_HEIGHT * 8;
+
+    /// Position of the Game Boy screen inside the SGB screen
+    pub const SCREEN_X: usize = sgb::SCREEN_X;
+    pub const SCREEN_Y: usize = sgb::SCREEN_Y;
+
+    fn new() -> Self {
+        Self {
+            pixels: [Color::new(0, 0, 0); Self::WIDTH * Self::HEIGHT],
+        }
+    }
+
+    pub fn pixels(&self) -> &[Color; Self::WIDTH * Self::HEIGHT] {
+        &self.pixels
     }
 }
 
@@ -0,0 +1,448 @@
+use super::super::color;
+use super::Color;
+use crate::gameboy::sgb::{Command, CommandCode};
+
+const TILES_WIDTH: usize = 20;
+const TILES_HEIGHT: usize = 18;
+
+const SYSTEM_PALETTE_COUNT: usize = 512;
+const ATTRIBUTE_FILE_SIZE: usize = TILES_WIDTH * TILES_HEIGHT / 4;
+const ATTRIBUTE_FILE_COUNT: usize = 45;
+
+/// Size of the data sent through a VRAM transfer
+pub const TRANSFER_SIZE: usize = 0x1000;
+
+/// Number of frames between a transfer command and the frame from which the data is captured
+const TRANSFER_DELAY: usize = 3;
+
+/// Size of the border in tiles
+pub const BORDER_TILES_WIDTH: usize = 32;
+pub const BORDER_TILES_HEIGHT: usize = 28;
+
+/// Position of the Game Boy screen inside the border
+pub const SCREEN_X: usize = 48;
+pub const SCREEN_Y: usize = 40;
+
+const BORDER_TILE_COUNT: usize = 256;
+const BORDER_TILE_SIZE: usize = 32;
+const BORDER_MAP_SIZE: usize = 32 * 32;
+const BORDER_PALETTE_COUNT: usize = 4;
+
+type Palette = [Color; 4];
+type BorderPalette = [Color; 16];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mask {
+    None,
+    Freeze,
+    Black,
+    Color0,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Transfer {
+    Palettes,
+    AttributeFiles,
+    /// Half of the border tiles, the first half if false and the second half if true
+    BorderTiles(bool),
+    BorderMap,
+}
+
+/// Colorization of the screen done by the SGB, the screen is split in 20x18 cells of 8x8 pixels, each one using
+/// one of four palettes. The color 0 of the first palette is shared by all the palettes.
+///
+/// The SGB also draws a border around the screen made of 4bpp SNES tiles, the pixels using the color 0 of their
+/// palette are transparent.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Sgb {
+    palettes: [Palette; 4],
+    system_palettes: Box<[Palette; SYSTEM_PALETTE_COUNT]>,
+    attribute_files: Box<[[u8; ATTRIBUTE_FILE_SIZE]; ATTRIBUTE_FILE_COUNT]>,
+    attributes: [u8; TILES_WIDTH * TILES_HEIGHT],
+    mask: Mask,
+    pending_transfer: Option<(Transfer, usize)>,
+    border_tiles: Box<[[u8; BORDER_TILE_SIZE]; BORDER_TILE_COUNT]>,
+    border_map: Box<[u16; BORDER_MAP_SIZE]>,
+    border_palettes: [BorderPalette; BORDER_PALETTE_COUNT],
+}
+
+impl Sgb {
+    pub fn new() -> Self {
+        // Palette 1-A used by the SGB when the game doesn't set any palette
+        let default_palette = [
+            Color::from_rgb24(0xF8, 0xE8, 0xC8),
+            Color::from_rgb24(0xD8, 0x90, 0x48),
+            Color::from_rgb24(0xA8, 0x28, 0x20),
+            Color::from_rgb24(0x30, 0x18, 0x50),
+        ];
+
+        Self {
+            palettes: [default_palette; 4],
+            system_palettes: Box::new([default_palette; SYSTEM_PALETTE_COUNT]),
+            attribute_files: Box::new([[0; ATTRIBUTE_FILE_SIZE]; ATTRIBUTE_FILE_COUNT]),
+            attributes: [0; TILES_WIDTH * TILES_HEIGHT],
+            mask: Mask::None,
+            pending_transfer: None,
+            border_tiles: Box::new([[0; BORDER_TILE_SIZE]; BORDER_TILE_COUNT]),
+            border_map: Box::new([0; BORDER_MAP_SIZE]),
+            border_palettes: [[Color::new(0, 0, 0); 16]; BORDER_PALETTE_COUNT],
+        }
+    }
+
+    pub fn mask(&self) -> Mask {
+        self.mask
+    }
+
+    pub fn color(&self, x: usize, y: usize, color: color::Color) -> Color {
+        let shade = color.shade() as usize;
+
+        if shade == 0 {
+            return self.palettes[0][0];
+        }
+
+        let palette = self.attributes[(y / 8) * TILES_WIDTH + (x / 8)];
+        self.palettes[palette as usize][shade]
+    }
+
+    pub fn apply(&mut self, command: &Command) {
+        match command.code() {
+            CommandCode::PAL01 => self.set_palettes(command, 0, 1),
+            CommandCode::PAL23 => self.set_palettes(command, 2, 3),
+            CommandCode::PAL03 => self.set_palettes(command, 0, 3),
+            CommandCode::PAL12 => self.set_palettes(command, 1, 2),
+            CommandCode::ATTR_BLK => self.attribute_blocks(command),
+            CommandCode::ATTR_LIN => self.attribute_lines(command),
+            CommandCode::ATTR_DIV => self.attribute_division(command),
+            CommandCode::ATTR_CHR => self.attribute_characters(command),
+            CommandCode::PAL_SET => {
+                for (idx, palette) in self.palettes.iter_mut().enumerate() {
+                    let system_palette =
+                        u16::from_le_bytes([command.param(idx * 2), command.param(idx * 2 + 1)]);
+                    *palette = self.system_palettes[system_palette as usize % SYSTEM_PALETTE_COUNT];
+                }
+
+                self.set_attribute_file(command.param(8));
+            }
+            CommandCode::PAL_TRN => {
+                self.pending_transfer = Some((Transfer::Palettes, TRANSFER_DELAY))
+            }
+            CommandCode::ATTR_TRN => {
+                self.pending_transfer = Some((Transfer::AttributeFiles, TRANSFER_DELAY))
+            }
+            CommandCode::CHR_TRN => {
+                // Bit 1 selects the SNES sprite tiles which are not used by the border
+                if command.param(0) & 0b10 == 0 {
+                    let high = command.param(0) & 0b1 != 0;
+                    self.pending_transfer = Some((Transfer::BorderTiles(high), TRANSFER_DELAY));
+                }
+            }
+            CommandCode::PCT_TRN => {
+                self.pending_transfer = Some((Transfer::BorderMap, TRANSFER_DELAY))
+            }
+            CommandCode::ATTR_SET => self.set_attribute_file(command.param(0) | 0b1000_0000),
+            CommandCode::MASK_EN => {
+                self.mask = match command.param(0) & 0b11 {
+                    0 => Mask::None,
+                    1 => Mask::Freeze,
+                    2 => Mask::Black,
+                    3 => Mask::Color0,
+                    _ => unreachable!(),
+                }
+            }
+            CommandCode::MLT_REQ | CommandCode::Unsupported(_) => {}
+        }
+    }
+
+    /// Called for each frame displayed, used to capture the data of VRAM transfers
+    pub fn frame_committed(&mut self, pixels: &[color::Color]) {
+        let (transfer, delay) = match self.pending_transfer.take() {
+            Some(pending_transfer) => pending_transfer,
+            None => return,
+        };
+
+        if delay > 1 {
+            self.pending_transfer = Some((transfer, delay - 1));
+            return;
+        }
+
+        let data = transfer_data(pixels);
+
+        match transfer {
+            Transfer::Palettes => {
+                for (palette, data) in self.system_palettes.iter_mut().zip(data.chunks_exact(8)) {
+                    for (color, data) in palette.iter_mut().zip(data.chunks_exact(2)) {
+                        *color = snes_color(data[0], data[1]);
+                    }
+                }
+            }
+            Transfer::AttributeFiles => {
+                for (attribute_file, data) in self
+                    .attribute_files
+                    .iter_mut()
+                    .zip(data.chunks_exact(ATTRIBUTE_FILE_SIZE))
+                {
+                    attribute_file.copy_from_slice(data);
+                }
+            }
+            Transfer::BorderTiles(high) => {
+                let offset = if high { BORDER_TILE_COUNT / 2 } else { 0 };
+
+                for (tile, data) in self.border_tiles[offset..offset + BORDER_TILE_COUNT / 2]
+                    .iter_mut()
+                    .zip(data.chunks_exact(BORDER_TILE_SIZE))
+                {
+                    tile.copy_from_slice(data);
+                }
+            }
+            Transfer::BorderMap => {
+                for (entry, data) in self
+                    .border_map
+                    .iter_mut()
+                    .zip(data[..BORDER_MAP_SIZE * 2].chunks_exact(2))
+                {
+                    *entry = u16::from_le_bytes([data[0], data[1]]);
+                }
+
+                // The palettes 4 to 7 of the SNES follow the map
+                let palettes_data = &data[BORDER_MAP_SIZE * 2..];
+                for (palette, data) in self
+                    .border_palettes
+                    .iter_mut()
+                    .zip(palettes_data.chunks_exact(32))
+                {
+                    for (color, data) in palette.iter_mut().zip(data.chunks_exact(2)) {
+                        *color = snes_color(data[0], data[1]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Compose the border with the colorized Game Boy screen
+    pub fn render_border(&self, screen: &[Color], pixels: &mut [Color]) {
+        let width = BORDER_TILES_WIDTH * 8;
+        let backdrop = self.palettes[0][0];
+
+        for (idx, pixel) in pixels.iter_mut().enumerate() {
+            let (x, y) = (idx % width, idx / width);
+
+            let entry = self.border_map[(y / 8) * 32 + (x / 8)];
+            let tile = &self.border_tiles[(entry & 0xFF) as usize];
+            let palette = &self.border_palettes[((entry >> 10) & 0b11) as usize];
+
+            let column = if entry & (1 << 14) != 0 {
+                7 - x % 8
+            } else {
+                x % 8
+            };
+            let row = if entry & (1 << 15) != 0 {
+                7 - y % 8
+            } else {
+                y % 8
+            };
+
+            let color_idx = (0..4).fold(0, |color_idx, plane| {
+                let byte = tile[(plane / 2) * 16 + row * 2 + plane % 2];
+                color_idx | (((byte >> (7 - column)) & 0b1) << plane)
+            });
+
+            let screen_x = x.wrapping_sub(SCREEN_X);
+            let screen_y = y.wrapping_sub(SCREEN_Y);
+
+            *pixel = if color_idx != 0 {
+                palette[color_idx as usize]
+            } else if screen_x < super::Screen::WIDTH && screen_y < super::Screen::HEIGHT {
+                screen[screen_y * super::Screen::WIDTH + screen_x]
+            } else {
+                backdrop
+            };
+        }
+    }
+
+    fn set_palettes(&mut self, command: &Command, first: usize, second: usize) {
+        let color = |idx: usize| snes_color(command.param(idx * 2), command.param(idx * 2 + 1));
+
+        for palette in &mut self.palettes {
+            palette[0] = color(0);
+        }
+
+        for shade in 1..4 {
+            self.palettes[first][shade] = color(shade);
+            self.palettes[second][shade] = color(shade + 3);
+        }
+    }
+
+    /// Apply an attribute file, bit 7 has to be set for the file to be applied and bit 6 cancels the mask
+    fn set_attribute_file(&mut self, value: u8) {
+        if value & 0b1000_0000 == 0 {
+            return;
+        }
+
+        let attribute_file =
+            self.attribute_files[(value & 0b0011_1111) as usize % ATTRIBUTE_FILE_COUNT];
+        for (idx, attribute) in self.attributes.iter_mut().enumerate() {
+            *attribute = (attribute_file[idx / 4] >> (6 - (idx % 4) * 2)) & 0b11;
+        }
+
+        if value & 0b0100_0000 != 0 {
+            self.mask = Mask::None;
+        }
+    }
+
+    fn attribute_blocks(&mut self, command: &Command) {
+        let count = (command.param(0) & 0x1F) as usize;
+
+        for block in 0..count {
+            let param = |idx: usize| command.param(1 + block * 6 + idx);
+
+            let control = param(0) & 0b111;
+            let palettes = param(1);
+            let (x1, y1, x2, y2) = (param(2), param(3), param(4), param(5));
+
+            let inside = palettes & 0b11;
+            let border = (palettes >> 2) & 0b11;
+            let outside = (palettes >> 4) & 0b11;
+
+            // When only the inside or the outside is changed, the border uses the same palette
+            let (border_enabled, border) = match control {
+                0b001 => (true, inside),
+                0b100 => (true, outside),
+                control => (control & 0b010 != 0, border),
+            };
+
+            for y in 0..TILES_HEIGHT as u8 {
+                for x in 0..TILES_WIDTH as u8 {
+                    let in_x_range = (x1..=x2).contains(&x);
+                    let in_y_range = (y1..=y2).contains(&y);
+
+                    let palette = if in_x_range && in_y_range {
+                        if x == x1 || x == x2 || y == y1 || y == y2 {
+                            border_enabled.then_some(border)
+                        } else {
+                            (control & 0b001 != 0).then_some(inside)
+                        }
+                    } else {
+                        (control & 0b100 != 0).then_some(outside)
+                    };
+
+                    if let Some(palette) = palette {
+                        self.attributes[y as usize * TILES_WIDTH + x as usize] = palette;
+                    }
+                }
+            }
+        }
+    }
+
+    fn attribute_lines(&mut self, command: &Command) {
+        let count = command.param(0) as usize;
+
+        for line in 0..count {
+            let value = command.param(1 + line);
+            let coordinate = (value & 0b1_1111) as usize;
+            let palette = (value >> 5) & 0b11;
+
+            if value & 0b1000_0000 != 0 {
+                if coordinate < TILES_HEIGHT {
+                    self.attributes[coordinate * TILES_WIDTH..(coordinate + 1) * TILES_WIDTH]
+                        .fill(palette);
+                }
+            } else if coordinate < TILES_WIDTH {
+                for y in 0..TILES_HEIGHT {
+                    self.attributes[y * TILES_WIDTH + coordinate] = palette;
+                }
+            }
+        }
+    }
+
+    fn attribute_division(&mut self, command: &Command) {
+        let value = command.param(0);
+        let after = value & 0b11;
+        let before = (value >> 2) & 0b11;
+        let on_line = (value >> 4) & 0b11;
+        let horizontal = value & 0b0100_0000 != 0;
+        let coordinate = command.param(1) as usize;
+
+        for y in 0..TILES_HEIGHT {
+            for x in 0..TILES_WIDTH {
+                let position = if horizontal { y } else { x };
+
+                self.attributes[y * TILES_WIDTH + x] = match position.cmp(&coordinate) {
+                    std::cmp::Ordering::Less => before,
+                    std::cmp::Ordering::Equal => on_line,
+                    std::cmp::Ordering::Greater => after,
+                };
+            }
+        }
+    }
+
+    fn attribute_characters(&mut self, command: &Command) {
+        let mut x = command.param(0) as usize % TILES_WIDTH;
+        let mut y = command.param(1) as usize % TILES_HEIGHT;
+        let count = u16::from_le_bytes([command.param(2), command.param(3)]) as usize;
+        let vertical = command.param(4) & 0b1 != 0;
+
+        for idx in 0..count.min(TILES_WIDTH * TILES_HEIGHT) {
+            let value = command.param(5 + idx / 4);
+            self.attributes[y * TILES_WIDTH + x] = (value >> (6 - (idx % 4) * 2)) & 0b11;
+
+            if vertical {
+                y += 1;
+                if y == TILES_HEIGHT {
+                    y = 0;
+                    x = (x + 1) % TILES_WIDTH;
+                }
+            } else {
+                x += 1;
+                if x == TILES_WIDTH {
+                    x = 0;
+                    y = (y + 1) % TILES_HEIGHT;
+                }
+            }
+        }
+    }
+}
+
+impl Default for Sgb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Convert a little endian BGR555 color used by the SNES
+fn snes_color(lsb: u8, msb: u8) -> Color {
+    let value = u16::from_le_bytes([lsb, msb]);
+
+    Color::new(
+        (value & 0x1F) as u8,
+        ((value >> 5) & 0x1F) as u8,
+        ((value >> 10) & 0x1F) as u8,
+    )
+}
+
+/// The data of a VRAM transfer is read from the screen, the tiles displayed from left to right and top to bottom
+/// are encoded back to the 2bpp format of the tiles
+pub fn transfer_data(pixels: &[color::Color]) -> [u8; TRANSFER_SIZE] {
+    let mut data = [0; TRANSFER_SIZE];
+
+    for (tile, tile_data) in data.chunks_exact_mut(16).enumerate() {
+        let tile_x = (tile % TILES_WIDTH) * 8;
+        let tile_y = (tile / TILES_WIDTH) * 8;
+
+        for row in 0..8 {
+            let mut lsb = 0;
+            let mut msb = 0;
+
+            for column in 0..8 {
+                let shade = pixels[(tile_y + row) * super::Screen::WIDTH + tile_x + column].shade();
+
+                lsb |= (shade & 0b01) << (7 - column);
+                msb |= ((shade & 0b10) >> 1) << (7 - column);
+            }
+
+            tile_data[row * 2] = lsb;
+            tile_data[row * 2 + 1] = msb;
+        }
+    }
+
+    data
+}
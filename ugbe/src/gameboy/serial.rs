@@ -0,0 +1,224 @@
+use std::any::Any;
+
+use super::components::{InterruptKind, InterruptLine};
+
+pub mod barcode;
+pub mod mobile;
+pub mod printer;
+pub mod registry;
+pub mod tcp;
+
+/// Number of T-cycles needed to shift one bit with the internal clock (8192Hz)
+const T_CYCLES_PER_BIT: usize = 512;
+
+/// Device plugged on the other end of the link cable.
+///
+/// The exchange is done one byte at a time: the byte returned by the peripheral has to be ready before it receives
+/// the byte sent by the Gameboy as both are shifted at the same time on the hardware.
+pub trait Peripheral: Any + Send {
+    /// Exchange a byte with the Gameboy when the transfer is clocked by the Gameboy
+    fn exchange(&mut self, byte: u8) -> u8;
+
+    /// Start a transfer clocked by the Gameboy, remote peripherals can return `None` when the byte isn't available
+    /// yet and the transfer waits until it is returned by `poll_exchange`
+    fn begin_exchange(&mut self, byte: u8) -> Option<u8> {
+        Some(self.exchange(byte))
+    }
+
+    /// Byte received for a transfer started with `begin_exchange`
+    fn poll_exchange(&mut self) -> Option<u8> {
+        None
+    }
+
+    /// Check if the peripheral clocked a transfer itself, `data` is the content of SB and `ready` indicates if the
+    /// Gameboy started a transfer with the external clock. The peripheral is responsible for sending `data` back
+    /// and the received byte is only shifted in SB when the Gameboy is ready.
+    fn poll_external_clock(&mut self, _data: u8, _ready: bool) -> Option<u8> {
+        None
+    }
+
+    /// Called at each T-cycle, allow the peripheral to emulate time based behaviors
+    fn tick(&mut self) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Transfer {
+    Idle,
+    /// Transfer in progress with the number of bits already shifted
+    Active(u8),
+}
+
+pub struct Serial {
+    data: u8,
+    internal_clock: bool,
+    transfer: Transfer,
+    cycles: usize,
+    /// Byte returned by the peripheral for the current transfer, `None` while waiting for a remote peripheral
+    incoming: Option<u8>,
+    peripheral: Option<Box<dyn Peripheral>>,
+    /// When linked to another Gameboy, the bits are exchanged by the owner of the link cable
+    linked: bool,
+    clock_pulse: bool,
+}
+
+impl std::fmt::Debug for Serial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Serial")
+            .field("data", &self.data)
+            .field("internal_clock", &self.internal_clock)
+            .field("transfer", &self.transfer)
+            .field("peripheral", &self.peripheral.is_some())
+            .field("linked", &self.linked)
+            .finish()
+    }
+}
+
+impl Serial {
+    pub fn new() -> Self {
+        Self {
+            data: 0x00,
+            internal_clock: false,
+            transfer: Transfer::Idle,
+            cycles: 0,
+            incoming: Some(0xFF),
+            peripheral: None,
+            linked: false,
+            clock_pulse: false,
+        }
+    }
+
+    pub fn connect(&mut self, peripheral: Box<dyn Peripheral>) -> Option<Box<dyn Peripheral>> {
+        self.peripheral.replace(peripheral)
+    }
+
+    pub fn disconnect(&mut self) -> Option<Box<dyn Peripheral>> {
+        self.peripheral.take()
+    }
+
+    pub fn peripheral<T: Peripheral>(&self) -> Option<&T> {
+        let peripheral: &dyn Any = self.peripheral.as_deref()?;
+        peripheral.downcast_ref()
+    }
+
+    pub fn peripheral_mut<T: Peripheral>(&mut self) -> Option<&mut T> {
+        let peripheral: &mut dyn Any = self.peripheral.as_deref_mut()?;
+        peripheral.downcast_mut()
+    }
+
+    /// Let the owner of a link cable exchange the bits instead of the peripheral
+    pub(crate) fn set_linked(&mut self, linked: bool) {
+        self.linked = linked;
+        self.clock_pulse = false;
+    }
+
+    /// Check if the internal clock ticked since the last call, meaning that a bit has to be exchanged through the
+    /// link cable
+    pub(crate) fn take_clock_pulse(&mut self) -> bool {
+        std::mem::take(&mut self.clock_pulse)
+    }
+
+    /// Bit currently output on the link cable
+    pub(crate) fn output_bit(&self) -> u8 {
+        self.data >> 7
+    }
+
+    /// Shift a bit in SB on a clock edge, only done when a transfer is in progress
+    pub(crate) fn shift(&mut self, incoming: u8, interrupt_line: &mut dyn InterruptLine) {
+        if let Transfer::Active(shifted_bits) = self.transfer {
+            self.data = (self.data << 1) | (incoming & 0b1);
+
+            self.transfer = if shifted_bits + 1 == 8 {
+                interrupt_line.request(InterruptKind::Serial);
+                Transfer::Idle
+            } else {
+                Transfer::Active(shifted_bits + 1)
+            };
+        }
+    }
+
+    pub fn tick(&mut self, interrupt_line: &mut dyn InterruptLine) {
+        if let Some(peripheral) = &mut self.peripheral {
+            peripheral.tick();
+        }
+
+        // Transfer clocked by a remote peripheral, the whole byte is received at once
+        let ready = self.transfer != Transfer::Idle && !self.internal_clock;
+        let clocked = match &mut self.peripheral {
+            Some(peripheral) if !self.linked => peripheral.poll_external_clock(self.data, ready),
+            _ => None,
+        };
+
+        if let (true, Some(incoming)) = (ready, clocked) {
+            self.data = incoming;
+            self.transfer = Transfer::Idle;
+            interrupt_line.request(InterruptKind::Serial);
+        }
+
+        let shifted_bits = match self.transfer {
+            Transfer::Active(shifted_bits) if self.internal_clock => shifted_bits,
+            _ => return,
+        };
+
+        // The clock is stalled until a remote peripheral sent its byte
+        if !self.linked && self.incoming.is_none() {
+            self.incoming = self.peripheral.as_mut().and_then(|p| p.poll_exchange());
+            if self.incoming.is_none() {
+                return;
+            }
+        }
+
+        self.cycles += 1;
+        if self.cycles < T_CYCLES_PER_BIT {
+            return;
+        }
+
+        self.cycles = 0;
+
+        if self.linked {
+            self.clock_pulse = true;
+        } else if let Some(incoming) = self.incoming {
+            let bit = (incoming >> (7 - shifted_bits)) & 0b1;
+            self.shift(bit, interrupt_line);
+        }
+    }
+
+    pub fn read_sb(&self) -> u8 {
+        self.data
+    }
+
+    pub fn write_sb(&mut self, value: u8) {
+        self.data = value;
+    }
+
+    pub fn read_sc(&self) -> u8 {
+        let transfer_enabled = self.transfer != Transfer::Idle;
+        ((transfer_enabled as u8) << 7) | 0b0111_1110 | (self.internal_clock as u8)
+    }
+
+    pub fn write_sc(&mut self, value: u8) {
+        self.internal_clock = value & 0b1 != 0;
+
+        if value & 0b1000_0000 == 0 {
+            self.transfer = Transfer::Idle;
+            return;
+        }
+
+        self.transfer = Transfer::Active(0);
+        self.cycles = 0;
+
+        // The peripherals only exchange bytes when clocked by the Gameboy, without anything plugged the line is
+        // pulled up and only 1s are received
+        if self.internal_clock && !self.linked {
+            self.incoming = match &mut self.peripheral {
+                Some(peripheral) => peripheral.begin_exchange(self.data),
+                None => Some(0xFF),
+            };
+        }
+    }
+}
+
+impl Default for Serial {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::VecDeque;
+use std::{fs, io, path::Path};
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("invalid barcode (got '{0}', expected 13 digits)")]
+    InvalidBarcode(String),
+
+    #[error("failed to read the card file")]
+    ReadError(#[from] io::Error),
+}
+
+/// Number of digits of the JAN-13 barcodes printed on the cards
+pub const DIGIT_COUNT: usize = 13;
+
+/// Bytes sent by the game to detect the Barcode Boy, and the answers of the Barcode Boy
+const HANDSHAKE: [u8; 4] = [0x10, 0x07, 0x10, 0x07];
+const HANDSHAKE_RESPONSE: [u8; 4] = [0xFF, 0xFF, 0x10, 0x07];
+
+const START_OF_TEXT: u8 = 0x02;
+const END_OF_TEXT: u8 = 0x03;
+
+/// Number of T-cycles between two bytes sent when a card is swiped
+const T_CYCLES_PER_BYTE: usize = 0x1000;
+
+/// Card with a barcode that can be swiped through the Barcode Boy
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Card {
+    digits: [u8; DIGIT_COUNT],
+}
+
+impl Card {
+    /// Load a card from a text file containing its barcode
+    pub fn from_path<P: ?Sized + AsRef<Path>>(path: &P) -> Result<Self, Error> {
+        Self::from_barcode(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_barcode(barcode: &str) -> Result<Self, Error> {
+        let barcode = barcode.trim();
+
+        if barcode.len() != DIGIT_COUNT || !barcode.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(Error::InvalidBarcode(barcode.to_string()));
+        }
+
+        let mut digits = [0; DIGIT_COUNT];
+        digits.copy_from_slice(barcode.as_bytes());
+
+        Ok(Self { digits })
+    }
+
+    pub fn barcode(&self) -> &str {
+        // Only ASCII digits are accepted when building the card
+        std::str::from_utf8(&self.digits).unwrap()
+    }
+}
+
+/// Barcode Boy, a barcode reader used by a few Japanese games.
+///
+/// The game detects the reader by sending `0x10 0x07 0x10 0x07`, the reader answering `0xFF 0xFF 0x10 0x07`. When
+/// a card is swiped, the reader clocks the transfers itself to send the barcode as ASCII digits between `0x02`
+/// and `0x03`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BarcodeBoy {
+    cards: Vec<Card>,
+    next_card: usize,
+    handshake: usize,
+    detected: bool,
+    sending: VecDeque<u8>,
+    cycles: usize,
+}
+
+impl BarcodeBoy {
+    pub fn new(cards: Vec<Card>) -> Self {
+        Self {
+            cards,
+            next_card: 0,
+            handshake: 0,
+            detected: false,
+            sending: VecDeque::new(),
+            cycles: 0,
+        }
+    }
+
+    pub fn cards(&self) -> &[Card] {
+        &self.cards
+    }
+
+    /// Check if the game completed the handshake with the reader
+    pub fn is_detected(&self) -> bool {
+        self.detected
+    }
+
+    pub fn swipe(&mut self, card: &Card) {
+        self.sending.push_back(START_OF_TEXT);
+        self.sending.extend(card.digits);
+        self.sending.push_back(END_OF_TEXT);
+    }
+
+    /// Swipe the loaded cards one after the other, return the swiped card
+    pub fn swipe_next(&mut self) -> Option<&Card> {
+        if self.cards.is_empty() {
+            return None;
+        }
+
+        let idx = self.next_card % self.cards.len();
+        self.next_card = idx + 1;
+
+        let card = self.cards[idx].clone();
+        self.swipe(&card);
+
+        Some(&self.cards[idx])
+    }
+}
+
+impl super::Peripheral for BarcodeBoy {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        let response = HANDSHAKE_RESPONSE[self.handshake];
+
+        self.handshake = if byte == HANDSHAKE[self.handshake] {
+            if self.handshake + 1 == HANDSHAKE.len() {
+                self.detected = true;
+                0
+            } else {
+                self.handshake + 1
+            }
+        } else {
+            (byte == HANDSHAKE[0]) as usize
+        };
+
+        response
+    }
+
+    fn poll_external_clock(&mut self, _data: u8, ready: bool) -> Option<u8> {
+        // The bytes are only sent once the game is waiting for them
+        if !ready || self.cycles < T_CYCLES_PER_BYTE {
+            return None;
+        }
+
+        self.cycles = 0;
+        self.sending.pop_front()
+    }
+
+    fn tick(&mut self) {
+        if !self.sending.is_empty() {
+            self.cycles = (self.cycles + 1).min(T_CYCLES_PER_BYTE);
+        }
+    }
+}
@@ -0,0 +1,648 @@
+use std::any::Any;
+use std::collections::VecDeque;
+
+use thiserror::Error;
+
+pub mod fake;
+
+const MAGIC: [u8; 2] = [0x99, 0x66];
+
+/// Value returned by the adapter to identify itself after a packet (blue adapter for PDC phones)
+const DEVICE_ID: u8 = 0x88;
+
+/// Value sent by the adapter when it has nothing to send
+const IDLE: u8 = 0xD2;
+
+const ACK_UNKNOWN_COMMAND: u8 = 0xF0;
+const ACK_CHECKSUM_ERROR: u8 = 0xF1;
+
+/// Maximum size of the data of a packet
+const MAX_DATA_SIZE: usize = 254;
+
+const CONFIG_SIZE: usize = 192;
+const CONNECTION_COUNT: usize = 2;
+
+/// Identifier used by the game for the connection of the telephone
+const TELEPHONE_CONNECTION_ID: u8 = 0xFF;
+
+const SESSION_MAGIC: &[u8] = b"NINTENDO";
+
+const TELEPHONE_STATUS_IDLE: u8 = 0x00;
+const TELEPHONE_STATUS_CONNECTED: u8 = 0x05;
+
+/// Code reported when a command is sent in an unexpected state (no session, not connected, ...).
+///
+/// Specific to ugbe: the protocol only documents the codes 0 to 4, with a meaning depending on the command, so a
+/// value outside of this range is used rather than borrowing the meaning of one of them.
+const INVALID_STATE_CODE: u8 = 0x10;
+
+/// Code reported when the parameters of a command are malformed, specific to ugbe like `INVALID_STATE_CODE`
+const INVALID_PARAMETERS_CODE: u8 = 0x11;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Error {
+    #[error("the line is busy")]
+    LineBusy,
+
+    #[error("the login has been refused")]
+    LoginRefused,
+
+    #[error("unknown host")]
+    UnknownHost,
+
+    #[error("connection refused")]
+    ConnectionRefused,
+
+    #[error("connection closed")]
+    ConnectionClosed,
+}
+
+impl Error {
+    /// Error code reported to the game
+    fn code(&self) -> u8 {
+        match self {
+            Self::LineBusy => 0x00,
+            Self::LoginRefused => 0x01,
+            Self::UnknownHost => 0x02,
+            Self::ConnectionRefused => 0x03,
+            Self::ConnectionClosed => 0x04,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Connection {
+    /// Direct connection with the telephone line once dialed
+    Telephone,
+    Tcp(u8),
+    Udp(u8),
+}
+
+/// Network reached through the adapter, allowing to plug an in-process server for testing or a real one
+pub trait Backend: Any + Send {
+    fn dial(&mut self, number: &str) -> Result<(), Error>;
+
+    fn hang_up(&mut self);
+
+    /// Log in to the internet service provider, return the IP address given to the adapter
+    fn login(&mut self, id: &[u8], password: &[u8]) -> Result<[u8; 4], Error>;
+
+    fn logout(&mut self);
+
+    fn dns_query(&mut self, name: &str) -> Result<[u8; 4], Error>;
+
+    fn open(&mut self, connection: Connection, address: [u8; 4], port: u16) -> Result<(), Error>;
+
+    fn close(&mut self, connection: Connection);
+
+    fn send(&mut self, connection: Connection, data: &[u8]) -> Result<(), Error>;
+
+    /// Data received since the last call, at most `max_size` bytes
+    fn receive(&mut self, connection: Connection, max_size: usize) -> Result<Vec<u8>, Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Command {
+    BeginSession,
+    EndSession,
+    Dial,
+    HangUp,
+    TransferData,
+    TelephoneStatus,
+    ReadConfig,
+    WriteConfig,
+    TransferDataEnd,
+    IspLogin,
+    IspLogout,
+    OpenTcp,
+    CloseTcp,
+    OpenUdp,
+    CloseUdp,
+    DnsQuery,
+    ErrorResponse,
+}
+
+impl TryFrom<u8> for Command {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x10 => Ok(Self::BeginSession),
+            0x11 => Ok(Self::EndSession),
+            0x12 => Ok(Self::Dial),
+            0x13 => Ok(Self::HangUp),
+            0x15 => Ok(Self::TransferData),
+            0x17 => Ok(Self::TelephoneStatus),
+            0x19 => Ok(Self::ReadConfig),
+            0x1A => Ok(Self::WriteConfig),
+            0x1F => Ok(Self::TransferDataEnd),
+            0x21 => Ok(Self::IspLogin),
+            0x22 => Ok(Self::IspLogout),
+            0x23 => Ok(Self::OpenTcp),
+            0x24 => Ok(Self::CloseTcp),
+            0x25 => Ok(Self::OpenUdp),
+            0x26 => Ok(Self::CloseUdp),
+            0x28 => Ok(Self::DnsQuery),
+            0x6E => Ok(Self::ErrorResponse),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<Command> for u8 {
+    fn from(command: Command) -> Self {
+        match command {
+            Command::BeginSession => 0x10,
+            Command::EndSession => 0x11,
+            Command::Dial => 0x12,
+            Command::HangUp => 0x13,
+            Command::TransferData => 0x15,
+            Command::TelephoneStatus => 0x17,
+            Command::ReadConfig => 0x19,
+            Command::WriteConfig => 0x1A,
+            Command::TransferDataEnd => 0x1F,
+            Command::IspLogin => 0x21,
+            Command::IspLogout => 0x22,
+            Command::OpenTcp => 0x23,
+            Command::CloseTcp => 0x24,
+            Command::OpenUdp => 0x25,
+            Command::CloseUdp => 0x26,
+            Command::DnsQuery => 0x28,
+            Command::ErrorResponse => 0x6E,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum PacketState {
+    Magic(usize),
+    Command,
+    Unused,
+    LengthMsb,
+    LengthLsb,
+    Data(usize),
+    ChecksumMsb,
+    ChecksumLsb,
+    DeviceId,
+    Acknowledge,
+}
+
+/// Failure of a command, reported to the game with an error packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum CommandError {
+    InvalidState,
+    InvalidParameters,
+    Backend(Error),
+}
+
+impl From<Error> for CommandError {
+    fn from(error: Error) -> Self {
+        Self::Backend(error)
+    }
+}
+
+impl CommandError {
+    fn code(&self) -> u8 {
+        match self {
+            Self::InvalidState => INVALID_STATE_CODE,
+            Self::InvalidParameters => INVALID_PARAMETERS_CODE,
+            Self::Backend(error) => error.code(),
+        }
+    }
+}
+
+/// Mobile Adapter GB, receives packets made of:
+///   - the magic bytes 0x99 0x66
+///   - a command, an unused byte and the length of the data (big endian)
+///   - the data
+///   - a checksum (big endian) of all the previous bytes except the magic bytes
+///   - two bytes for which the adapter answers with its device ID and the command acknowledgement
+///
+/// The adapter then answers with a packet of the same format, the game sending 0x4B while receiving it.
+pub struct MobileAdapter {
+    state: PacketState,
+    command: u8,
+    length: u16,
+    data: Vec<u8>,
+    checksum: u16,
+    received_checksum: u16,
+    acknowledge: u8,
+    response: VecDeque<u8>,
+
+    session: bool,
+    telephone: bool,
+    logged_in: bool,
+    connections: [Option<Connection>; CONNECTION_COUNT],
+    config: [u8; CONFIG_SIZE],
+    backend: Box<dyn Backend>,
+}
+
+impl std::fmt::Debug for MobileAdapter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MobileAdapter")
+            .field("state", &self.state)
+            .field("session", &self.session)
+            .field("telephone", &self.telephone)
+            .field("logged_in", &self.logged_in)
+            .field("connections", &self.connections)
+            .finish()
+    }
+}
+
+impl MobileAdapter {
+    pub fn new(backend: Box<dyn Backend>) -> Self {
+        Self {
+            state: PacketState::Magic(0),
+            command: 0,
+            length: 0,
+            data: Vec::new(),
+            checksum: 0,
+            received_checksum: 0,
+            acknowledge: 0,
+            response: VecDeque::new(),
+
+            session: false,
+            telephone: false,
+            logged_in: false,
+            connections: [None; CONNECTION_COUNT],
+            config: [0; CONFIG_SIZE],
+            backend,
+        }
+    }
+
+    pub fn backend<T: Backend>(&self) -> Option<&T> {
+        let backend: &dyn Any = self.backend.as_ref();
+        backend.downcast_ref()
+    }
+
+    pub fn backend_mut<T: Backend>(&mut self) -> Option<&mut T> {
+        let backend: &mut dyn Any = self.backend.as_mut();
+        backend.downcast_mut()
+    }
+
+    /// Configuration stored in the EEPROM of the adapter (user, ISP, e-mail settings)
+    pub fn config(&self) -> &[u8; CONFIG_SIZE] {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: [u8; CONFIG_SIZE]) {
+        self.config = config;
+    }
+
+    pub fn is_in_session(&self) -> bool {
+        self.session
+    }
+
+    fn receive(&mut self, byte: u8) {
+        self.state = match self.state {
+            PacketState::Magic(idx) => {
+                if byte == MAGIC[idx] {
+                    if idx + 1 == MAGIC.len() {
+                        self.checksum = 0;
+                        PacketState::Command
+                    } else {
+                        PacketState::Magic(idx + 1)
+                    }
+                } else if byte == MAGIC[0] {
+                    PacketState::Magic(1)
+                } else {
+                    PacketState::Magic(0)
+                }
+            }
+            PacketState::Command => {
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.command = byte;
+                PacketState::Unused
+            }
+            PacketState::Unused => {
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                PacketState::LengthMsb
+            }
+            PacketState::LengthMsb => {
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.length = (byte as u16) << 8;
+                PacketState::LengthLsb
+            }
+            PacketState::LengthLsb => {
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.length |= byte as u16;
+                self.data.clear();
+
+                match self.length {
+                    0 => PacketState::ChecksumMsb,
+                    length => PacketState::Data(length as usize),
+                }
+            }
+            PacketState::Data(remaining) => {
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.data.push(byte);
+
+                match remaining - 1 {
+                    0 => PacketState::ChecksumMsb,
+                    remaining => PacketState::Data(remaining),
+                }
+            }
+            PacketState::ChecksumMsb => {
+                self.received_checksum = (byte as u16) << 8;
+                PacketState::ChecksumLsb
+            }
+            PacketState::ChecksumLsb => {
+                self.received_checksum |= byte as u16;
+
+                self.acknowledge = if self.received_checksum != self.checksum {
+                    ACK_CHECKSUM_ERROR
+                } else if Command::try_from(self.command).is_err() {
+                    ACK_UNKNOWN_COMMAND
+                } else {
+                    self.command ^ 0x80
+                };
+
+                PacketState::DeviceId
+            }
+            PacketState::DeviceId => PacketState::Acknowledge,
+            PacketState::Acknowledge => {
+                // The game sends the packet again when it has not been acknowledged
+                if self.acknowledge == self.command ^ 0x80 {
+                    self.execute();
+                }
+
+                PacketState::Magic(0)
+            }
+        }
+    }
+
+    fn execute(&mut self) {
+        let command = match Command::try_from(self.command) {
+            Ok(command) => command,
+            Err(_) => return,
+        };
+
+        let data = std::mem::take(&mut self.data);
+
+        match self.run(command, &data) {
+            Ok((command, data)) => self.respond(command, &data),
+            Err(error) => self.respond(Command::ErrorResponse, &[self.command, error.code()]),
+        }
+    }
+
+    fn run(&mut self, command: Command, data: &[u8]) -> Result<(Command, Vec<u8>), CommandError> {
+        if !self.session && command != Command::BeginSession {
+            return Err(CommandError::InvalidState);
+        }
+
+        match command {
+            Command::BeginSession => {
+                if data != SESSION_MAGIC {
+                    return Err(CommandError::InvalidParameters);
+                }
+
+                self.session = true;
+                Ok((command, data.to_vec()))
+            }
+            Command::EndSession => {
+                self.hang_up();
+                self.session = false;
+                Ok((command, Vec::new()))
+            }
+            Command::Dial => {
+                // The first byte selects the dialing mode, followed by the number
+                let number = data
+                    .get(1..)
+                    .map(|number| String::from_utf8_lossy(number).into_owned())
+                    .ok_or(CommandError::InvalidParameters)?;
+
+                if self.telephone {
+                    return Err(CommandError::InvalidState);
+                }
+
+                self.backend.dial(&number)?;
+                self.telephone = true;
+                Ok((command, Vec::new()))
+            }
+            Command::HangUp => {
+                if !self.telephone {
+                    return Err(CommandError::InvalidState);
+                }
+
+                self.hang_up();
+                Ok((command, Vec::new()))
+            }
+            Command::TransferData => {
+                let (&id, payload) = data.split_first().ok_or(CommandError::InvalidParameters)?;
+                let connection = self.connection(id)?;
+
+                if !payload.is_empty() {
+                    self.backend.send(connection, payload)?;
+                }
+
+                match self.backend.receive(connection, MAX_DATA_SIZE - 1) {
+                    Ok(received) => {
+                        let mut response = vec![id];
+                        response.extend_from_slice(&received);
+                        Ok((command, response))
+                    }
+                    Err(Error::ConnectionClosed) => {
+                        self.release(id);
+                        Ok((Command::TransferDataEnd, vec![id]))
+                    }
+                    Err(error) => Err(error.into()),
+                }
+            }
+            Command::TelephoneStatus => {
+                let status = if self.telephone {
+                    TELEPHONE_STATUS_CONNECTED
+                } else {
+                    TELEPHONE_STATUS_IDLE
+                };
+
+                Ok((command, vec![status, 0x4D, 0x00]))
+            }
+            Command::ReadConfig => {
+                let (offset, size) = match data {
+                    [offset, size] => (*offset as usize, *size as usize),
+                    _ => return Err(CommandError::InvalidParameters),
+                };
+
+                let config = self
+                    .config
+                    .get(offset..offset + size)
+                    .ok_or(CommandError::InvalidParameters)?;
+
+                let mut response = vec![offset as u8];
+                response.extend_from_slice(config);
+                Ok((command, response))
+            }
+            Command::WriteConfig => {
+                let (&offset, bytes) = data.split_first().ok_or(CommandError::InvalidParameters)?;
+
+                let offset = offset as usize;
+                self.config
+                    .get_mut(offset..offset + bytes.len())
+                    .ok_or(CommandError::InvalidParameters)?
+                    .copy_from_slice(bytes);
+
+                Ok((command, vec![offset as u8, bytes.len() as u8]))
+            }
+            Command::IspLogin => {
+                if !self.telephone {
+                    return Err(CommandError::InvalidState);
+                }
+
+                let (id, rest) = length_prefixed(data)?;
+                let (password, rest) = length_prefixed(rest)?;
+                let dns = rest.get(..8).ok_or(CommandError::InvalidParameters)?;
+
+                let address = self.backend.login(id, password)?;
+                self.logged_in = true;
+
+                let mut response = address.to_vec();
+                response.extend_from_slice(dns);
+                Ok((command, response))
+            }
+            Command::IspLogout => {
+                if !self.logged_in {
+                    return Err(CommandError::InvalidState);
+                }
+
+                self.logout();
+                Ok((command, Vec::new()))
+            }
+            Command::OpenTcp | Command::OpenUdp => {
+                let (address, port) = match data {
+                    [a, b, c, d, port_msb, port_lsb] => {
+                        ([*a, *b, *c, *d], u16::from_be_bytes([*port_msb, *port_lsb]))
+                    }
+                    _ => return Err(CommandError::InvalidParameters),
+                };
+
+                if !self.logged_in {
+                    return Err(CommandError::InvalidState);
+                }
+
+                let id = self
+                    .connections
+                    .iter()
+                    .position(Option::is_none)
+                    .ok_or(CommandError::InvalidState)? as u8;
+
+                let connection = match command {
+                    Command::OpenTcp => Connection::Tcp(id),
+                    _ => Connection::Udp(id),
+                };
+
+                self.backend.open(connection, address, port)?;
+                self.connections[id as usize] = Some(connection);
+                Ok((command, vec![id]))
+            }
+            Command::CloseTcp | Command::CloseUdp => {
+                let id = *data.first().ok_or(CommandError::InvalidParameters)?;
+                let connection = self.connection(id)?;
+
+                self.backend.close(connection);
+                self.release(id);
+                Ok((command, vec![id]))
+            }
+            Command::DnsQuery => {
+                if !self.logged_in {
+                    return Err(CommandError::InvalidState);
+                }
+
+                let name = String::from_utf8_lossy(data);
+                let address = self.backend.dns_query(&name)?;
+                Ok((command, address.to_vec()))
+            }
+            Command::TransferDataEnd | Command::ErrorResponse => {
+                Err(CommandError::InvalidParameters)
+            }
+        }
+    }
+
+    fn connection(&self, id: u8) -> Result<Connection, CommandError> {
+        match id {
+            TELEPHONE_CONNECTION_ID if self.telephone && !self.logged_in => {
+                Ok(Connection::Telephone)
+            }
+            id => self
+                .connections
+                .get(id as usize)
+                .copied()
+                .flatten()
+                .ok_or(CommandError::InvalidState),
+        }
+    }
+
+    fn release(&mut self, id: u8) {
+        if let Some(connection) = self.connections.get_mut(id as usize) {
+            *connection = None;
+        }
+    }
+
+    fn logout(&mut self) {
+        for connection in self.connections.iter_mut() {
+            if let Some(connection) = connection.take() {
+                self.backend.close(connection);
+            }
+        }
+
+        if self.logged_in {
+            self.backend.logout();
+            self.logged_in = false;
+        }
+    }
+
+    fn hang_up(&mut self) {
+        self.logout();
+
+        if self.telephone {
+            self.backend.hang_up();
+            self.telephone = false;
+        }
+    }
+
+    fn respond(&mut self, command: Command, data: &[u8]) {
+        let data = &data[..data.len().min(MAX_DATA_SIZE)];
+
+        let mut packet = vec![u8::from(command) | 0x80, 0x00];
+        packet.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        packet.extend_from_slice(data);
+
+        let checksum = packet
+            .iter()
+            .fold(0u16, |checksum, byte| checksum.wrapping_add(*byte as u16));
+
+        self.response.extend(MAGIC);
+        self.response.extend(packet);
+        self.response.extend(checksum.to_be_bytes());
+        self.response.extend([DEVICE_ID, 0x00]);
+    }
+}
+
+/// Split a string prefixed by its length from the data
+fn length_prefixed(data: &[u8]) -> Result<(&[u8], &[u8]), CommandError> {
+    let (&length, rest) = data.split_first().ok_or(CommandError::InvalidParameters)?;
+
+    if rest.len() < length as usize {
+        return Err(CommandError::InvalidParameters);
+    }
+
+    Ok(rest.split_at(length as usize))
+}
+
+impl super::Peripheral for MobileAdapter {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        // The bytes sent by the game while it receives a response are ignored
+        if let Some(response) = self.response.pop_front() {
+            return response;
+        }
+
+        let response = match self.state {
+            PacketState::DeviceId => DEVICE_ID,
+            PacketState::Acknowledge => self.acknowledge,
+            _ => IDLE,
+        };
+
+        self.receive(byte);
+
+        response
+    }
+}
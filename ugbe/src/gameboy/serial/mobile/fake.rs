@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use super::{Backend, Connection, Error};
+
+/// Handler answering the data sent by the game, returning the data sent back
+pub type Service = Box<dyn FnMut(&[u8]) -> Vec<u8> + Send>;
+
+/// IP address given to the adapter when logging in
+pub const ADAPTER_ADDRESS: [u8; 4] = [10, 0, 0, 2];
+
+struct OpenConnection {
+    endpoint: Endpoint,
+    pending: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Endpoint {
+    Telephone(String),
+    Socket([u8; 4], u16),
+}
+
+/// In-process stand-in for the telephone network and the servers reached through the adapter, allowing to run
+/// the games relying on the Mobile Adapter GB without any connection.
+///
+/// Telephone numbers and servers are registered with a handler called with the data sent by the game, its answer
+/// is received by the game with the next transfer.
+#[derive(Default)]
+pub struct FakeServer {
+    telephones: HashMap<String, Service>,
+    hosts: HashMap<String, [u8; 4]>,
+    services: HashMap<([u8; 4], u16), Service>,
+    credentials: Option<(Vec<u8>, Vec<u8>)>,
+    connections: HashMap<Connection, OpenConnection>,
+    logged_in: bool,
+}
+
+impl std::fmt::Debug for FakeServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FakeServer")
+            .field("telephones", &self.telephones.keys())
+            .field("hosts", &self.hosts)
+            .field("services", &self.services.keys())
+            .field("logged_in", &self.logged_in)
+            .finish()
+    }
+}
+
+impl FakeServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer the calls to a telephone number, like the one of the internet service provider
+    pub fn add_telephone(mut self, number: &str, service: Service) -> Self {
+        self.telephones.insert(number.to_string(), service);
+        self
+    }
+
+    pub fn add_host(mut self, name: &str, address: [u8; 4]) -> Self {
+        self.hosts.insert(name.to_string(), address);
+        self
+    }
+
+    /// Accept the TCP and UDP connections to the given address and port
+    pub fn add_service(mut self, address: [u8; 4], port: u16, service: Service) -> Self {
+        self.services.insert((address, port), service);
+        self
+    }
+
+    /// Only accept the given ID and password when logging in, any is accepted otherwise
+    pub fn set_credentials(self, id: &[u8], password: &[u8]) -> Self {
+        Self {
+            credentials: Some((id.to_vec(), password.to_vec())),
+            ..self
+        }
+    }
+
+    pub fn is_logged_in(&self) -> bool {
+        self.logged_in
+    }
+}
+
+impl Backend for FakeServer {
+    fn dial(&mut self, number: &str) -> Result<(), Error> {
+        if !self.telephones.contains_key(number) {
+            return Err(Error::LineBusy);
+        }
+
+        self.connections.insert(
+            Connection::Telephone,
+            OpenConnection {
+                endpoint: Endpoint::Telephone(number.to_string()),
+                pending: Vec::new(),
+            },
+        );
+
+        Ok(())
+    }
+
+    fn hang_up(&mut self) {
+        self.connections.clear();
+        self.logged_in = false;
+    }
+
+    fn login(&mut self, id: &[u8], password: &[u8]) -> Result<[u8; 4], Error> {
+        match &self.credentials {
+            Some((expected_id, expected_password))
+                if expected_id != id || expected_password != password =>
+            {
+                Err(Error::LoginRefused)
+            }
+            _ => {
+                self.logged_in = true;
+                Ok(ADAPTER_ADDRESS)
+            }
+        }
+    }
+
+    fn logout(&mut self) {
+        self.connections
+            .retain(|connection, _| *connection == Connection::Telephone);
+        self.logged_in = false;
+    }
+
+    fn dns_query(&mut self, name: &str) -> Result<[u8; 4], Error> {
+        self.hosts.get(name).copied().ok_or(Error::UnknownHost)
+    }
+
+    fn open(&mut self, connection: Connection, address: [u8; 4], port: u16) -> Result<(), Error> {
+        if !self.services.contains_key(&(address, port)) {
+            return Err(Error::ConnectionRefused);
+        }
+
+        self.connections.insert(
+            connection,
+            OpenConnection {
+                endpoint: Endpoint::Socket(address, port),
+                pending: Vec::new(),
+            },
+        );
+
+        Ok(())
+    }
+
+    fn close(&mut self, connection: Connection) {
+        self.connections.remove(&connection);
+    }
+
+    fn send(&mut self, connection: Connection, data: &[u8]) -> Result<(), Error> {
+        let open_connection = self
+            .connections
+            .get_mut(&connection)
+            .ok_or(Error::ConnectionClosed)?;
+
+        let service = match &open_connection.endpoint {
+            Endpoint::Telephone(number) => self.telephones.get_mut(number),
+            Endpoint::Socket(address, port) => self.services.get_mut(&(*address, *port)),
+        }
+        .ok_or(Error::ConnectionClosed)?;
+
+        open_connection.pending.extend(service(data));
+
+        Ok(())
+    }
+
+    fn receive(&mut self, connection: Connection, max_size: usize) -> Result<Vec<u8>, Error> {
+        let open_connection = self
+            .connections
+            .get_mut(&connection)
+            .ok_or(Error::ConnectionClosed)?;
+
+        let size = open_connection.pending.len().min(max_size);
+        Ok(open_connection.pending.drain(..size).collect())
+    }
+}
@@ -0,0 +1,515 @@
+use std::io;
+
+/// Width of the printed images in pixels
+pub const WIDTH: usize = 160;
+
+/// The printer RAM can hold up to 9 bands of 160x16 pixels
+const BUFFER_SIZE: usize = 0x280 * 9;
+
+/// Number of T-cycles needed to print a band of 160x16 pixels
+const T_CYCLES_PER_BAND: usize = 0x10000;
+
+const MAGIC: [u8; 2] = [0x88, 0x33];
+
+/// Value returned by the printer to identify itself after a packet
+const DEVICE_ID: u8 = 0x81;
+
+const STATUS_CHECKSUM_ERROR: u8 = 1 << 0;
+const STATUS_BUSY: u8 = 1 << 1;
+const STATUS_IMAGE_DATA_FULL: u8 = 1 << 2;
+const STATUS_UNPROCESSED_DATA: u8 = 1 << 3;
+const STATUS_PACKET_ERROR: u8 = 1 << 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Command {
+    Init,
+    Print,
+    Data,
+    Status,
+}
+
+impl TryFrom<u8> for Command {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Self::Init),
+            0x02 => Ok(Self::Print),
+            0x04 => Ok(Self::Data),
+            0x0F => Ok(Self::Status),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum PacketState {
+    Magic(usize),
+    Command,
+    Compression,
+    LengthLsb,
+    LengthMsb,
+    Data(usize),
+    ChecksumLsb,
+    ChecksumMsb,
+    DeviceId,
+    Status,
+}
+
+/// Image printed by the printer, stored as 8-bit grayscale pixels (0x00 is black and 0xFF white)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PrintedImage {
+    height: usize,
+    pixels: Vec<u8>,
+    margin_before: u8,
+    margin_after: u8,
+    exposure: u8,
+}
+
+impl PrintedImage {
+    pub fn width(&self) -> usize {
+        WIDTH
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Number of line feeds before printing the image
+    pub fn margin_before(&self) -> u8 {
+        self.margin_before
+    }
+
+    /// Number of line feeds after printing the image
+    pub fn margin_after(&self) -> u8 {
+        self.margin_after
+    }
+
+    /// Darkness requested by the game, 0x40 being the default one
+    pub fn exposure(&self) -> u8 {
+        self.exposure
+    }
+
+    /// Export the image to a grayscale PNG (stored without compression)
+    pub fn write_png<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        const STORED_BLOCK_SIZE: usize = 0xFFFF;
+
+        let mut scanlines = Vec::with_capacity((WIDTH + 1) * self.height);
+        for row in self.pixels.chunks_exact(WIDTH) {
+            // Each scanline starts with its filter type, 0 being no filter
+            scanlines.push(0);
+            scanlines.extend_from_slice(row);
+        }
+
+        // Even an empty image needs a final block
+        let blocks: Vec<&[u8]> = if scanlines.is_empty() {
+            vec![&[]]
+        } else {
+            scanlines.chunks(STORED_BLOCK_SIZE).collect()
+        };
+
+        let mut zlib = vec![0x78, 0x01];
+        for (idx, block) in blocks.iter().enumerate() {
+            let len = block.len() as u16;
+            zlib.push((idx + 1 == blocks.len()) as u8);
+            zlib.extend_from_slice(&len.to_le_bytes());
+            zlib.extend_from_slice(&(!len).to_le_bytes());
+            zlib.extend_from_slice(block);
+        }
+        zlib.extend_from_slice(&adler32(&scanlines).to_be_bytes());
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(WIDTH as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // 8 bits grayscale, default compression and filter, no interlacing
+        header.extend_from_slice(&[8, 0, 0, 0, 0]);
+
+        writer.write_all(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'])?;
+        write_png_chunk(writer, b"IHDR", &header)?;
+        write_png_chunk(writer, b"IDAT", &zlib)?;
+        write_png_chunk(writer, b"IEND", &[])
+    }
+}
+
+fn write_png_chunk<W: io::Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let crc = !crate::crc::crc32_update(crate::crc::crc32_update(0xFFFFFFFF, kind), data);
+
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    writer.write_all(&crc.to_be_bytes())
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (a, b) = bytes.iter().fold((1u32, 0u32), |(a, b), byte| {
+        let a = (a + *byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+
+    (b << 16) | a
+}
+
+/// Game Boy Printer, receives packets made of:
+///   - the magic bytes 0x88 0x33
+///   - a command, a compression flag and the length of the data (little endian)
+///   - the data, optionally compressed with a run-length encoding
+///   - a checksum (little endian) of all the previous bytes except the magic bytes
+///   - two bytes for which the printer answers with its device ID and its status
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Printer {
+    state: PacketState,
+    command: u8,
+    compressed: bool,
+    length: u16,
+    data: Vec<u8>,
+    checksum: u16,
+    received_checksum: u16,
+
+    status: u8,
+    busy_cycles: usize,
+    buffer: Vec<u8>,
+    printed_images: Vec<PrintedImage>,
+}
+
+impl Printer {
+    pub fn new() -> Self {
+        Self {
+            state: PacketState::Magic(0),
+            command: 0,
+            compressed: false,
+            length: 0,
+            data: Vec::new(),
+            checksum: 0,
+            received_checksum: 0,
+
+            status: 0,
+            busy_cycles: 0,
+            buffer: Vec::with_capacity(BUFFER_SIZE),
+            printed_images: Vec::new(),
+        }
+    }
+
+    /// Images printed since the last call
+    pub fn take_printed_images(&mut self) -> Vec<PrintedImage> {
+        std::mem::take(&mut self.printed_images)
+    }
+
+    fn status(&self) -> u8 {
+        if self.busy_cycles > 0 {
+            self.status | STATUS_BUSY
+        } else {
+            self.status
+        }
+    }
+
+    fn receive(&mut self, byte: u8) {
+        self.state = match self.state {
+            PacketState::Magic(idx) => {
+                if byte == MAGIC[idx] {
+                    if idx + 1 == MAGIC.len() {
+                        self.checksum = 0;
+                        PacketState::Command
+                    } else {
+                        PacketState::Magic(idx + 1)
+                    }
+                } else if byte == MAGIC[0] {
+                    PacketState::Magic(1)
+                } else {
+                    PacketState::Magic(0)
+                }
+            }
+            PacketState::Command => {
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.command = byte;
+                PacketState::Compression
+            }
+            PacketState::Compression => {
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.compressed = byte & 0b1 != 0;
+                PacketState::LengthLsb
+            }
+            PacketState::LengthLsb => {
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.length = byte as u16;
+                PacketState::LengthMsb
+            }
+            PacketState::LengthMsb => {
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.length |= (byte as u16) << 8;
+                self.data.clear();
+
+                match self.length {
+                    0 => PacketState::ChecksumLsb,
+                    length => PacketState::Data(length as usize),
+                }
+            }
+            PacketState::Data(remaining) => {
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.data.push(byte);
+
+                match remaining - 1 {
+                    0 => PacketState::ChecksumLsb,
+                    remaining => PacketState::Data(remaining),
+                }
+            }
+            PacketState::ChecksumLsb => {
+                self.received_checksum = byte as u16;
+                PacketState::ChecksumMsb
+            }
+            PacketState::ChecksumMsb => {
+                self.received_checksum |= (byte as u16) << 8;
+                self.execute();
+                PacketState::DeviceId
+            }
+            PacketState::DeviceId => PacketState::Status,
+            PacketState::Status => PacketState::Magic(0),
+        }
+    }
+
+    fn execute(&mut self) {
+        if self.received_checksum != self.checksum {
+            self.status |= STATUS_CHECKSUM_ERROR;
+            return;
+        }
+
+        self.status &= !(STATUS_CHECKSUM_ERROR | STATUS_PACKET_ERROR);
+
+        match Command::try_from(self.command) {
+            Ok(Command::Init) => {
+                self.buffer.clear();
+                self.status = 0;
+            }
+            Ok(Command::Data) => {
+                let data = std::mem::take(&mut self.data);
+
+                if self.compressed {
+                    self.decompress(&data);
+                } else {
+                    self.append(&data);
+                }
+
+                if !data.is_empty() {
+                    self.status |= STATUS_UNPROCESSED_DATA;
+                }
+            }
+            Ok(Command::Print) => self.print(),
+            Ok(Command::Status) => {}
+            Err(_) => self.status |= STATUS_PACKET_ERROR,
+        }
+    }
+
+    fn append(&mut self, data: &[u8]) {
+        let available = BUFFER_SIZE - self.buffer.len();
+        self.buffer
+            .extend_from_slice(&data[..data.len().min(available)]);
+
+        if self.buffer.len() == BUFFER_SIZE {
+            self.status |= STATUS_IMAGE_DATA_FULL;
+        }
+    }
+
+    /// The compressed data is made of runs: a control byte with bit 7 set is followed by one byte repeated
+    /// (control & 0x7F) + 2 times, otherwise it is followed by (control + 1) bytes to copy.
+    fn decompress(&mut self, data: &[u8]) {
+        let mut idx = 0;
+
+        while idx < data.len() {
+            let control = data[idx];
+            idx += 1;
+
+            if control & 0b1000_0000 != 0 {
+                let count = (control & 0b0111_1111) as usize + 2;
+                let value = data.get(idx).copied().unwrap_or(0);
+                idx += 1;
+
+                self.append(&vec![value; count]);
+            } else {
+                let count = control as usize + 1;
+                let end = (idx + count).min(data.len());
+
+                let bytes = data[idx..end].to_vec();
+                self.append(&bytes);
+                idx = end;
+            }
+        }
+    }
+
+    fn print(&mut self) {
+        let param = |idx: usize| self.data.get(idx).copied().unwrap_or(0);
+
+        let sheets = param(0);
+        let margins = param(1);
+        // Some games send an empty palette, in which case the default one is used
+        let palette = match param(2) {
+            0x00 => 0xE4,
+            palette => palette,
+        };
+        let exposure = param(3) & 0b0111_1111;
+
+        self.status &= !(STATUS_UNPROCESSED_DATA | STATUS_IMAGE_DATA_FULL);
+
+        // No sheet means that the paper is only fed
+        if sheets == 0 {
+            return;
+        }
+
+        let tiles_per_row = WIDTH / 8;
+        let tile_rows = self.buffer.len() / (16 * tiles_per_row);
+        let height = tile_rows * 8;
+
+        let mut pixels = vec![0xFF; WIDTH * height];
+        for (tile_idx, tile) in self.buffer.chunks_exact(16).enumerate() {
+            let tile_x = (tile_idx % tiles_per_row) * 8;
+            let tile_y = (tile_idx / tiles_per_row) * 8;
+
+            if tile_y >= height {
+                break;
+            }
+
+            for row in 0..8 {
+                let (lsb, msb) = (tile[row * 2], tile[row * 2 + 1]);
+
+                for column in 0..8 {
+                    let color =
+                        (((msb >> (7 - column)) & 0b1) << 1) | ((lsb >> (7 - column)) & 0b1);
+                    let shade = (palette >> (color * 2)) & 0b11;
+
+                    pixels[(tile_y + row) * WIDTH + tile_x + column] = match shade {
+                        0 => 0xFF,
+                        1 => 0xAA,
+                        2 => 0x55,
+                        _ => 0x00,
+                    };
+                }
+            }
+        }
+
+        self.busy_cycles = (tile_rows / 2).max(1) * T_CYCLES_PER_BAND;
+        self.buffer.clear();
+
+        self.printed_images.push(PrintedImage {
+            height,
+            pixels,
+            margin_before: margins >> 4,
+            margin_after: margins & 0x0F,
+            exposure,
+        });
+    }
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl super::Peripheral for Printer {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        let response = match self.state {
+            PacketState::DeviceId => DEVICE_ID,
+            PacketState::Status => self.status(),
+            _ => 0x00,
+        };
+
+        self.receive(byte);
+
+        response
+    }
+
+    fn tick(&mut self) {
+        self.busy_cycles = self.busy_cycles.saturating_sub(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Printer;
+    use crate::gameboy::serial::Peripheral;
+
+    /// Build a printer packet and send it, returning the device ID and the status answered by the printer
+    fn send_printer_packet(
+        printer: &mut Printer,
+        command: u8,
+        compressed: bool,
+        data: &[u8],
+    ) -> (u8, u8) {
+        let mut packet = vec![command, compressed as u8];
+        packet.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        packet.extend_from_slice(data);
+
+        let checksum = packet
+            .iter()
+            .fold(0u16, |checksum, byte| checksum.wrapping_add(*byte as u16));
+
+        for byte in [0x88, 0x33]
+            .iter()
+            .chain(&packet)
+            .chain(&checksum.to_le_bytes())
+        {
+            assert_eq!(printer.exchange(*byte), 0x00);
+        }
+
+        (printer.exchange(0x00), printer.exchange(0x00))
+    }
+
+    #[test]
+    fn printer_packets() {
+        let mut printer = Printer::new();
+
+        assert_eq!(
+            send_printer_packet(&mut printer, 0x01, false, &[]),
+            (0x81, 0x00)
+        );
+        assert_eq!(
+            send_printer_packet(&mut printer, 0x0F, false, &[]),
+            (0x81, 0x00)
+        );
+
+        // A band of 160x16 pixels: the first row of tiles is black and the second one white, sent compressed
+        let mut data = Vec::new();
+        for value in [0xFF, 0x00] {
+            for run in [128, 128, 64] {
+                data.extend_from_slice(&[0x80 | (run - 2), value]);
+            }
+        }
+
+        assert_eq!(
+            send_printer_packet(&mut printer, 0x04, true, &data),
+            (0x81, 0x08)
+        );
+        assert_eq!(
+            send_printer_packet(&mut printer, 0x04, false, &[]),
+            (0x81, 0x08)
+        );
+
+        let (_, status) = send_printer_packet(&mut printer, 0x02, false, &[0x01, 0x13, 0xE4, 0x40]);
+        assert_eq!(status & 0b0000_0010, 0b0000_0010);
+
+        let images = printer.take_printed_images();
+        assert_eq!(images.len(), 1);
+
+        let image = &images[0];
+        assert_eq!((image.width(), image.height()), (160, 16));
+        assert_eq!((image.margin_before(), image.margin_after()), (1, 3));
+        assert!(image.pixels()[..160 * 8].iter().all(|pixel| *pixel == 0x00));
+        assert!(image.pixels()[160 * 8..].iter().all(|pixel| *pixel == 0xFF));
+
+        let mut png = Vec::new();
+        image.write_png(&mut png).unwrap();
+        assert_eq!(
+            &png[..8],
+            &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']
+        );
+
+        // A wrong checksum is reported in the status
+        for byte in [0x88, 0x33, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00] {
+            printer.exchange(byte);
+        }
+        printer.exchange(0x00);
+        assert_eq!(printer.exchange(0x00) & 0b0000_0001, 0b0000_0001);
+    }
+}
@@ -0,0 +1,145 @@
+use thiserror::Error;
+
+use super::{barcode, mobile, printer, tcp, Peripheral};
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("unknown peripheral '{0}'")]
+    UnknownPeripheral(String),
+
+    #[error("peripheral '{0}' needs an argument")]
+    MissingArgument(&'static str),
+
+    #[error("failed to load a barcode card")]
+    BarcodeError(#[from] barcode::Error),
+
+    #[error("failed to open the TCP link cable")]
+    TcpError(#[from] tcp::Error),
+
+    #[error("failed to listen for the TCP link cable")]
+    ListenError(#[from] std::io::Error),
+}
+
+/// Build a peripheral from an optional argument given by the user (paths, addresses, ...)
+pub type Factory = Box<dyn Fn(Option<&str>) -> Result<Box<dyn Peripheral>, Error> + Send + Sync>;
+
+pub struct Entry {
+    name: &'static str,
+    description: &'static str,
+    factory: Factory,
+}
+
+impl Entry {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn description(&self) -> &'static str {
+        self.description
+    }
+}
+
+/// Peripherals that can be plugged into the serial port, selected by name.
+///
+/// A peripheral is selected with a specification `name[:argument]`, for example `printer`,
+/// `barcode-boy:cards/a.txt,cards/b.txt` or `tcp-connect:127.0.0.1:8765`.
+pub struct Registry {
+    entries: Vec<Entry>,
+}
+
+impl Registry {
+    /// Registry with all the peripherals emulated by ugbe
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+        .register(
+            "printer",
+            "Game Boy Printer",
+            Box::new(|_| Ok(Box::new(printer::Printer::new()))),
+        )
+        .register(
+            "barcode-boy",
+            "Barcode Boy, with the comma separated paths of the card files",
+            Box::new(|argument| {
+                let cards = argument
+                    .unwrap_or_default()
+                    .split(',')
+                    .filter(|path| !path.is_empty())
+                    .map(barcode::Card::from_path)
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok(Box::new(barcode::BarcodeBoy::new(cards)))
+            }),
+        )
+        .register(
+            "mobile-adapter",
+            "Mobile Adapter GB connected to an empty in-process server",
+            Box::new(|_| {
+                let server = mobile::fake::FakeServer::new();
+                Ok(Box::new(mobile::MobileAdapter::new(Box::new(server))))
+            }),
+        )
+        .register(
+            "tcp-listen",
+            "Link cable over TCP to another ugbe, unplugged until the other side connects on the given address",
+            Box::new(|argument| {
+                let address = argument.ok_or(Error::MissingArgument("tcp-listen"))?;
+                let listener = std::net::TcpListener::bind(address)?;
+                Ok(Box::new(tcp::TcpListenLink::new(listener)))
+            }),
+        )
+        .register(
+            "tcp-connect",
+            "Link cable over TCP to another ugbe, connecting to the other side at the given address",
+            Box::new(|argument| {
+                let address = argument.ok_or(Error::MissingArgument("tcp-connect"))?;
+                Ok(Box::new(tcp::TcpLink::connect(address)?))
+            }),
+        )
+    }
+
+    /// Add a peripheral, replacing the one with the same name
+    pub fn register(
+        mut self,
+        name: &'static str,
+        description: &'static str,
+        factory: Factory,
+    ) -> Self {
+        self.entries.retain(|entry| entry.name != name);
+        self.entries.push(Entry {
+            name,
+            description,
+            factory,
+        });
+        self
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn create(&self, name: &str, argument: Option<&str>) -> Result<Box<dyn Peripheral>, Error> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.name == name)
+            .ok_or_else(|| Error::UnknownPeripheral(name.to_string()))?;
+
+        (entry.factory)(argument)
+    }
+
+    /// Create a peripheral from a specification `name[:argument]`
+    pub fn create_from_spec(&self, spec: &str) -> Result<Box<dyn Peripheral>, Error> {
+        match spec.split_once(':') {
+            Some((name, argument)) => self.create(name, Some(argument)),
+            None => self.create(spec, None),
+        }
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,362 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("unsupported protocol version (got '{0}.{1}', expected '1.4')")]
+    UnsupportedVersion(u8, u8),
+
+    #[error("unexpected packet during the handshake (got command '{0}')")]
+    UnexpectedPacket(u8),
+
+    #[error("connection failed")]
+    ConnectionError(#[from] io::Error),
+}
+
+/// Size of a packet of the BGB link protocol
+const PACKET_SIZE: usize = 8;
+
+const COMMAND_VERSION: u8 = 1;
+const COMMAND_SYNC1: u8 = 104;
+const COMMAND_SYNC2: u8 = 105;
+const COMMAND_SYNC3: u8 = 106;
+const COMMAND_STATUS: u8 = 108;
+const COMMAND_WANT_DISCONNECT: u8 = 109;
+
+const PROTOCOL_VERSION: (u8, u8) = (1, 4);
+
+/// Maximum time waited for the version of the other side when connecting
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default time waited for the answer of the other side to a transfer clocked by this side
+const EXCHANGE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Number of T-cycles between two reads of the socket
+const POLL_INTERVAL: u64 = 256;
+
+/// Number of T-cycles between two timestamp updates sent to the other side
+const TIMESTAMP_INTERVAL: u64 = 0x10000;
+
+/// Link cable between two ugbe instances over TCP, using the packets of the BGB link protocol (version 1.4).
+///
+/// The timestamps are sent to the other side but the ones it sends are ignored, each side running at its own pace.
+/// BGB relies on them to keep both emulators in sync, so linking ugbe to BGB isn't supported.
+///
+/// The protocol exchanges whole bytes: the side using its internal clock sends its byte and keeps running until the
+/// other side answers with its own byte, the transfer then completes after the usual 8 bits delay. This makes the
+/// link tolerant to the network latency at the price of slower transfers. When the other side doesn't answer in time,
+/// the transfer completes with 0xFF as if the cable was unplugged.
+#[derive(Debug)]
+pub struct TcpLink {
+    stream: TcpStream,
+    connected: bool,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    cycles: u64,
+    /// Transfer clocked by this side waiting for the answer of the other side
+    pending_exchange: bool,
+    exchange_started: Instant,
+    exchange_timeout: Duration,
+    exchanged: Option<u8>,
+    /// Byte sent by the other side using its internal clock
+    clocked: Option<u8>,
+}
+
+impl TcpLink {
+    pub fn connect<A: ToSocketAddrs>(address: A) -> Result<Self, Error> {
+        Self::new(TcpStream::connect(address)?)
+    }
+
+    /// Wait for the other side to connect on the listener
+    pub fn accept(listener: &TcpListener) -> Result<Self, Error> {
+        let (stream, _) = listener.accept()?;
+        Self::new(stream)
+    }
+
+    fn new(mut stream: TcpStream) -> Result<Self, Error> {
+        stream.set_nodelay(true)?;
+
+        let (major, minor) = PROTOCOL_VERSION;
+        stream.write_all(&packet(COMMAND_VERSION, major, minor, 0, 0))?;
+
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let mut version = [0; PACKET_SIZE];
+        stream.read_exact(&mut version)?;
+
+        if version[0] != COMMAND_VERSION {
+            return Err(Error::UnexpectedPacket(version[0]));
+        }
+        if (version[1], version[2]) != PROTOCOL_VERSION {
+            return Err(Error::UnsupportedVersion(version[1], version[2]));
+        }
+
+        stream.set_read_timeout(None)?;
+        stream.set_nonblocking(true)?;
+
+        let mut link = Self {
+            stream,
+            connected: true,
+            incoming: Vec::with_capacity(PACKET_SIZE),
+            outgoing: Vec::new(),
+            cycles: 0,
+            pending_exchange: false,
+            exchange_started: Instant::now(),
+            exchange_timeout: EXCHANGE_TIMEOUT,
+            exchanged: None,
+            clocked: None,
+        };
+
+        // Running without pause and reconnection support
+        link.send(COMMAND_STATUS, 0b001, 0, 0);
+
+        Ok(link)
+    }
+
+    /// Time waited for the answer of the other side before receiving 0xFF
+    pub fn set_exchange_timeout(self, exchange_timeout: Duration) -> Self {
+        Self {
+            exchange_timeout,
+            ..self
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Timestamp of the protocol, counted at 2MiHz
+    fn timestamp(&self) -> u32 {
+        ((self.cycles / 2) as u32) & 0x7FFF_FFFF
+    }
+
+    fn send(&mut self, command: u8, b2: u8, b3: u8, timestamp: u32) {
+        self.outgoing
+            .extend_from_slice(&packet(command, b2, b3, 0, timestamp));
+        self.flush();
+    }
+
+    fn flush(&mut self) {
+        while self.connected && !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => self.connected = false,
+                Ok(size) => {
+                    self.outgoing.drain(..size);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => self.connected = false,
+            }
+        }
+    }
+
+    /// Read the packets received since the last call
+    fn poll(&mut self) {
+        self.flush();
+
+        let mut buffer = [0; 64];
+        while self.connected {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.connected = false,
+                Ok(size) => self.incoming.extend_from_slice(&buffer[..size]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => self.connected = false,
+            }
+        }
+
+        let packet_count = self.incoming.len() / PACKET_SIZE;
+        let packets: Vec<u8> = self.incoming.drain(..packet_count * PACKET_SIZE).collect();
+
+        for packet in packets.chunks_exact(PACKET_SIZE) {
+            self.handle_packet(packet);
+        }
+
+        // Without the other side the line is pulled up and only 1s are received
+        let timed_out = self.exchange_started.elapsed() >= self.exchange_timeout;
+        if self.pending_exchange && (!self.connected || timed_out) {
+            self.pending_exchange = false;
+            self.exchanged = Some(0xFF);
+        }
+    }
+
+    fn handle_packet(&mut self, packet: &[u8]) {
+        match packet[0] {
+            COMMAND_SYNC1 => self.clocked = Some(packet[1]),
+            COMMAND_SYNC2 if self.pending_exchange => {
+                self.pending_exchange = false;
+                self.exchanged = Some(packet[1]);
+            }
+            // Acknowledge sent instead of SYNC2 when the other side didn't start a transfer
+            COMMAND_SYNC3 if packet[1] == 1 && self.pending_exchange => {
+                self.pending_exchange = false;
+                self.exchanged = Some(0xFF);
+            }
+            COMMAND_WANT_DISCONNECT => self.connected = false,
+            // Timestamps, joypad and status updates are ignored as each side runs at its own pace
+            _ => {}
+        }
+    }
+}
+
+impl super::Peripheral for TcpLink {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        let mut incoming = self.begin_exchange(byte);
+
+        while incoming.is_none() {
+            std::thread::sleep(Duration::from_micros(100));
+            self.poll();
+            incoming = self.poll_exchange();
+        }
+
+        incoming.unwrap_or(0xFF)
+    }
+
+    fn begin_exchange(&mut self, byte: u8) -> Option<u8> {
+        if !self.connected {
+            return Some(0xFF);
+        }
+
+        self.pending_exchange = true;
+        self.exchange_started = Instant::now();
+        self.exchanged = None;
+        let timestamp = self.timestamp();
+        self.send(COMMAND_SYNC1, byte, 0x81, timestamp);
+
+        None
+    }
+
+    fn poll_exchange(&mut self) -> Option<u8> {
+        self.exchanged.take()
+    }
+
+    fn poll_external_clock(&mut self, data: u8, ready: bool) -> Option<u8> {
+        let clocked = self.clocked.take()?;
+
+        if ready {
+            self.send(COMMAND_SYNC2, data, 0x80, 0);
+        } else {
+            self.send(COMMAND_SYNC3, 1, 0, 0);
+        }
+
+        Some(clocked)
+    }
+
+    fn tick(&mut self) {
+        self.cycles += 1;
+
+        if self.cycles.is_multiple_of(TIMESTAMP_INTERVAL) && self.connected {
+            let timestamp = self.timestamp();
+            self.send(COMMAND_SYNC3, 0, 0, timestamp);
+        }
+
+        if self.cycles.is_multiple_of(POLL_INTERVAL) {
+            self.poll();
+        }
+    }
+}
+
+/// Link cable waiting in the background for the other side to connect, so that the Gameboy keeps running
+/// meanwhile. The cable is unplugged until the connection is accepted, or when accepting it failed.
+#[derive(Debug)]
+pub struct TcpListenLink {
+    link: Option<TcpLink>,
+    accepted: Option<mpsc::Receiver<Result<TcpLink, Error>>>,
+    error: Option<Error>,
+    cycles: u64,
+}
+
+impl TcpListenLink {
+    pub fn new(listener: TcpListener) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            // Nobody is waiting anymore when the link has been dropped before the other side connected
+            let _ = sender.send(TcpLink::accept(&listener));
+        });
+
+        Self {
+            link: None,
+            accepted: Some(receiver),
+            error: None,
+            cycles: 0,
+        }
+    }
+
+    /// The link to the other side, once connected
+    pub fn link(&self) -> Option<&TcpLink> {
+        self.link.as_ref()
+    }
+
+    pub fn link_mut(&mut self) -> Option<&mut TcpLink> {
+        self.link.as_mut()
+    }
+
+    /// Why the connection of the other side has been refused
+    pub fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+
+    fn poll_accepted(&mut self) {
+        let Some(accepted) = &self.accepted else {
+            return;
+        };
+
+        match accepted.try_recv() {
+            Ok(Ok(link)) => self.link = Some(link),
+            Ok(Err(err)) => self.error = Some(err),
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => {}
+        }
+        self.accepted = None;
+    }
+}
+
+impl super::Peripheral for TcpListenLink {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        self.link.as_mut().map_or(0xFF, |link| link.exchange(byte))
+    }
+
+    fn begin_exchange(&mut self, byte: u8) -> Option<u8> {
+        match &mut self.link {
+            Some(link) => link.begin_exchange(byte),
+            None => Some(0xFF),
+        }
+    }
+
+    fn poll_exchange(&mut self) -> Option<u8> {
+        self.link.as_mut()?.poll_exchange()
+    }
+
+    fn poll_external_clock(&mut self, data: u8, ready: bool) -> Option<u8> {
+        self.link.as_mut()?.poll_external_clock(data, ready)
+    }
+
+    fn tick(&mut self) {
+        match &mut self.link {
+            Some(link) => link.tick(),
+            None => {
+                self.cycles += 1;
+                if self.cycles.is_multiple_of(POLL_INTERVAL) {
+                    self.poll_accepted();
+                }
+            }
+        }
+    }
+}
+
+fn packet(command: u8, b2: u8, b3: u8, b4: u8, timestamp: u32) -> [u8; PACKET_SIZE] {
+    let timestamp = timestamp.to_le_bytes();
+    [
+        command,
+        b2,
+        b3,
+        b4,
+        timestamp[0],
+        timestamp[1],
+        timestamp[2],
+        timestamp[3],
+    ]
+}
@@ -0,0 +1,201 @@
+/// Size of a packet sent by the game to the SGB through P1
+pub const PACKET_SIZE: usize = 16;
+
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommandCode {
+    PAL01,
+    PAL23,
+    PAL03,
+    PAL12,
+    ATTR_BLK,
+    ATTR_LIN,
+    ATTR_DIV,
+    ATTR_CHR,
+    PAL_SET,
+    PAL_TRN,
+    MLT_REQ,
+    CHR_TRN,
+    PCT_TRN,
+    ATTR_TRN,
+    ATTR_SET,
+    MASK_EN,
+    /// Commands that are not emulated (sound, SNES code upload, ...)
+    Unsupported(u8),
+}
+
+impl From<u8> for CommandCode {
+    fn from(value: u8) -> Self {
+        match value {
+            0x00 => Self::PAL01,
+            0x01 => Self::PAL23,
+            0x02 => Self::PAL03,
+            0x03 => Self::PAL12,
+            0x04 => Self::ATTR_BLK,
+            0x05 => Self::ATTR_LIN,
+            0x06 => Self::ATTR_DIV,
+            0x07 => Self::ATTR_CHR,
+            0x0A => Self::PAL_SET,
+            0x0B => Self::PAL_TRN,
+            0x11 => Self::MLT_REQ,
+            0x13 => Self::CHR_TRN,
+            0x14 => Self::PCT_TRN,
+            0x15 => Self::ATTR_TRN,
+            0x16 => Self::ATTR_SET,
+            0x17 => Self::MASK_EN,
+            code => Self::Unsupported(code),
+        }
+    }
+}
+
+/// Command made of one or more packets, the first byte contains the command code and the number of packets
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Command(Vec<u8>);
+
+impl Command {
+    pub fn code(&self) -> CommandCode {
+        (self.0[0] >> 3).into()
+    }
+
+    /// Parameters of the command, starting at the byte following the command code, bytes past the end of the
+    /// packets are read as 0
+    pub fn param(&self, idx: usize) -> u8 {
+        self.0.get(idx + 1).copied().unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ReceiverState {
+    Idle,
+    Receiving(usize),
+    StopBit,
+}
+
+/// Receive the packets sent by the game through the P14 and P15 lines of P1.
+///
+/// A packet starts with a reset pulse (P14 and P15 low), then each bit is sent with P14 low for a 0 or P15 low for
+/// a 1, followed by both lines high. The 128 bits of a packet are sent LSB first and followed by a 0 stop bit.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PacketReceiver {
+    state: ReceiverState,
+    lines: u8,
+    packet: [u8; PACKET_SIZE],
+    command: Vec<u8>,
+}
+
+impl PacketReceiver {
+    pub fn new() -> Self {
+        Self {
+            state: ReceiverState::Idle,
+            lines: 0x30,
+            packet: [0; PACKET_SIZE],
+            command: Vec::new(),
+        }
+    }
+
+    /// Update the state of the P14 and P15 lines, return the command once all its packets have been received
+    pub fn write(&mut self, value: u8) -> Option<Command> {
+        let lines = value & 0x30;
+        let previous_lines = std::mem::replace(&mut self.lines, lines);
+
+        if lines == 0x00 {
+            self.state = ReceiverState::Receiving(0);
+            self.packet = [0; PACKET_SIZE];
+            return None;
+        }
+
+        // A bit is only sent when going out of the idle state where both lines are high
+        if lines == 0x30 || previous_lines != 0x30 {
+            return None;
+        }
+
+        let bit = lines == 0x10;
+
+        match self.state {
+            ReceiverState::Idle => None,
+            ReceiverState::Receiving(idx) => {
+                if bit {
+                    self.packet[idx / 8] |= 1 << (idx % 8);
+                }
+
+                self.state = if idx + 1 == PACKET_SIZE * 8 {
+                    ReceiverState::StopBit
+                } else {
+                    ReceiverState::Receiving(idx + 1)
+                };
+
+                None
+            }
+            ReceiverState::StopBit => {
+                self.state = ReceiverState::Idle;
+
+                if bit {
+                    // Invalid stop bit, the packet is dropped
+                    return None;
+                }
+
+                self.packet_received()
+            }
+        }
+    }
+
+    fn packet_received(&mut self) -> Option<Command> {
+        if self.command.is_empty() && self.packet[0] & 0b111 == 0 {
+            return None;
+        }
+
+        self.command.extend_from_slice(&self.packet);
+
+        let packet_count = (self.command[0] & 0b111) as usize;
+        if self.command.len() < packet_count * PACKET_SIZE {
+            return None;
+        }
+
+        Some(Command(std::mem::take(&mut self.command)))
+    }
+}
+
+impl Default for PacketReceiver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandCode, PACKET_SIZE};
+    use crate::gameboy::joypad::Joypad;
+    use crate::gameboy::tests::send_sgb_packet;
+
+    #[test]
+    fn sgb_packets() {
+        let mut joypad = Joypad::new_sgb();
+
+        let mut packet = [0x00; PACKET_SIZE];
+        packet[0] = 1; // PAL01 (command 0x00) in a single packet
+        packet[1] = 0x1F;
+        send_sgb_packet(&mut joypad, packet);
+
+        let command = joypad.take_sgb_command().unwrap();
+        assert_eq!(command.code(), CommandCode::PAL01);
+        assert_eq!(command.param(0), 0x1F);
+        assert!(joypad.take_sgb_command().is_none());
+
+        let mut packet = [0x00; PACKET_SIZE];
+        packet[0] = (0x11 << 3) | 1; // MLT_REQ
+        packet[1] = 0x01;
+        send_sgb_packet(&mut joypad, packet);
+
+        // Multiplayer requests are handled by the joypad
+        assert!(joypad.take_sgb_command().is_none());
+        assert_eq!(joypad.read_p1() & 0x0F, 0x0F);
+
+        joypad.write_p1(0x10);
+        joypad.write_p1(0x30);
+        assert_eq!(joypad.read_p1() & 0x0F, 0x0E);
+
+        joypad.write_p1(0x10);
+        joypad.write_p1(0x30);
+        assert_eq!(joypad.read_p1() & 0x0F, 0x0F);
+    }
+}
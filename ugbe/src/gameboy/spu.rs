@@ -53,12 +53,12 @@ pub struct Spu {
 
 impl Default for Spu {
     fn default() -> Self {
-        Self::new()
+        Self::new(super::Model::default())
     }
 }
 
 impl Spu {
-    pub fn new() -> Self {
+    pub fn new(model: super::Model) -> Self {
         Self {
             enabled: false,
 
@@ -75,7 +75,7 @@ impl Spu {
             voice2_left_enabled: false,
             voice2_right_enabled: false,
 
-            voice3: wave::WaveVoice::new(),
+            voice3: wave::WaveVoice::new(model.has_wave_ram_quirks()),
             voice3_left_enabled: false,
             voice3_right_enabled: false,
 
@@ -174,6 +174,25 @@ impl Spu {
         }
     }
 
+    /// Periods of the channels 1 to 3, written through the write-only NRx3 and NRx4
+    pub fn periods(&self) -> [u16; 3] {
+        [
+            self.voice1.period(),
+            self.voice2.period(),
+            self.voice3.period(),
+        ]
+    }
+
+    /// Length steps left before the channels 1 to 4 are turned off, loaded through the write-only NRx1 bits
+    pub fn length_timers(&self) -> [u16; 4] {
+        [
+            self.voice1.length_timer(),
+            self.voice2.length_timer(),
+            self.voice3.length_timer(),
+            self.voice4.length_timer(),
+        ]
+    }
+
     pub fn read_nr10(&self) -> u8 {
         self.voice1.read_register_0()
     }
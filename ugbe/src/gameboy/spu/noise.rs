@@ -121,6 +121,10 @@ impl NoiseVoice {
         self.lfsr = 0;
     }
 
+    pub fn length_timer(&self) -> u16 {
+        self.length_counter.value()
+    }
+
     pub fn read_register_0(&self) -> u8 {
         0xFF
     }
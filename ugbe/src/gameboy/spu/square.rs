@@ -95,6 +95,14 @@ impl<const FREQUENCY_SWEEP: bool> SquareWaveVoice<FREQUENCY_SWEEP> {
         self.duty_position = 0;
     }
 
+    pub fn period(&self) -> u16 {
+        self.frequency_sweep.current()
+    }
+
+    pub fn length_timer(&self) -> u16 {
+        self.length_counter.value()
+    }
+
     pub fn read_register_0(&self) -> u8 {
         if !FREQUENCY_SWEEP {
             return 0xFF;
@@ -19,13 +19,14 @@ pub struct WaveVoice {
     ram_recently_accessed: bool,
     ram_accessed_after_trigger: bool,
     ram: [u8; 16],
+    ram_quirks: bool,
 
     cycles: usize,
     delay: usize,
 }
 
 impl WaveVoice {
-    pub fn new() -> Self {
+    pub fn new(ram_quirks: bool) -> Self {
         Self {
             enabled: false,
             dac_enabled: false,
@@ -41,6 +42,7 @@ impl WaveVoice {
             ram_recently_accessed: false,
             ram_accessed_after_trigger: false,
             ram: [0; 16],
+            ram_quirks,
 
             cycles: 0,
             delay: 0,
@@ -79,7 +81,9 @@ impl WaveVoice {
     }
 
     fn trigger(&mut self, frame_sequencer: &FrameSequencer) {
-        if !self.enabled {}
+        if self.ram_quirks && self.enabled && self.delay == 0 && self.frequency_timer <= 2 {
+            self.corrupt_ram();
+        }
 
         if self.dac_enabled {
             self.enabled = true;
@@ -95,6 +99,20 @@ impl WaveVoice {
         self.delay = 4;
     }
 
+    /// On DMG, retriggering the voice while it is reading the RAM corrupts its first bytes: if the byte being
+    /// read is one of the first four, it is copied in the first byte, otherwise the aligned block of four bytes
+    /// containing it is copied in the first four bytes.
+    fn corrupt_ram(&mut self) {
+        let idx = (((self.ram_idx + 1) % 32) / 2) as usize;
+
+        if idx < 4 {
+            self.ram[0] = self.ram[idx];
+        } else {
+            let block = idx & !0b11;
+            self.ram.copy_within(block..block + 4, 0);
+        }
+    }
+
     pub fn reset(&mut self, frame_sequencer: &FrameSequencer) {
         self.enabled = false;
         self.dac_enabled = false;
@@ -109,6 +127,14 @@ impl WaveVoice {
         self.ram_idx = 0;
     }
 
+    pub fn period(&self) -> u16 {
+        self.frequency
+    }
+
+    pub fn length_timer(&self) -> u16 {
+        self.length_counter.value()
+    }
+
     pub fn read_register_0(&self) -> u8 {
         (self.dac_enabled as u8) << 7 | 0b0111_1111
     }
@@ -162,7 +188,7 @@ impl WaveVoice {
             self.ram[address as usize]
         } else {
             // In DMG mode we can't read from the RAM when the voice is enabled if we haven't access it recently
-            if self.ram_recently_accessed {
+            if !self.ram_quirks || self.ram_recently_accessed {
                 self.ram[(self.ram_idx / 2) as usize]
             } else {
                 0xFF
@@ -175,7 +201,7 @@ impl WaveVoice {
             self.ram[address as usize] = value;
         } else {
             // In DMG mode we can't write to the RAM when the voice is enabled if we haven't access it recently
-            if self.ram_recently_accessed {
+            if !self.ram_quirks || self.ram_recently_accessed {
                 self.ram[(self.ram_idx / 2) as usize] = value;
             }
         }
@@ -0,0 +1,2109 @@
+use std::path::{Path, PathBuf};
+
+use super::{Gameboy, GameboyBuilder, Model, Warning};
+
+const T_CYCLES_PER_FRAME: usize = 70224;
+
+/// Directory containing the test ROMs, they are not part of the repository and have to be downloaded separately
+fn test_roms_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../roms")
+}
+
+/// Minimal boot ROM only setting up the stack and the LCD before jumping to the cartridge entry point
+fn stub_boot_rom() -> crate::bootrom::BootRom {
+    let mut boot_rom = [0x00; 0x100];
+
+    boot_rom[0xF5..].copy_from_slice(&[
+        0x31, 0xFE, 0xFF, // LD SP, $FFFE
+        0x3E, 0x91, // LD A, $91
+        0xE0, 0x40, // LD (FF00+$40), A
+        0x3E, 0x01, // LD A, $01
+        0xE0, 0x50, // LD (FF00+$50), A
+    ]);
+
+    crate::bootrom::BootRom::from_bytes(&boot_rom).unwrap()
+}
+
+/// Load a test ROM, when a model is given the boot ROM is skipped and the Gameboy starts in the post-boot state of
+/// this model
+fn load_test_rom(name: &str, model: Option<Model>) -> Gameboy {
+    let path = test_roms_path().join(name);
+    let cartridge = crate::cartridge::Cartridge::from_rom_path(&path)
+        .unwrap_or_else(|error| panic!("Can't load the ROM '{}': {}", path.display(), error));
+
+    let builder = match model {
+        Some(model) => GameboyBuilder::new_without_boot_rom(cartridge).set_model(model),
+        None => GameboyBuilder::new(stub_boot_rom(), cartridge),
+    };
+
+    builder.build()
+}
+
+#[test]
+fn boot_rom_variants() {
+    let mut boot_rom = vec![0x00; 0x900];
+    boot_rom[0x100] = 0x42;
+    boot_rom[0x200] = 0x43;
+    let boot_rom = crate::bootrom::BootRom::from_bytes(&boot_rom).unwrap();
+
+    assert_eq!(boot_rom.size(), 0x900);
+    assert_eq!(boot_rom.model(), None);
+    assert!(boot_rom.is_mapped(0x00FF));
+    assert!(!boot_rom.is_mapped(0x0100));
+    assert!(!boot_rom.is_mapped(0x01FF));
+    assert!(boot_rom.is_mapped(0x0200));
+    assert!(!boot_rom.is_mapped(0x0900));
+
+    assert!(crate::bootrom::BootRom::from_bytes(&[0x00; 0x200]).is_err());
+    assert!(!stub_boot_rom().is_mapped(0x0200));
+}
+
+#[test]
+fn boot_rom_size_mismatch_warning() {
+    let builder = GameboyBuilder::new(stub_boot_rom(), build_test_cartridge(&[]));
+    assert!(builder.warnings().is_empty());
+
+    assert_eq!(
+        builder.set_model(Model::CGB).warnings(),
+        vec![Warning::BootRomSizeMismatch {
+            model: Model::CGB,
+            size: 0x100,
+            expected: 0x900,
+        }]
+    );
+}
+
+/// Send a SGB packet through P1 as a game would do
+pub(super) fn send_sgb_packet(
+    joypad: &mut super::joypad::Joypad,
+    packet: [u8; super::sgb::PACKET_SIZE],
+) {
+    joypad.write_p1(0x00);
+    joypad.write_p1(0x30);
+
+    for byte in packet {
+        for bit in 0..8 {
+            joypad.write_p1(if (byte >> bit) & 0b1 != 0 { 0x10 } else { 0x20 });
+            joypad.write_p1(0x30);
+        }
+    }
+
+    joypad.write_p1(0x20);
+    joypad.write_p1(0x30);
+}
+
+#[test]
+fn sgb_screen_without_border() {
+    let mut screen = super::screen::Screen::new(super::screen::Config::default());
+    assert!(screen.sgb_screen().is_none());
+
+    screen.enable_sgb();
+
+    let sgb_screen = screen.sgb_screen().unwrap();
+    let screen_pixel = |x: usize, y: usize| screen.pixels()[y * super::screen::Screen::WIDTH + x];
+    let sgb_screen_pixel =
+        |x: usize, y: usize| sgb_screen.pixels()[y * super::screen::SgbScreen::WIDTH + x];
+
+    // Without any border transferred, the backdrop color is displayed around the Game Boy screen
+    assert_eq!(sgb_screen_pixel(0, 0), screen_pixel(0, 0));
+    assert_eq!(
+        sgb_screen_pixel(
+            super::screen::SgbScreen::SCREEN_X + 159,
+            super::screen::SgbScreen::SCREEN_Y + 143
+        ),
+        screen_pixel(159, 143)
+    );
+}
+
+/// Build a SGB running a cartridge which declares the SGB support and loops forever, the background being filled
+/// with the tile 0
+fn build_sgb_gameboy() -> Gameboy {
+    let mut rom = build_test_rom(&[0x18, 0xFE]); // JR -2
+    rom[0x146] = 0x03;
+    rom[0x14B] = 0x33;
+
+    GameboyBuilder::new_without_boot_rom(cartridge_from_test_rom(rom))
+        .set_model(Model::SGB)
+        .build()
+}
+
+/// Send the packets of a SGB command, then run a few frames so that the screen shows its effect
+fn send_sgb_command(gameboy: &mut Gameboy, command: &[u8]) {
+    for data in command.chunks(super::sgb::PACKET_SIZE) {
+        let mut packet = [0x00; super::sgb::PACKET_SIZE];
+        packet[..data.len()].copy_from_slice(data);
+        send_sgb_packet(&mut gameboy.joypad, packet);
+    }
+
+    for _ in 0..5 * T_CYCLES_PER_FRAME {
+        gameboy.tick();
+    }
+}
+
+#[test]
+fn sgb_colorization() {
+    use super::screen::{Color, Screen};
+
+    let mut gameboy = build_sgb_gameboy();
+
+    // The whole background uses the color 3
+    gameboy.ppu.mut_vram()[..16].fill(0xFF);
+
+    // The color 3 of the palettes 0 to 3 is red, green, blue and white
+    let colors = [
+        Color::new(31, 0, 0),
+        Color::new(0, 31, 0),
+        Color::new(0, 0, 31),
+        Color::new(31, 31, 31),
+    ];
+    #[rustfmt::skip]
+    send_sgb_command(&mut gameboy, &[
+        0x01, // PAL01
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0xE0, 0x03,
+    ]);
+    #[rustfmt::skip]
+    send_sgb_command(&mut gameboy, &[
+        0x09, // PAL23
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7C,
+        0x00, 0x00, 0x00, 0x00, 0xFF, 0x7F,
+    ]);
+
+    // Color of the cell of 8x8 pixels at the given position
+    let cell = |gameboy: &Gameboy, x: usize, y: usize| {
+        gameboy.screen().pixels()[(y * 8 + 4) * Screen::WIDTH + x * 8 + 4]
+    };
+    assert_eq!(cell(&gameboy, 0, 0), colors[0]);
+    assert_eq!(cell(&gameboy, 19, 17), colors[0]);
+
+    // ATTR_BLK: the block (2, 2)-(6, 5) uses the palette 1 inside, 2 on its border and 3 outside
+    send_sgb_command(
+        &mut gameboy,
+        &[0x21, 0x01, 0b111, 0b11_10_01, 0x02, 0x02, 0x06, 0x05],
+    );
+    assert_eq!(cell(&gameboy, 4, 3), colors[1]);
+    assert_eq!(cell(&gameboy, 2, 2), colors[2]);
+    assert_eq!(cell(&gameboy, 6, 4), colors[2]);
+    assert_eq!(cell(&gameboy, 4, 5), colors[2]);
+    assert_eq!(cell(&gameboy, 0, 0), colors[3]);
+    assert_eq!(cell(&gameboy, 7, 3), colors[3]);
+
+    // Only changing the inside of a block also changes its border
+    send_sgb_command(
+        &mut gameboy,
+        &[0x21, 0x01, 0b001, 0b00_00_00, 0x0A, 0x0A, 0x0C, 0x0C],
+    );
+    assert_eq!(cell(&gameboy, 10, 10), colors[0]);
+    assert_eq!(cell(&gameboy, 11, 11), colors[0]);
+    assert_eq!(cell(&gameboy, 13, 11), colors[3]);
+
+    // Only the 5 lower bits of the first parameter give the number of blocks, the following bytes are ignored
+    #[rustfmt::skip]
+    send_sgb_command(&mut gameboy, &[
+        0x21, 0xE1,
+        0b001, 0b00_00_01, 0x0F, 0x0F, 0x10, 0x10,
+        0b001, 0b00_00_00, 0x00, 0x00, 0x00, 0x00,
+    ]);
+    assert_eq!(cell(&gameboy, 15, 15), colors[1]);
+    assert_eq!(cell(&gameboy, 0, 0), colors[3]);
+
+    // ATTR_LIN: the row 1 uses the palette 2, then the column 3 uses the palette 1
+    send_sgb_command(
+        &mut gameboy,
+        &[0x29, 0x02, 0x80 | (2 << 5) | 1, (1 << 5) | 3],
+    );
+    assert_eq!(cell(&gameboy, 0, 1), colors[2]);
+    assert_eq!(cell(&gameboy, 19, 1), colors[2]);
+    assert_eq!(cell(&gameboy, 3, 1), colors[1]);
+    assert_eq!(cell(&gameboy, 3, 17), colors[1]);
+    assert_eq!(cell(&gameboy, 0, 0), colors[3]);
+
+    // ATTR_DIV: the screen is divided at the row 9, palette 1 above, 2 on the row and 3 below
+    send_sgb_command(&mut gameboy, &[0x31, 0x40 | (2 << 4) | (1 << 2) | 3, 0x09]);
+    assert_eq!(cell(&gameboy, 5, 8), colors[1]);
+    assert_eq!(cell(&gameboy, 5, 9), colors[2]);
+    assert_eq!(cell(&gameboy, 5, 10), colors[3]);
+    assert_eq!(cell(&gameboy, 0, 0), colors[1]);
+
+    // ATTR_CHR: the palettes 0 to 3 are set from (18, 0) from left to right, wrapping to the next row
+    send_sgb_command(
+        &mut gameboy,
+        &[0x39, 18, 0, 0x04, 0x00, 0x00, 0b00_01_10_11],
+    );
+    assert_eq!(cell(&gameboy, 18, 0), colors[0]);
+    assert_eq!(cell(&gameboy, 19, 0), colors[1]);
+    assert_eq!(cell(&gameboy, 0, 1), colors[2]);
+    assert_eq!(cell(&gameboy, 1, 1), colors[3]);
+    assert_eq!(cell(&gameboy, 17, 0), colors[1]);
+
+    // PAL_SET: the four palettes use the system palette 0, the default one, and the attribute file 0 is applied
+    send_sgb_command(&mut gameboy, &[0x51, 0, 0, 0, 0, 0, 0, 0, 0, 0x80]);
+    for (x, y) in [(0, 0), (19, 0), (5, 9), (4, 3), (19, 17)] {
+        assert_eq!(cell(&gameboy, x, y), Color::from_rgb24(0x30, 0x18, 0x50));
+    }
+}
+
+/// Display the data of a SGB VRAM transfer: the 256 tiles of the VRAM are shown with the identity palette, from
+/// left to right and top to bottom
+fn display_sgb_transfer_data(gameboy: &mut Gameboy, data: &[u8; 0x1000]) {
+    let vram = gameboy.ppu.mut_vram();
+    vram[..0x1000].copy_from_slice(data);
+    for tile in 0..256 {
+        vram[0x1800 + (tile / 20) * 32 + tile % 20] = tile as u8;
+    }
+
+    gameboy.ppu.write_bgp(0b11_10_01_00);
+}
+
+#[test]
+fn sgb_border() {
+    use super::screen::{Color, SgbScreen};
+
+    let mut gameboy = build_sgb_gameboy();
+
+    // CHR_TRN: the tile 1 only has its top left pixel set, with the color 5
+    let mut tiles = [0x00; 0x1000];
+    tiles[32] = 0x80;
+    tiles[32 + 16] = 0x80;
+    display_sgb_transfer_data(&mut gameboy, &tiles);
+    send_sgb_command(&mut gameboy, &[0x99, 0x00]);
+
+    // PCT_TRN: the tile 1 is displayed with the palettes 4 and 5 and every flip, the palettes are following the
+    // map and have red and green as color 5
+    let mut map = [0x00; 0x1000];
+    let mut set_entry = |x: usize, y: usize, entry: u16| {
+        map[(y * 32 + x) * 2..(y * 32 + x) * 2 + 2].copy_from_slice(&entry.to_le_bytes());
+    };
+    let (palette_4, palette_5) = (4 << 10, 5 << 10);
+    let (x_flip, y_flip) = (1 << 14, 1 << 15);
+    set_entry(0, 0, 0x01 | palette_4);
+    set_entry(1, 0, 0x01 | palette_5 | x_flip);
+    set_entry(0, 1, 0x01 | palette_4 | y_flip);
+    set_entry(1, 1, 0x01 | palette_5 | x_flip | y_flip);
+    set_entry(6, 5, 0x01 | palette_4);
+    map[0x800 + 5 * 2..0x800 + 5 * 2 + 2].copy_from_slice(&0x001Fu16.to_le_bytes());
+    map[0x820 + 5 * 2..0x820 + 5 * 2 + 2].copy_from_slice(&0x03E0u16.to_le_bytes());
+    display_sgb_transfer_data(&mut gameboy, &map);
+    send_sgb_command(&mut gameboy, &[0xA1]);
+
+    let (red, green) = (Color::new(31, 0, 0), Color::new(0, 31, 0));
+    let backdrop = Color::from_rgb24(0xF8, 0xE8, 0xC8);
+
+    let screen = gameboy.screen().pixels();
+    let sgb_screen = gameboy.sgb_screen().unwrap().pixels();
+    let pixel = |x: usize, y: usize| sgb_screen[y * SgbScreen::WIDTH + x];
+
+    // The pixels using the color 0 of their palette are transparent and show the backdrop
+    assert_eq!(pixel(0, 0), red);
+    assert_eq!(pixel(1, 0), backdrop);
+    assert_eq!(pixel(0, 1), backdrop);
+    assert_eq!(pixel(8 + 7, 0), green);
+    assert_eq!(pixel(8, 0), backdrop);
+    assert_eq!(pixel(0, 8 + 7), red);
+    assert_eq!(pixel(0, 8), backdrop);
+    assert_eq!(pixel(8 + 7, 8 + 7), green);
+    assert_eq!(pixel(8, 8), backdrop);
+    assert_eq!(pixel(100, 20), backdrop);
+
+    // The Game Boy screen is displayed at (48, 40) under the border
+    assert_eq!((SgbScreen::SCREEN_X, SgbScreen::SCREEN_Y), (48, 40));
+    assert_eq!(pixel(48, 40), red);
+    for (x, y) in [(1, 0), (0, 1), (80, 72), (159, 143)] {
+        assert_eq!(pixel(48 + x, 40 + y), screen[y * 160 + x]);
+    }
+    assert_eq!(pixel(47, 41), backdrop);
+    assert_eq!(pixel(48 + 160, 40 + 143), backdrop);
+    assert_eq!(pixel(48 + 159, 40 + 144), backdrop);
+}
+
+/// Build a 32KB ROM only cartridge running the program at 0x150
+fn build_test_cartridge(program: &[u8]) -> crate::cartridge::Cartridge {
+    cartridge_from_test_rom(build_test_rom(program))
+}
+
+/// ROM jumping to the program at 0x150, without the checksums
+pub(super) fn build_test_rom(program: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0x00; 0x8000];
+
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x104..0x134].copy_from_slice(&crate::cartridge::NINTENDO_LOGO);
+    rom[0x150..0x150 + program.len()].copy_from_slice(program);
+
+    rom
+}
+
+fn cartridge_from_test_rom(mut rom: Vec<u8>) -> crate::cartridge::Cartridge {
+    rom[0x14D] = rom[0x134..0x14D]
+        .iter()
+        .fold(0u8, |checksum, value| checksum.wrapping_add(!value));
+
+    let global_checksum = rom
+        .iter()
+        .fold(0u16, |checksum, value| checksum.wrapping_add(*value as u16));
+    rom[0x14E..0x150].copy_from_slice(&global_checksum.to_be_bytes());
+
+    crate::cartridge::Cartridge::from_rom(rom).unwrap()
+}
+
+/// Run a program with the given code at the restart and interrupt vectors
+fn run_test_program(program: &[u8], vectors: &[(usize, &[u8])], cycles: usize) -> Gameboy {
+    let mut rom = build_test_rom(program);
+    for (address, code) in vectors {
+        rom[*address..*address + code.len()].copy_from_slice(code);
+    }
+
+    let mut gameboy = GameboyBuilder::new_without_boot_rom(cartridge_from_test_rom(rom)).build();
+    for _ in 0..cycles {
+        gameboy.tick();
+    }
+
+    gameboy
+}
+
+#[test]
+fn halt_bug() {
+    let program = [
+        0xF3, // 0x150: DI
+        0x3E, 0x01, // 0x151: LD A, 0x01
+        0xE0, 0xFF, // 0x153: LDH (IE), A
+        0xE0, 0x0F, // 0x155: LDH (IF), A
+        0x76, // 0x157: HALT
+        0x04, // 0x158: INC B
+        0x18, 0xFE, // 0x159: JR -2
+    ];
+    let gameboy = run_test_program(&program, &[], 1_000);
+
+    // With IME cleared and an interrupt pending, HALT doesn't halt and the byte following it is read twice
+    assert_eq!(gameboy.cpu.registers().b(), 0x02);
+    assert!(!gameboy.cpu.is_halted());
+}
+
+#[test]
+fn ei_followed_by_halt() {
+    let program = [
+        0x3E, 0x01, // 0x150: LD A, 0x01
+        0xE0, 0xFF, // 0x152: LDH (IE), A
+        0xE0, 0x0F, // 0x154: LDH (IF), A
+        0xFB, // 0x156: EI
+        0x76, // 0x157: HALT
+        0x18, 0xFE, // 0x158: JR -2
+    ];
+    let vblank_handler: &[u8] = &[
+        0xD1, // POP DE
+        0x18, 0xFE, // JR -2
+    ];
+    let gameboy = run_test_program(&program, &[(0x40, vblank_handler)], 1_000);
+
+    // The interrupt is dispatched right after EI and returns to the HALT instruction, to execute it again
+    assert_eq!(gameboy.cpu.registers().de(), 0x0157);
+}
+
+#[test]
+fn ie_push_cancellation() {
+    let program = [
+        0x31, 0x00, 0x00, // 0x150: LD SP, 0x0000
+        0x3E, 0x04, // 0x153: LD A, 0x04
+        0xE0, 0xFF, // 0x155: LDH (IE), A
+        0xE0, 0x0F, // 0x157: LDH (IF), A
+        0xFB, // 0x159: EI
+        0x00, // 0x15A: NOP
+        0x18, 0xFE, // 0x15B: JR -2
+    ];
+    let reset: &[u8] = &[
+        0x06, 0x42, // LD B, 0x42
+        0x18, 0xFE, // JR -2
+    ];
+    let timer_handler: &[u8] = &[
+        0x06, 0x50, // LD B, 0x50
+        0x18, 0xFE, // JR -2
+    ];
+    let gameboy = run_test_program(&program, &[(0x00, reset), (0x50, timer_handler)], 1_000);
+
+    // Pushing the MSB of PC (0x01) to 0xFFFF disables the timer interrupt, the dispatch jumps to 0x0000 and the
+    // interrupt stays requested
+    assert_eq!(gameboy.cpu.registers().b(), 0x42);
+    assert_eq!(gameboy.interrupt.enable() & 0x1F, 0x01);
+    assert_eq!(gameboy.interrupt.flags() & 0x04, 0x04);
+}
+
+#[test]
+fn ppu_memory_locking_edges() {
+    for model in [Model::DMG, Model::CGB] {
+        let mut gameboy = GameboyBuilder::new_without_boot_rom(build_test_cartridge(&[]))
+            .set_model(model)
+            .build();
+        let stat_mode = |gameboy: &Gameboy| gameboy.ppu.read_stat() & 0b11;
+
+        // Reach the first T-cycle of the OAM scan of a line
+        while stat_mode(&gameboy) != 0 {
+            gameboy.tick();
+        }
+        while stat_mode(&gameboy) != 2 {
+            gameboy.tick();
+        }
+
+        gameboy.ppu.mut_vram()[0] = 0x11;
+        gameboy.ppu.mut_oam().write_byte(0, 0x22);
+        let early_lock = !model.is_cgb();
+
+        // Mode 2 -> 3: the DMG locks the VRAM reads and unlocks the OAM writes 6 T-cycles before Drawing
+        for _ in 0..73 {
+            gameboy.tick();
+        }
+        assert_eq!(gameboy.read_byte(0x8000), 0x11);
+        assert_eq!(gameboy.read_byte(0xFE00), 0xFF);
+        assert!(gameboy.ppu.oam_write_locked());
+
+        gameboy.tick();
+        assert_eq!(stat_mode(&gameboy), 2);
+        assert_eq!(gameboy.read_byte(0x8000) == 0xFF, early_lock);
+        assert_eq!(gameboy.read_byte(0xFE00), 0xFF);
+        assert!(!gameboy.ppu.vram_write_locked());
+        assert_eq!(gameboy.ppu.oam_write_locked(), !early_lock);
+
+        for _ in 0..5 {
+            gameboy.tick();
+        }
+        assert_eq!(stat_mode(&gameboy), 2);
+        assert!(!gameboy.ppu.vram_write_locked());
+
+        gameboy.tick();
+        assert_eq!(stat_mode(&gameboy), 3);
+        assert_eq!(gameboy.read_byte(0x8000), 0xFF);
+        assert_eq!(gameboy.read_byte(0xFE00), 0xFF);
+        assert!(gameboy.ppu.vram_write_locked());
+        assert!(gameboy.ppu.oam_write_locked());
+
+        // Mode 3 -> 0: everything is unlocked on the first T-cycle of HBlank
+        let mut last_drawing_cycle = None;
+        while stat_mode(&gameboy) == 3 {
+            last_drawing_cycle = Some((
+                gameboy.read_byte(0x8000),
+                gameboy.read_byte(0xFE00),
+                gameboy.ppu.vram_write_locked(),
+                gameboy.ppu.oam_write_locked(),
+            ));
+            gameboy.tick();
+        }
+        assert_eq!(last_drawing_cycle, Some((0xFF, 0xFF, true, true)));
+
+        assert_eq!(stat_mode(&gameboy), 0);
+        assert_eq!(gameboy.read_byte(0x8000), 0x11);
+        assert_eq!(gameboy.read_byte(0xFE00), 0x22);
+        assert!(!gameboy.ppu.vram_write_locked());
+        assert!(!gameboy.ppu.oam_write_locked());
+    }
+}
+
+#[test]
+fn oam_bug_inc_hl() {
+    let program = [
+        0x21, 0x00, 0xFE, // 0x150: LD HL, 0xFE00
+        0xF0, 0x41, // 0x153: LDH A, (STAT)
+        0xE6, 0x03, // 0x155: AND 0x03
+        0x20, 0xFA, // 0x157: JR NZ, -6
+        0xF0, 0x41, // 0x159: LDH A, (STAT)
+        0xE6, 0x03, // 0x15B: AND 0x03
+        0xFE, 0x02, // 0x15D: CP 0x02
+        0x20, 0xF8, // 0x15F: JR NZ, -8
+        0x23, // 0x161: INC HL
+        0x18, 0xFE, // 0x162: JR -2
+    ];
+    let mut gameboy = GameboyBuilder::new_without_boot_rom(build_test_cartridge(&program)).build();
+    for idx in 0..0xA0u16 {
+        let value = (idx as u8).wrapping_mul(0x4D).wrapping_add(0x1B);
+        gameboy.ppu.mut_oam().write_byte(idx, value);
+    }
+    let before: Vec<u8> = (0..0xA0)
+        .map(|idx| gameboy.ppu.oam().read_byte(idx))
+        .collect();
+
+    // INC HL puts HL on the address bus during the OAM scan, which corrupts the row being scanned like a write
+    while gameboy.cpu_state().pc != 0x162 {
+        gameboy.tick();
+    }
+    let after: Vec<u8> = (0..0xA0)
+        .map(|idx| gameboy.ppu.oam().read_byte(idx))
+        .collect();
+
+    let row = (0..20)
+        .find(|row| before[row * 8..][..8] != after[row * 8..][..8])
+        .expect("no row has been corrupted");
+    assert!(row > 0);
+
+    let word = |row: usize, idx: usize| {
+        u16::from_le_bytes([before[row * 8 + idx * 2], before[row * 8 + idx * 2 + 1]])
+    };
+    let (a, b, c) = (word(row, 0), word(row - 1, 0), word(row - 1, 2));
+    let mut expected = before.clone();
+    expected[row * 8..][..2].copy_from_slice(&(((a ^ c) & (b ^ c)) ^ c).to_le_bytes());
+    expected[row * 8 + 2..][..6].copy_from_slice(&before[row * 8 - 6..row * 8]);
+    assert_eq!(after, expected);
+}
+
+/// Program exchanging a byte on the link cable, then sending back the received byte incremented by one
+fn link_handshake_program(value: u8, serial_control: u8) -> Vec<u8> {
+    let exchange = [
+        0xE0,
+        0x01, // LDH (SB), A
+        0x3E,
+        serial_control, // LD A, serial_control
+        0xE0,
+        0x02, // LDH (SC), A
+        0xF0,
+        0x02, // LDH A, (SC)
+        0xCB,
+        0x7F, // BIT 7, A
+        0x20,
+        0xFA, // JR NZ, -6
+        0xF0,
+        0x01, // LDH A, (SB)
+    ];
+
+    let mut program = vec![0x3E, value]; // LD A, value
+    program.extend_from_slice(&exchange);
+    program.push(0x3C); // INC A
+    program.extend_from_slice(&exchange);
+    program.extend_from_slice(&[0x18, 0xFE]); // JR -2
+
+    program
+}
+
+#[test]
+fn link_cable_handshake() {
+    let master = GameboyBuilder::new_without_boot_rom(build_test_cartridge(
+        &link_handshake_program(0x42, 0x81),
+    ))
+    .build();
+    let slave = GameboyBuilder::new_without_boot_rom(build_test_cartridge(
+        &link_handshake_program(0x99, 0x80),
+    ))
+    .build();
+
+    let mut pair = super::link::LinkedPair::new(master, slave);
+
+    // Two bytes at 8192 bits per second take a bit less than 10000 T-cycles
+    for _ in 0..20_000 {
+        pair.tick();
+    }
+
+    assert_eq!(pair.first().serial().read_sc() & 0x80, 0x00);
+    assert_eq!(pair.second().serial().read_sc() & 0x80, 0x00);
+    assert_eq!(pair.first().serial().read_sb(), 0x43);
+    assert_eq!(pair.second().serial().read_sb(), 0x9A);
+}
+
+#[test]
+fn tcp_link_cable_handshake() {
+    use super::serial::tcp::TcpLink;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    // Both sides wait for the version of the other one when connecting
+    let client = std::thread::spawn(move || TcpLink::connect(address).unwrap());
+    let server = TcpLink::accept(&listener).unwrap();
+    let client = client.join().unwrap();
+
+    let mut master = GameboyBuilder::new_without_boot_rom(build_test_cartridge(
+        &link_handshake_program(0x42, 0x81),
+    ))
+    .build();
+    master.serial().connect(Box::new(client));
+
+    let mut slave = GameboyBuilder::new_without_boot_rom(build_test_cartridge(
+        &link_handshake_program(0x99, 0x80),
+    ))
+    .build();
+    slave.serial().connect(Box::new(server));
+
+    // Each side runs at its own pace, the transfers take as long as the packets need to go through localhost. The
+    // master clocks both transfers and each one completes, at worst with 0xFF once the link timed out, so it always
+    // reaches the final loop. The slave already holds its final SB when it answers the last transfer.
+    let final_loop = 0x150 + link_handshake_program(0x42, 0x81).len() as u16 - 2;
+    while master.cpu_state().pc < final_loop {
+        master.tick();
+        slave.tick();
+    }
+
+    assert_eq!(master.serial().read_sb(), 0x43);
+    assert_eq!(slave.serial().read_sb(), 0x9A);
+    assert!(master
+        .serial()
+        .peripheral::<TcpLink>()
+        .unwrap()
+        .is_connected());
+}
+
+#[test]
+fn tcp_link_silent_peer() {
+    use std::io::{Read, Write};
+
+    use super::serial::{tcp::TcpLink, Peripheral};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    // The peer answers the handshake, then reads the status and the transfer without ever answering
+    let peer = std::thread::spawn(move || {
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        stream.write_all(&[1, 1, 4, 0, 0, 0, 0, 0]).unwrap();
+
+        let mut packets = [0; 24];
+        stream.read_exact(&mut packets).unwrap();
+        (stream, packets)
+    });
+
+    let mut link = TcpLink::accept(&listener)
+        .unwrap()
+        .set_exchange_timeout(std::time::Duration::from_millis(50));
+
+    // The transfer completes with 0xFF as if the cable was unplugged, while the peer is still connected
+    assert_eq!(link.exchange(0x42), 0xFF);
+    assert!(link.is_connected());
+
+    let (_stream, packets) = peer.join().unwrap();
+    assert_eq!(packets[16..19], [104, 0x42, 0x81]);
+}
+
+#[test]
+fn tcp_link_listen_in_background() {
+    use super::serial::registry::Registry;
+    use super::serial::tcp::{TcpLink, TcpListenLink};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    // The Gameboy keeps running with the cable unplugged until the other side connects
+    let mut gameboy = GameboyBuilder::new_without_boot_rom(build_test_cartridge(
+        &link_handshake_program(0x42, 0x81),
+    ))
+    .build();
+    gameboy
+        .serial()
+        .connect(Box::new(TcpListenLink::new(listener)));
+    let final_loop = 0x150 + link_handshake_program(0x42, 0x81).len() as u16 - 2;
+    while gameboy.cpu_state().pc < final_loop {
+        gameboy.tick();
+    }
+    assert_eq!(gameboy.serial().read_sb(), 0xFF);
+
+    let client = TcpLink::connect(address).unwrap();
+    let started = std::time::Instant::now();
+    while gameboy
+        .serial()
+        .peripheral::<TcpListenLink>()
+        .unwrap()
+        .link()
+        .is_none()
+    {
+        gameboy.tick();
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    }
+    assert!(client.is_connected());
+
+    // The registry doesn't wait for the other side either
+    let peripheral = Registry::new()
+        .create_from_spec("tcp-listen:127.0.0.1:0")
+        .unwrap();
+    gameboy.serial().connect(peripheral);
+    assert!(gameboy.serial().peripheral::<TcpListenLink>().is_some());
+}
+
+#[test]
+fn four_player_adapter_ping() {
+    use super::link::four_player::{FourPlayerAdapter, Phase};
+
+    // Answer every byte clocked by the adapter with 0x88
+    let program = [
+        0x3E, 0x88, // LD A, 0x88
+        0xE0, 0x01, // LDH (SB), A
+        0x3E, 0x80, // LD A, 0x80
+        0xE0, 0x02, // LDH (SC), A
+        0xF0, 0x02, // LDH A, (SC)
+        0xCB, 0x7F, // BIT 7, A
+        0x20, 0xFA, // JR NZ, -6
+        0x18, 0xF0, // JR -16
+    ];
+
+    let gameboys = (0..3)
+        .map(|_| GameboyBuilder::new_without_boot_rom(build_test_cartridge(&program)).build())
+        .collect();
+    let mut adapter = FourPlayerAdapter::new(gameboys).unwrap();
+
+    // Two ping packets: the first one to detect the players, the second one to send their status
+    for _ in 0..(8 * 0x4000) {
+        adapter.tick();
+    }
+
+    assert_eq!(adapter.phase(), Phase::Ping);
+    assert_eq!(adapter.connected_players(), [true, true, true, false]);
+
+    // Last byte of the ping packet holds the connected players and the ID of the player
+    for idx in 0..3 {
+        assert_eq!(
+            adapter.player(idx).serial().read_sb(),
+            0x70 | (idx as u8 + 1)
+        );
+    }
+}
+
+#[test]
+fn four_player_adapter_transmission() {
+    use super::link::four_player::{Error, FourPlayerAdapter, Phase, PORT_COUNT};
+
+    // Send the bytes of the table at 0x200 to the adapter, storing the received ones from 0xC000
+    let program = [
+        0x21, 0x00, 0x02, // 0x150: LD HL, 0x0200
+        0x11, 0x00, 0xC0, // 0x153: LD DE, 0xC000
+        0x2A, // 0x156: LD A, (HL+)
+        0xE0, 0x01, // 0x157: LDH (SB), A
+        0x3E, 0x80, // 0x159: LD A, 0x80
+        0xE0, 0x02, // 0x15B: LDH (SC), A
+        0xF0, 0x02, // 0x15D: LDH A, (SC)
+        0xCB, 0x7F, // 0x15F: BIT 7, A
+        0x20, 0xFA, // 0x161: JR NZ, -6
+        0xF0, 0x01, // 0x163: LDH A, (SB)
+        0x12, // 0x165: LD (DE), A
+        0x13, // 0x166: INC DE
+        0x18, 0xED, // 0x167: JR -19
+    ];
+
+    // Each player answers the first ping with a rate of 0 and packets of 1 byte, player 1 then asks to start the
+    // transmission, and every player sends its own byte at the start of the transmission packets
+    let data = [0x11, 0x22, 0x33, 0x44];
+    let build = |player: usize| {
+        let mut table = vec![0x88, 0x88, 0x00, 0x01];
+        table.extend_from_slice(match player {
+            0 => &[0xAA; 4],
+            _ => &[0x88, 0x88, 0x00, 0x01],
+        });
+        table.extend_from_slice(&[0x00; 4]);
+        for _ in 0..2 {
+            table.extend_from_slice(&[data[player], 0x00, 0x00, 0x00]);
+        }
+
+        let mut rom = build_test_rom(&program);
+        rom[0x200..0x200 + table.len()].copy_from_slice(&table);
+        GameboyBuilder::new_without_boot_rom(cartridge_from_test_rom(rom)).build()
+    };
+
+    let too_many = (0..PORT_COUNT + 1)
+        .map(|_| GameboyBuilder::new_without_boot_rom(build_test_cartridge(&[])).build())
+        .collect();
+    assert_eq!(
+        FourPlayerAdapter::new(too_many).err(),
+        Some(Error::TooManyGameboys(5))
+    );
+
+    let mut adapter = FourPlayerAdapter::new((0..PORT_COUNT).map(build).collect()).unwrap();
+
+    // Two ping packets, the four transmission headers and two transmission packets, then let the players store the
+    // last byte
+    for _ in 0..(12 * 0x4000 + 8 * 0xA00 + 0x100) {
+        adapter.tick();
+    }
+
+    assert_eq!(adapter.phase(), Phase::Transmission);
+    assert_eq!(adapter.connected_players(), [true; PORT_COUNT]);
+
+    for player in 0..PORT_COUNT {
+        let received = adapter.player(player).memory().dump_range(0xC000..=0xC013);
+
+        // The second ping packet holds the connected players and the ID of the player
+        assert_eq!(received[4], 0xFE);
+        assert_eq!(received[7], 0xF0 | (player as u8 + 1));
+        assert_eq!(received[8..12], [0xCC; 4]);
+
+        // The first packet is empty, the second one holds the bytes collected from all the players
+        assert_eq!(received[12..16], [0x00; 4]);
+        assert_eq!(received[16..20], data);
+    }
+}
+
+#[test]
+fn infrared_pair() {
+    use super::infrared::InfraredPair;
+
+    let build = |model: Model, rp: u8| {
+        let program = [
+            0x3E, rp, // LD A, rp
+            0xE0, 0x56, // LDH (RP), A
+            0x18, 0xFE, // JR -2
+        ];
+        GameboyBuilder::new_without_boot_rom(build_test_cartridge(&program))
+            .set_model(model)
+            .build()
+    };
+
+    // The first Gameboy switches its LED on, the second one only enables the reading
+    let mut emitter = build(Model::CGB, 0xC1);
+    let mut receiver = build(Model::CGB, 0xC0);
+
+    let (first, second) = InfraredPair::new();
+    emitter.infrared().connect(Box::new(first));
+    receiver.infrared().connect(Box::new(second));
+
+    for _ in 0..1000 {
+        emitter.tick();
+    }
+
+    // The light is only received once the receiver reached the time it has been emitted at
+    assert_eq!(receiver.infrared().read_rp(), 0x3E);
+    for _ in 0..1000 {
+        receiver.tick();
+    }
+    assert_eq!(receiver.infrared().read_rp(), 0xFC);
+    assert_eq!(emitter.infrared().read_rp(), 0xFF);
+
+    // RP only exists on CGB models
+    let mut dmg = build(Model::DMG, 0x00);
+    for _ in 0..1000 {
+        dmg.tick();
+    }
+    assert_eq!(dmg.infrared().read_rp(), 0xFF);
+}
+
+/// Send a packet to the Mobile Adapter GB, return the acknowledgement and the response packet
+fn send_mobile_packet(
+    adapter: &mut super::serial::mobile::MobileAdapter,
+    command: u8,
+    data: &[u8],
+    corrupted: bool,
+) -> (u8, Option<(u8, Vec<u8>)>) {
+    use super::serial::Peripheral;
+
+    let mut packet = vec![command, 0x00];
+    packet.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    packet.extend_from_slice(data);
+
+    let checksum = packet.iter().fold(corrupted as u16, |checksum, byte| {
+        checksum.wrapping_add(*byte as u16)
+    });
+
+    for byte in [0x99, 0x66]
+        .iter()
+        .chain(&packet)
+        .chain(&checksum.to_be_bytes())
+    {
+        assert_eq!(adapter.exchange(*byte), 0xD2);
+    }
+
+    assert_eq!(adapter.exchange(0x80), 0x88);
+    let acknowledge = adapter.exchange(0x00);
+
+    if adapter.exchange(0x4B) != 0x99 {
+        return (acknowledge, None);
+    }
+    assert_eq!(adapter.exchange(0x4B), 0x66);
+
+    let header: Vec<u8> = (0..4).map(|_| adapter.exchange(0x4B)).collect();
+    let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+    let data: Vec<u8> = (0..length).map(|_| adapter.exchange(0x4B)).collect();
+
+    let checksum = header
+        .iter()
+        .chain(&data)
+        .fold(0u16, |checksum, byte| checksum.wrapping_add(*byte as u16));
+    let received_checksum = u16::from_be_bytes([adapter.exchange(0x4B), adapter.exchange(0x4B)]);
+    assert_eq!(received_checksum, checksum);
+
+    assert_eq!(adapter.exchange(0x80), 0x88);
+    adapter.exchange(header[0] ^ 0x80);
+
+    (acknowledge, Some((header[0], data)))
+}
+
+#[test]
+fn mobile_adapter_session() {
+    use super::serial::mobile::{fake::FakeServer, MobileAdapter};
+
+    let server = FakeServer::new()
+        .add_telephone("#9677", Box::new(|_| Vec::new()))
+        .add_host("gameboy.datacenter.ne.jp", [192, 168, 0, 1])
+        .add_service(
+            [192, 168, 0, 1],
+            80,
+            Box::new(|data| data.iter().rev().copied().collect()),
+        );
+    let mut adapter = MobileAdapter::new(Box::new(server));
+
+    // Commands are refused until the session begins
+    assert_eq!(
+        send_mobile_packet(&mut adapter, 0x17, &[], false),
+        (0x97, Some((0xEE, vec![0x17, 0x10])))
+    );
+
+    assert_eq!(
+        send_mobile_packet(&mut adapter, 0x10, b"NINTENDO", true),
+        (0xF1, None)
+    );
+    assert_eq!(
+        send_mobile_packet(&mut adapter, 0x10, b"NINTENDO", false),
+        (0x90, Some((0x90, b"NINTENDO".to_vec())))
+    );
+
+    // Unknown numbers are busy
+    assert_eq!(
+        send_mobile_packet(&mut adapter, 0x12, b"\x00#1234", false).1,
+        Some((0xEE, vec![0x12, 0x00]))
+    );
+    assert_eq!(
+        send_mobile_packet(&mut adapter, 0x12, b"\x00#9677", false).1,
+        Some((0x92, vec![]))
+    );
+
+    let mut login = vec![2, b'i', b'd', 2, b'p', b'w'];
+    login.extend_from_slice(&[1, 1, 1, 1, 2, 2, 2, 2]);
+    assert_eq!(
+        send_mobile_packet(&mut adapter, 0x21, &login, false).1,
+        Some((0xA1, vec![10, 0, 0, 2, 1, 1, 1, 1, 2, 2, 2, 2]))
+    );
+
+    assert_eq!(
+        send_mobile_packet(&mut adapter, 0x28, b"gameboy.datacenter.ne.jp", false).1,
+        Some((0xA8, vec![192, 168, 0, 1]))
+    );
+    assert_eq!(
+        send_mobile_packet(&mut adapter, 0x23, &[192, 168, 0, 1, 0, 80], false).1,
+        Some((0xA3, vec![0]))
+    );
+    assert_eq!(
+        send_mobile_packet(&mut adapter, 0x15, &[0, 1, 2, 3], false).1,
+        Some((0x95, vec![0, 3, 2, 1]))
+    );
+    assert_eq!(
+        send_mobile_packet(&mut adapter, 0x24, &[0], false).1,
+        Some((0xA4, vec![0]))
+    );
+
+    assert_eq!(
+        send_mobile_packet(&mut adapter, 0x17, &[], false).1,
+        Some((0x97, vec![0x05, 0x4D, 0x00]))
+    );
+    assert_eq!(
+        send_mobile_packet(&mut adapter, 0x13, &[], false).1,
+        Some((0x93, vec![]))
+    );
+    assert_eq!(
+        send_mobile_packet(&mut adapter, 0x11, &[], false).1,
+        Some((0x91, vec![]))
+    );
+    assert!(!adapter.is_in_session());
+    assert!(!adapter.backend::<FakeServer>().unwrap().is_logged_in());
+}
+
+#[test]
+fn barcode_boy_swipe() {
+    use super::serial::barcode::BarcodeBoy;
+    use super::serial::registry::Registry;
+
+    let card_path = std::env::temp_dir().join(format!("ugbe-card-{}.txt", std::process::id()));
+    std::fs::write(&card_path, "4902370501234\n").unwrap();
+
+    let registry = Registry::new();
+    assert!(registry
+        .create_from_spec("barcode-boy:/missing/card.txt")
+        .is_err());
+    assert!(registry.create("unknown", None).is_err());
+
+    let mut peripheral = registry
+        .create_from_spec(&format!("barcode-boy:{}", card_path.display()))
+        .unwrap();
+    std::fs::remove_file(&card_path).unwrap();
+
+    let responses: Vec<u8> = [0x10, 0x07, 0x10, 0x07]
+        .iter()
+        .map(|byte| peripheral.exchange(*byte))
+        .collect();
+    assert_eq!(responses, [0xFF, 0xFF, 0x10, 0x07]);
+
+    // Receive the bytes clocked by the Barcode Boy in 0xC000
+    let program = [
+        0x21, 0x00, 0xC0, // LD HL, 0xC000
+        0x3E, 0x80, // LD A, 0x80
+        0xE0, 0x02, // LDH (SC), A
+        0xF0, 0x02, // LDH A, (SC)
+        0xCB, 0x7F, // BIT 7, A
+        0x20, 0xFA, // JR NZ, -6
+        0xF0, 0x01, // LDH A, (SB)
+        0x22, // LD (HL+), A
+        0x18, 0xF1, // JR -15
+    ];
+
+    let mut gameboy = GameboyBuilder::new_without_boot_rom(build_test_cartridge(&program)).build();
+    gameboy.serial().connect(peripheral);
+
+    let barcode_boy = gameboy.serial().peripheral_mut::<BarcodeBoy>().unwrap();
+    assert!(barcode_boy.is_detected());
+    assert_eq!(barcode_boy.swipe_next().unwrap().barcode(), "4902370501234");
+
+    for _ in 0..(16 * 0x1000 + 1000) {
+        gameboy.tick();
+    }
+
+    let received: Vec<u8> = (0..15).map(|idx| gameboy.work_ram[idx]).collect();
+    assert_eq!(received, b"\x024902370501234\x03");
+}
+
+/// Program calling a function incrementing A, with its value stored in and loaded from 0xC000
+pub(super) const DEBUGGER_PROGRAM: [u8; 19] = [
+    0x3E, 0x12, // 0x150: LD A, 0x12
+    0xEA, 0x00, 0xC0, // 0x152: LD (0xC000), A
+    0xCD, 0x60, 0x01, // 0x155: CALL 0x0160
+    0xFA, 0x00, 0xC0, // 0x158: LD A, (0xC000)
+    0x18, 0xFE, // 0x15B: JR -2
+    0x00, 0x00, 0x00, // 0x15D: Padding
+    0x00, // 0x160: NOP
+    0x3C, // 0x161: INC A
+    0xC9, // 0x162: RET
+];
+
+#[test]
+fn debugger_breakpoints_and_watchpoints() {
+    use super::debug::{Access, Breakpoint, Debugger, PauseReason, Watchpoint};
+
+    let mut gameboy =
+        GameboyBuilder::new_without_boot_rom(build_test_cartridge(&DEBUGGER_PROGRAM)).build();
+    let mut debugger = Debugger::new();
+
+    // The breakpoint only applies to the ROM bank 1
+    debugger.add_breakpoint(Breakpoint::new(0x155).set_bank(1));
+    assert_eq!(debugger.run(&mut gameboy, 10_000), None);
+    assert!(debugger.remove_breakpoint(&Breakpoint::new(0x155).set_bank(1)));
+
+    let mut gameboy =
+        GameboyBuilder::new_without_boot_rom(build_test_cartridge(&DEBUGGER_PROGRAM)).build();
+
+    debugger.add_breakpoint(Breakpoint::new(0x155).set_bank(0));
+    debugger.add_watchpoint(Watchpoint::write(0xC000..=0xC0FF));
+    assert_eq!(
+        debugger.run(&mut gameboy, 10_000),
+        Some(&PauseReason::Watchpoint {
+            watchpoint: Watchpoint::write(0xC000..=0xC0FF),
+            access: Access::Write(0x12),
+            address: 0xC000,
+            bank: 0,
+            pc: 0x152,
+            register: None,
+            label: None,
+        })
+    );
+
+    // Nothing is executed while paused
+    let pause_reason = debugger.pause_reason().cloned();
+    assert_eq!(debugger.run(&mut gameboy, 10_000).cloned(), pause_reason);
+    assert_eq!(debugger.instruction_address(), Some(0x152));
+
+    debugger.resume();
+    assert_eq!(
+        debugger.run(&mut gameboy, 10_000),
+        Some(&PauseReason::Breakpoint(Breakpoint::new(0x155).set_bank(0)))
+    );
+    assert_eq!(debugger.instruction_address(), Some(0x155));
+
+    debugger.step_over();
+    assert_eq!(debugger.run(&mut gameboy, 10_000), Some(&PauseReason::Step));
+    assert_eq!(debugger.instruction_address(), Some(0x158));
+
+    debugger.add_watchpoint(Watchpoint::read(0xC000..=0xC000));
+    debugger.resume();
+    assert_eq!(
+        debugger.run(&mut gameboy, 10_000),
+        Some(&PauseReason::Watchpoint {
+            watchpoint: Watchpoint::read(0xC000..=0xC000),
+            access: Access::Read(0x12),
+            address: 0xC000,
+            bank: 0,
+            pc: 0x158,
+            register: None,
+            label: None,
+        })
+    );
+}
+
+#[test]
+fn debugger_steps() {
+    use super::debug::{Breakpoint, Debugger, PauseReason};
+
+    let mut gameboy =
+        GameboyBuilder::new_without_boot_rom(build_test_cartridge(&DEBUGGER_PROGRAM)).build();
+    let mut debugger = Debugger::new();
+
+    debugger.add_breakpoint(Breakpoint::new(0x155));
+    assert!(debugger.run(&mut gameboy, 10_000).is_some());
+    assert!(debugger.remove_breakpoint(&Breakpoint::new(0x155)));
+
+    debugger.step_into();
+    assert_eq!(debugger.run(&mut gameboy, 10_000), Some(&PauseReason::Step));
+    assert_eq!(debugger.instruction_address(), Some(0x160));
+
+    debugger.step_over();
+    assert_eq!(debugger.run(&mut gameboy, 10_000), Some(&PauseReason::Step));
+    assert_eq!(debugger.instruction_address(), Some(0x161));
+
+    debugger.step_out();
+    assert_eq!(debugger.run(&mut gameboy, 10_000), Some(&PauseReason::Step));
+    assert_eq!(debugger.instruction_address(), Some(0x158));
+
+    debugger.resume();
+    assert_eq!(debugger.run(&mut gameboy, 10_000), None);
+
+    debugger.pause();
+    assert_eq!(
+        debugger.run(&mut gameboy, 10_000),
+        Some(&PauseReason::Requested)
+    );
+    assert_eq!(debugger.instruction_address(), Some(0x15B));
+}
+
+#[test]
+fn debugger_interrupts() {
+    use super::debug::{self, Access, Debugger, Interrupt, PauseReason, Watchpoint};
+
+    let program = [
+        0x3E, 0x04, // LD A, 0x04
+        0xE0, 0xFF, // LDH (IE), A
+        0x3E, 0x05, // LD A, 0x05
+        0xE0, 0x07, // LDH (TAC), A
+        0xFB, // EI
+        0x18, 0xFE, // JR -2
+    ];
+
+    let mut gameboy = GameboyBuilder::new_without_boot_rom(build_test_cartridge(&program)).build();
+    let mut debugger = Debugger::new();
+
+    let tac = debug::io_register_address("tac").unwrap();
+    debugger.add_watchpoint(Watchpoint::io_register(tac));
+    assert_eq!(
+        debugger.run(&mut gameboy, 10_000),
+        Some(&PauseReason::Watchpoint {
+            watchpoint: Watchpoint::io_register(0xFF07),
+            access: Access::Write(0x05),
+            address: 0xFF07,
+            bank: 0,
+            pc: 0x156,
+            register: Some("TAC"),
+            label: None,
+        })
+    );
+    assert!(debugger.remove_watchpoint(&Watchpoint::io_register(tac)));
+
+    debugger.run_to_next_interrupt();
+    assert_eq!(
+        debugger.run(&mut gameboy, 10_000),
+        Some(&PauseReason::Interrupt(Interrupt::Timer))
+    );
+    assert_eq!(debugger.instruction_address(), Some(0x50));
+}
+
+#[test]
+fn debugger_breakpoint_preempted_by_interrupt() {
+    use super::debug::{Breakpoint, Debugger, PauseReason};
+
+    let program = [
+        0xAF, // 0x150: XOR A
+        0xE0, 0x0F, // 0x151: LDH (IF), A
+        0x3E, 0x01, // 0x153: LD A, 0x01
+        0xE0, 0xFF, // 0x155: LDH (IE), A
+        0xFB, // 0x157: EI
+        0x76, // 0x158: HALT
+        0x04, // 0x159: INC B
+        0x18, 0xFC, // 0x15A: JR -4
+    ];
+    let mut rom = build_test_rom(&program);
+    rom[0x40] = 0xD9; // 0x40: RETI
+    let mut gameboy = GameboyBuilder::new_without_boot_rom(cartridge_from_test_rom(rom)).build();
+    let mut debugger = Debugger::new();
+
+    // Waking up from HALT, the fetch of INC B is dropped to dispatch the VBlank interrupt, and done again by RETI
+    debugger.add_breakpoint(Breakpoint::new(0x159));
+    for expected_b in 0..3 {
+        assert_eq!(
+            debugger.run(&mut gameboy, 2 * T_CYCLES_PER_FRAME),
+            Some(&PauseReason::Breakpoint(Breakpoint::new(0x159)))
+        );
+        assert_eq!(gameboy.cpu_state().b, expected_b);
+        assert!(!gameboy.io_registers().interrupt_flags.vblank);
+        debugger.resume();
+    }
+
+    debugger.step_into();
+    assert_eq!(debugger.run(&mut gameboy, 100), Some(&PauseReason::Step));
+    assert_eq!(debugger.instruction_address(), Some(0x15A));
+}
+
+/// Symbols of `DEBUGGER_PROGRAM`, as written by rgblink in a `.sym` and a `.map` file
+pub(super) const DEBUGGER_PROGRAM_SYM: &str = "; File generated by rgblink
+00:0150 Main
+00:015b Main.loop
+00:0160 IncrementA
+00:c000 wValue
+";
+
+pub(super) const DEBUGGER_PROGRAM_MAP: &str = "SUMMARY:
+	ROM0: 19 bytes used / 16365 free
+
+ROM0 bank #0:
+	SECTION: $0150-$0162 ($0013 bytes) [\"Main\"]
+	         $0150 = Main
+	         $015b = Main.loop
+	         $0160 = IncrementA
+	EMPTY: $0163-$3fff ($3e9d bytes)
+
+WRAM0 bank #0:
+	SECTION: $c000-$c000 ($0001 byte) [\"Variables\"]
+	         $c000 = wValue
+";
+
+#[test]
+fn symbols_in_debugger_and_disassembly() {
+    use super::debug::disassembler::RomDisassembly;
+    use super::debug::symbols::SymbolTable;
+    use super::debug::{Access, Breakpoint, Debugger, PauseReason, Watchpoint};
+
+    let symbols = SymbolTable::from_sym(DEBUGGER_PROGRAM_SYM).unwrap();
+    let cartridge = build_test_cartridge(&DEBUGGER_PROGRAM);
+
+    let listing = RomDisassembly::new(cartridge.rom())
+        .set_symbols(&symbols)
+        .to_string();
+    assert!(listing.starts_with("DEF wValue EQU $C000\n\nSECTION"));
+    assert!(listing.contains("    jp Main\n"));
+    assert!(listing.contains("Main:\n    ld a, $12\n    ld [wValue], a\n    call IncrementA\n"));
+    assert!(listing.contains("Main.loop:\n    jr Main.loop\n"));
+
+    let mut gameboy = GameboyBuilder::new_without_boot_rom(cartridge).build();
+    assert_eq!(symbols.label_at(&gameboy, 0x160), Some("IncrementA"));
+
+    let (bank, range) = symbols.range("wValue").unwrap();
+    let watchpoint = Watchpoint::write(range).set_bank(bank);
+    let breakpoint = Breakpoint::at_symbol(symbols.symbol("IncrementA").unwrap());
+
+    let mut debugger = Debugger::new();
+    debugger.set_symbols(symbols);
+    debugger.add_watchpoint(watchpoint.clone());
+    debugger.add_breakpoint(breakpoint);
+
+    assert_eq!(
+        debugger.run(&mut gameboy, 10_000),
+        Some(&PauseReason::Watchpoint {
+            watchpoint,
+            access: Access::Write(0x12),
+            address: 0xC000,
+            bank: 0,
+            pc: 0x152,
+            register: None,
+            label: Some("wValue".to_string()),
+        })
+    );
+
+    debugger.resume();
+    assert_eq!(
+        debugger.run(&mut gameboy, 10_000),
+        Some(&PauseReason::Breakpoint(breakpoint))
+    );
+    assert_eq!(debugger.instruction_address(), Some(0x160));
+}
+
+#[test]
+fn tracer() {
+    use super::debug::symbols::SymbolTable;
+    use super::debug::trace::{ChannelSink, Format, RingBuffer, Tracer};
+
+    let mut gameboy =
+        GameboyBuilder::new_without_boot_rom(build_test_cartridge(&DEBUGGER_PROGRAM)).build();
+    gameboy.start_trace(Tracer::new(Box::new(RingBuffer::new(4))));
+
+    while gameboy.tracer().unwrap().line_count() < 10 {
+        gameboy.tick();
+    }
+
+    let tracer = gameboy.stop_trace().unwrap();
+    let lines: Vec<&str> = tracer.sink::<RingBuffer>().unwrap().lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "A:12 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFC PC:0161 PCMEM:3C,C9,00,00",
+            "A:13 F:10 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFC PC:0162 PCMEM:C9,00,00,00",
+            "A:13 F:10 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0158 PCMEM:FA,00,C0,18",
+            "A:12 F:10 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:015B PCMEM:18,FE,00,00",
+        ]
+    );
+
+    let mut gameboy =
+        GameboyBuilder::new_without_boot_rom(build_test_cartridge(&DEBUGGER_PROGRAM)).build();
+    let (sink, receiver) = ChannelSink::channel();
+    let tracer = Tracer::new(Box::new(sink))
+        .set_format(Format::Annotated)
+        .set_symbols(SymbolTable::from_sym(DEBUGGER_PROGRAM_SYM).unwrap());
+    gameboy.start_trace(tracer);
+
+    while gameboy.tracer().unwrap().line_count() < 4 {
+        gameboy.tick();
+    }
+
+    let lines: Vec<String> = receiver.try_iter().collect();
+    assert_eq!(
+        lines[0],
+        "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,50,01 ; 000:0100 nop"
+    );
+    assert!(lines[2].ends_with("PC:0150 PCMEM:3E,12,EA,00 ; 000:Main ld a, $12"));
+    assert!(lines[3].ends_with("PC:0152 PCMEM:EA,00,C0,CD ; 000:Main+2 ld [$C000], a"));
+
+    // The custom formats get the cycle at which the instructions are decoded
+    let tracer =
+        Tracer::new(Box::new(RingBuffer::new(2))).set_format(Format::Custom(Box::new(|entry| {
+            format!("{:04X} {}", entry.pc, entry.t_cycle)
+        })));
+    gameboy.start_trace(tracer);
+
+    while gameboy.tracer().unwrap().line_count() < 2 {
+        gameboy.tick();
+    }
+
+    let tracer = gameboy.tracer().unwrap();
+    let lines: Vec<&str> = tracer.sink::<RingBuffer>().unwrap().lines().collect();
+    assert_eq!(lines, ["0155 48", "0160 72"]);
+}
+
+#[test]
+fn trace_divergence() {
+    use super::debug::diff::{
+        find_divergence, Difference, GameboySource, Source, Step, TraceReader,
+    };
+
+    let gameboy =
+        GameboyBuilder::new_without_boot_rom(build_test_cartridge(&DEBUGGER_PROGRAM)).build();
+    let mut source = GameboySource::new(gameboy);
+    let steps: Vec<Step> = (0..12)
+        .map(|_| source.next_step().unwrap().unwrap())
+        .collect();
+
+    let cycles: Vec<usize> = steps.iter().map(|step| step.cycles.unwrap()).collect();
+    assert_eq!(cycles, [4, 16, 8, 16, 24, 4, 4, 16, 16, 12, 12, 12]);
+    assert_eq!(steps[3].writes, Some(vec![(0xC000, 0x12)]));
+    assert_eq!(steps[4].writes, Some(vec![(0xFFFD, 0x01), (0xFFFC, 0x58)]));
+
+    let lines: Vec<String> = steps.iter().map(Step::to_string).collect();
+    assert_eq!(
+        lines[3],
+        "A:12 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0152 PCMEM:EA,00,C0,CD CY:16 W:C000=12"
+    );
+    assert_eq!(Step::parse(&lines[3]).unwrap().writes, steps[3].writes);
+
+    // Reference trace where INC A sets the carry flag
+    let mut reference = lines.clone();
+    reference[7] = reference[7].replace("F:10", "F:30");
+    let reference = reference.join("\n");
+
+    let gameboy =
+        GameboyBuilder::new_without_boot_rom(build_test_cartridge(&DEBUGGER_PROGRAM)).build();
+    let divergence = find_divergence(
+        &mut GameboySource::new(gameboy),
+        &mut TraceReader::new(reference.as_bytes()),
+        100,
+        2,
+    )
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(divergence.index, 7);
+    assert_eq!(
+        divergence.differences,
+        [Difference::Flags {
+            left: 0x10,
+            right: 0x30
+        }]
+    );
+    assert_eq!(divergence.before.len(), 2);
+    assert_eq!(divergence.left.len(), 3);
+    assert!(divergence
+        .to_string()
+        .starts_with("Divergence at instruction 7:\n    F: 10 (---C) != 30 (--HC)\n"));
+
+    // A Gameboy Doctor log has neither the cycles nor the writes
+    let doctor: Vec<String> = steps.iter().map(|step| step.entry.to_string()).collect();
+    let gameboy =
+        GameboyBuilder::new_without_boot_rom(build_test_cartridge(&DEBUGGER_PROGRAM)).build();
+    let divergence = find_divergence(
+        &mut GameboySource::new(gameboy),
+        &mut TraceReader::new(doctor.join("\n").as_bytes()),
+        100,
+        2,
+    )
+    .unwrap();
+    assert_eq!(divergence, None);
+}
+
+#[test]
+fn trace_divergence_doctor_mode() {
+    use super::debug::diff::{find_divergence, GameboySource, TraceReader};
+
+    let program = [
+        0xF0, 0x44, // 0x150: LDH A, (LY)
+        0xFE, 0x90, // 0x152: CP 0x90
+        0x20, 0xFA, // 0x154: JR NZ, -6
+        0x18, 0xFE, // 0x156: JR -2
+    ];
+    // Gameboy Doctor log, recorded with LY always read as 0x90
+    let doctor = [
+        "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,50,01",
+        "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0101 PCMEM:C3,50,01,CE",
+        "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0150 PCMEM:F0,44,FE,90",
+        "A:90 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0152 PCMEM:FE,90,20,FA",
+        "A:90 F:C0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0154 PCMEM:20,FA,18,FE",
+        "A:90 F:C0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0156 PCMEM:18,FE,00,00",
+    ]
+    .join("\n");
+
+    let gameboy = GameboyBuilder::new_without_boot_rom(build_test_cartridge(&program)).build();
+    let divergence = find_divergence(
+        &mut GameboySource::new(gameboy),
+        &mut TraceReader::new(doctor.as_bytes()),
+        100,
+        2,
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(divergence.index, 3);
+
+    let gameboy = GameboyBuilder::new_without_boot_rom(build_test_cartridge(&program)).build();
+    let mut source = GameboySource::new(gameboy).set_doctor_mode(true);
+    let divergence = find_divergence(
+        &mut source,
+        &mut TraceReader::new(doctor.as_bytes()),
+        100,
+        2,
+    )
+    .unwrap();
+    assert_eq!(divergence, None);
+
+    let gameboy = source.into_gameboy();
+    assert_ne!(gameboy.io_registers().ly, 0x90);
+}
+
+#[test]
+fn profiler() {
+    use super::debug::profiler::{Budget, Overrun, Profiler};
+    use super::debug::symbols::SymbolTable;
+
+    let program = [
+        0x3E, 0x01, // 0x150: LD A, 0x01
+        0xE0, 0xFF, // 0x152: LDH (IE), A
+        0xFB, // 0x154: EI
+        0x76, // 0x155: HALT
+        0x18, 0xFD, // 0x156: JR -3
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x158: Padding
+        0x06, 0x10, // 0x160: LD B, 0x10
+        0x05, // 0x162: DEC B
+        0x20, 0xFD, // 0x163: JR NZ, -3
+        0xC9, // 0x165: RET
+    ];
+    let mut rom = build_test_rom(&program);
+    rom[0x40..0x44].copy_from_slice(&[
+        0xCD, 0x60, 0x01, // 0x40: CALL 0x0160
+        0xD9, // 0x43: RETI
+    ]);
+    let mut gameboy = GameboyBuilder::new_without_boot_rom(cartridge_from_test_rom(rom)).build();
+
+    let mut profiler = Profiler::new();
+    profiler.add_budget(Budget::vblank());
+    profiler.add_budget(Budget::new(0, 0x160, 50));
+    for _ in 0..3 * T_CYCLES_PER_FRAME {
+        gameboy.tick();
+        profiler.observe(&gameboy);
+    }
+
+    // The VBlank interrupt requested when the boot ROM ends is dispatched right after EI
+    let interrupts = 4;
+    // Loop of 16 iterations, the last JR NZ not being taken
+    let delay = 2 + 16 + 15 * 3 + 2 + 4;
+    assert_eq!(profiler.location(0, 0x162).executions, 16 * interrupts);
+    assert_eq!(profiler.location(0, 0x162).m_cycles, 16 * interrupts);
+    assert_eq!(
+        profiler.location(0, 0x163).m_cycles,
+        (15 * 3 + 2) * interrupts
+    );
+    assert_eq!(profiler.location(0, 0x156).executions, interrupts - 1);
+
+    assert_eq!(profiler.frames().len(), 3);
+    for frame in &profiler.frames()[1..] {
+        assert_eq!(frame.m_cycles, T_CYCLES_PER_FRAME / 4);
+        assert_eq!(frame.budget_m_cycles, [10 + delay, delay]);
+        // Dispatch, handler, HALT and the opcode following it fetched as the CPU halts, JR
+        assert_eq!(
+            frame.halted_m_cycles,
+            frame.m_cycles - 5 - 10 - delay - 2 - 3
+        );
+    }
+    assert_eq!(
+        profiler.overruns().nth(1),
+        Some(Overrun {
+            frame: 1,
+            routine: Budget::new(0, 0x160, 50).routine,
+            m_cycles: delay,
+            budget: 50,
+        })
+    );
+
+    let mut symbols = SymbolTable::new();
+    symbols.add_symbol(0, 0x40, "VBlank");
+    symbols.add_symbol(0, 0x100, "EntryPoint");
+    symbols.add_symbol(0, 0x150, "Main");
+    symbols.add_symbol(0, 0x160, "Delay");
+    profiler.set_symbols(symbols);
+
+    let folded = profiler.folded_stacks();
+    assert!(folded.contains(&format!("EntryPoint;VBlank;Delay {}\n", delay * interrupts)));
+    assert!(folded.contains(&format!("EntryPoint;VBlank {}\n", 10 * interrupts)));
+    assert!(folded.contains("EntryPoint;[halted] "));
+    assert!(folded.contains("EntryPoint;[interrupt dispatch] "));
+
+    let table = profiler.flat_table();
+    assert!(table.contains(&format!(
+        "000:0163   Delay+3 {:>37}",
+        (15 * 3 + 2) * interrupts
+    )));
+    assert!(table.lines().last().unwrap().starts_with("halted"));
+}
+
+#[test]
+fn code_data_log() {
+    use super::debug::cdl::{CodeDataLog, Coverage, Flags};
+    use super::debug::disassembler::RomDisassembly;
+
+    let program = [
+        0xFA, 0x00, 0x02, // 0x150: LD A, (0x0200)
+        0x3E, 0x03, // 0x153: LD A, 0x03
+        0xE0, 0x46, // 0x155: LDH (DMA), A
+        0x21, 0x5B, 0x01, // 0x157: LD HL, 0x015B
+        0xE9, // 0x15A: JP HL
+        0x18, 0xFE, // 0x15B: JR -2
+    ];
+    let cartridge = build_test_cartridge(&program);
+    let rom = cartridge.rom().to_vec();
+    let mut gameboy = GameboyBuilder::new_without_boot_rom(cartridge).build();
+
+    let mut log = CodeDataLog::for_gameboy(&gameboy);
+    for _ in 0..1_000 {
+        gameboy.tick();
+        log.observe(&gameboy);
+    }
+
+    let code = Flags::CODE | Flags::OPCODE;
+    assert_eq!(log.flags_at(0, 0x100), code | Flags::JUMP_TARGET);
+    assert_eq!(log.flags_at(0, 0x150), code | Flags::JUMP_TARGET);
+    assert_eq!(log.flags_at(0, 0x151), Flags::CODE);
+    assert_eq!(log.flags_at(0, 0x153), code);
+    assert_eq!(log.flags_at(0, 0x15B), code | Flags::JUMP_TARGET);
+    assert_eq!(log.flags_at(0, 0x15D), Flags::NONE);
+    assert_eq!(log.flags_at(0, 0x200), Flags::DATA);
+    assert_eq!(log.flags_at(0, 0x300), Flags::DMA);
+    assert_eq!(log.flags_at(0, 0x39F), Flags::DMA);
+    assert_eq!(log.flags_at(0, 0x3A0), Flags::NONE);
+    assert_eq!(
+        log.coverage(),
+        Coverage {
+            size: 0x8000,
+            accessed: 4 + 13 + 1 + 0xA0,
+            code: 4 + 13,
+            data: 1,
+            dma: 0xA0,
+        }
+    );
+    assert_eq!(log.bank_coverage(1).accessed, 0);
+
+    // The indirect jump can only be followed with the log
+    let static_disassembly = RomDisassembly::new(&rom);
+    assert!(!static_disassembly
+        .instructions(0)
+        .any(|instruction| instruction.address() == 0x15B));
+    let logged_disassembly = RomDisassembly::from_code_data_log(&rom, &log).to_string();
+    assert!(logged_disassembly.contains("label_000_015B:\n    jr label_000_015B\n"));
+
+    let path = std::env::temp_dir().join(format!("ugbe-test-{}.cdl", std::process::id()));
+    log.save(&path).unwrap();
+    let mut loaded = CodeDataLog::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.to_bytes(), log.to_bytes());
+
+    let mut gameboy =
+        GameboyBuilder::new_without_boot_rom(build_test_cartridge(&DEBUGGER_PROGRAM)).build();
+    let mut other = CodeDataLog::for_gameboy(&gameboy);
+    for _ in 0..1_000 {
+        gameboy.tick();
+        other.observe(&gameboy);
+    }
+    assert_eq!(other.flags_at(0, 0x160), code | Flags::JUMP_TARGET);
+
+    loaded.merge(&other).unwrap();
+    assert_eq!(loaded.flags_at(0, 0x160), code | Flags::JUMP_TARGET);
+    assert_eq!(loaded.flags_at(0, 0x200), Flags::DATA);
+    assert!(loaded.merge(&CodeDataLog::new(0x4000)).is_err());
+}
+
+#[test]
+fn debug_tools_in_one_tick_loop() {
+    use super::debug::cdl::{CodeDataLog, Flags};
+    use super::debug::profiler::{Location, Profiler};
+    use super::debug::{Breakpoint, Debugger, PauseReason};
+
+    let mut gameboy =
+        GameboyBuilder::new_without_boot_rom(build_test_cartridge(&DEBUGGER_PROGRAM)).build();
+    let mut debugger = Debugger::new();
+    let mut profiler = Profiler::new();
+    let mut log = CodeDataLog::for_gameboy(&gameboy);
+
+    let mut run = |gameboy: &mut Gameboy, debugger: &mut Debugger, cycles: usize| {
+        for _ in 0..cycles {
+            if debugger.is_paused() {
+                break;
+            }
+
+            gameboy.tick();
+            debugger.observe(gameboy);
+            profiler.observe(gameboy);
+            log.observe(gameboy);
+        }
+    };
+
+    debugger.add_breakpoint(Breakpoint::new(0x160));
+    run(&mut gameboy, &mut debugger, 10_000);
+    assert_eq!(
+        debugger.pause_reason(),
+        Some(&PauseReason::Breakpoint(Breakpoint::new(0x160)))
+    );
+
+    debugger.resume();
+    run(&mut gameboy, &mut debugger, 1_000);
+    assert!(!debugger.is_paused());
+
+    let code = Flags::CODE | Flags::OPCODE;
+    assert_eq!(log.flags_at(0, 0x155), code);
+    assert_eq!(log.flags_at(0, 0x160), code | Flags::JUMP_TARGET);
+    assert_eq!(log.flags_at(0, 0xC000), Flags::NONE);
+    assert_eq!(profiler.location(0, 0x155).executions, 1);
+    assert_eq!(profiler.location(0, 0x161).executions, 1);
+    assert!(profiler.location(0, 0x15B).executions > 1);
+    assert_eq!(
+        profiler.call_stack().collect::<Vec<_>>(),
+        vec![Location::new(0, 0x100)]
+    );
+}
+
+#[test]
+fn memory_inspector() {
+    use super::debug::memory::{Error, Region};
+
+    let mut gameboy =
+        GameboyBuilder::new_without_boot_rom(build_test_cartridge(&DEBUGGER_PROGRAM)).build();
+    let div = gameboy.memory().peek(0xFF04);
+
+    let mut memory = gameboy.memory_mut();
+    assert_eq!(memory.locate(0x4123), Some((Region::Rom, 1, 0x123)));
+    assert_eq!(memory.locate(0xD010), Some((Region::WorkRam, 1, 0x10)));
+    assert_eq!(memory.locate(0xFF40), None);
+    assert_eq!(memory.bank_count(Region::Rom), 2);
+    assert_eq!(memory.bank_count(Region::CartridgeRam), 0);
+
+    // Writing to the ROM patches it instead of selecting a bank
+    memory.poke(0x2000, 0x42).unwrap();
+    assert_eq!(memory.peek(0x2000), 0x42);
+    assert_eq!(memory.peek_region(Region::Rom, 0, 0x2000), Ok(0x42));
+    memory.poke_region(Region::Rom, 1, 0x10, 0x24).unwrap();
+    assert_eq!(memory.peek(0x4010), 0x24);
+
+    memory.poke(0xE001, 0x11).unwrap();
+    memory.poke_region(Region::WorkRam, 1, 0xFFF, 0x22).unwrap();
+    assert_eq!(memory.peek(0xC001), 0x11);
+    assert_eq!(memory.peek(0xDFFF), 0x22);
+    assert_eq!(memory.locate(0xFDFF), Some((Region::WorkRam, 1, 0xDFF)));
+    memory.poke(0xF002, 0x44).unwrap();
+    assert_eq!(memory.peek(0xD002), 0x44);
+    memory.poke(0xFF80, 0x33).unwrap();
+    assert_eq!(memory.dump(Region::HighRam, 0).unwrap()[0], 0x33);
+    memory.poke(0xFFFF, 0x1F).unwrap();
+    assert_eq!(memory.peek(0xFFFF), 0xFF);
+
+    assert_eq!(memory.poke(0xFF04, 0x00), Err(Error::IoRegister(0xFF04)));
+    assert_eq!(memory.poke(0xFEA0, 0x00), Err(Error::Unmapped(0xFEA0)));
+    assert_eq!(memory.poke(0xA000, 0x00), Err(Error::Unmapped(0xA000)));
+    assert_eq!(
+        memory.peek_region(Region::WorkRam, 2, 0),
+        Err(Error::OutOfBounds {
+            region: Region::WorkRam,
+            bank: 2,
+            offset: 0
+        })
+    );
+    assert_eq!(memory.dump_range(0xC000..=0xC001), [0x00, 0x11]);
+
+    // Peeking the I/O registers has no effect on them
+    assert_eq!(memory.peek(0xFF04), div);
+    assert_eq!(gameboy.memory().peek(0xFF04), div);
+
+    // At the end of the OAM scan, the DMG locks the VRAM reads but unlocks the OAM writes
+    while !(gameboy.ppu.vram_locked() && gameboy.ppu.oam_locked())
+        || gameboy.ppu.vram_write_locked()
+    {
+        gameboy.tick();
+    }
+
+    let mut memory = gameboy.memory_mut();
+    assert_eq!(memory.peek(0x8001), 0xFF);
+    memory.poke(0x8001, 0x77).unwrap();
+    memory.poke(0xFE01, 0x88).unwrap();
+    assert_eq!(memory.dump(Region::Oam, 0), Err(Error::Locked(Region::Oam)));
+    assert_eq!(gameboy.ppu.vram()[1], 0x77);
+    assert_eq!(gameboy.ppu.oam().read_byte(1), 0x88);
+
+    while !gameboy.ppu.vram_write_locked() {
+        gameboy.tick();
+    }
+
+    let mut memory = gameboy.memory_mut();
+    assert_eq!(memory.peek(0x8000), 0xFF);
+    assert_eq!(
+        memory.poke(0x8000, 0x55),
+        Err(Error::Locked(Region::VideoRam))
+    );
+    assert_eq!(memory.dump(Region::Oam, 0), Err(Error::Locked(Region::Oam)));
+
+    let mut memory = memory.set_ppu_locking(false);
+    memory.poke(0x8000, 0x55).unwrap();
+    memory.poke(0xFE00, 0x66).unwrap();
+    assert_eq!(memory.peek(0x8000), 0x55);
+    assert_eq!(memory.dump(Region::Oam, 0).unwrap()[0], 0x66);
+    assert_eq!(gameboy.ppu.vram()[0], 0x55);
+}
+
+#[test]
+fn cpu_state_and_io_registers() {
+    use super::debug::cpu::{CpuState, Error, Register};
+    use super::debug::io::Palette;
+
+    let mut program = vec![0x00; 0x20];
+    program[..3].copy_from_slice(&[
+        0x04, // 0x150: INC B
+        0x18, 0xFD, // 0x151: JR -3
+    ]);
+    program[0x10..0x15].copy_from_slice(&[
+        0x0E, 0x42, // 0x160: LD C, 0x42
+        0x76, // 0x162: HALT
+        0x18, 0xFE, // 0x163: JR -2
+    ]);
+    let mut gameboy = GameboyBuilder::new_without_boot_rom(build_test_cartridge(&program)).build();
+
+    let state = gameboy.cpu_state();
+    assert_eq!(
+        (state.a, state.f, state.sp, state.pc),
+        (0x01, 0xB0, 0xFFFE, 0x100)
+    );
+    assert!(state.zero_flag() && !state.subtract_flag() && state.carry_flag());
+    assert_eq!(state.register(Register::AF), 0x01B0);
+    assert_eq!(
+        state.to_string(),
+        "AF:01B0 BC:0013 DE:00D8 HL:014D SP:FFFE PC:0100 ZNHC:1011 IME:0"
+    );
+
+    let run_until = |gameboy: &mut Gameboy, condition: fn(&CpuState) -> bool| {
+        for _ in 0..10_000 {
+            // The state of the CPU only changes once per M-cycle
+            for _ in 0..4 {
+                gameboy.tick();
+            }
+            if condition(&gameboy.cpu_state()) {
+                return;
+            }
+        }
+        panic!("condition never met");
+    };
+
+    run_until(&mut gameboy, |state| {
+        state.at_instruction_boundary && state.pc == 0x151
+    });
+    assert_eq!(gameboy.cpu_state().b, 0x01);
+    gameboy.set_register(Register::BC, 0x1234);
+    gameboy.set_register("f".parse().unwrap(), 0x00FF);
+    assert_eq!(gameboy.cpu_state().register(Register::BC), 0x1234);
+    assert_eq!(gameboy.cpu_state().f, 0xF0);
+    assert_eq!(
+        "IX".parse::<Register>(),
+        Err(Error::UnknownRegister("IX".to_string()))
+    );
+
+    run_until(&mut gameboy, |state| {
+        state.at_instruction_boundary && state.pc == 0x151
+    });
+    assert_eq!(gameboy.cpu_state().b, 0x13);
+
+    // Jumping restarts the fetch of the next instruction
+    gameboy.set_pc(0x160);
+    run_until(&mut gameboy, |state| state.halted);
+    let state = gameboy.cpu_state();
+    assert_eq!((state.c, state.pc), (0x42, 0x163));
+
+    gameboy.set_halted(false);
+    gameboy.set_ime(true);
+    run_until(&mut gameboy, |state| state.at_instruction_boundary);
+    let state = gameboy.cpu_state();
+    assert!(!state.halted && state.ime);
+    assert_eq!(state.pc, 0x163);
+
+    let mut restored = state;
+    restored.a = 0x99;
+    restored.ime = false;
+    gameboy.set_cpu_state(&restored);
+    assert_eq!(gameboy.cpu_state().a, 0x99);
+    assert!(!gameboy.cpu_state().ime);
+
+    let io = gameboy.io_registers();
+    assert!(io.lcdc.lcd_enabled && io.lcdc.tile_data_8000 && io.lcdc.bg_window_enabled);
+    assert!(!io.lcdc.window_enabled);
+    assert_eq!(io.bgp, Palette([0, 3, 3, 3]));
+    assert!(!io.tac.enabled);
+    assert!(io.nr52.enabled);
+    assert_eq!(io.nr50.left_volume, 7);
+    assert!(!io.boot_rom_enabled);
+    assert_eq!(io.ly, gameboy.memory().peek(0xFF44));
+
+    gameboy.write_io_register(0xFF07, 0x05).unwrap();
+    let tac = gameboy.io_registers().tac;
+    assert!(tac.enabled);
+    assert_eq!(tac.period(), 16);
+    assert!(gameboy.write_io_register(0xC000, 0x00).is_err());
+
+    // The periods and the length timers can't be read back by the CPU
+    gameboy.write_io_register(0xFF11, 0x3F).unwrap();
+    gameboy.write_io_register(0xFF13, 0x34).unwrap();
+    gameboy.write_io_register(0xFF14, 0x05).unwrap();
+    gameboy.write_io_register(0xFF1B, 0x00).unwrap();
+    gameboy.write_io_register(0xFF1D, 0xCD).unwrap();
+    gameboy.write_io_register(0xFF1E, 0x03).unwrap();
+    let io = gameboy.io_registers();
+    assert_eq!((io.channel1.period, io.channel1.length_timer), (0x534, 1));
+    assert_eq!((io.channel3.period, io.channel3.length_timer), (0x3CD, 256));
+}
+
+#[test]
+fn video_viewer() {
+    use super::debug::video::{Error, TileMap};
+    use super::screen::{Color, ColorPalette};
+
+    let mut gameboy =
+        GameboyBuilder::new_without_boot_rom(build_test_cartridge(&DEBUGGER_PROGRAM)).build();
+
+    // Tile 1: a row of each color, then a single pixel of color 1 on the left
+    let mut memory = gameboy.memory_mut().set_ppu_locking(false);
+    for (offset, value) in [0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0x80, 0x00]
+        .into_iter()
+        .enumerate()
+    {
+        memory.poke(0x8010 + offset as u16, value).unwrap();
+    }
+    memory.poke(0x9801, 0x01).unwrap();
+    memory.poke(0x9C00, 0x01).unwrap();
+    // Sprite 0 at the top left of the screen, flipped horizontally and using OBP1
+    for (offset, value) in [16, 8, 0x01, 0x30].into_iter().enumerate() {
+        memory.poke(0xFE00 + offset as u16, value).unwrap();
+    }
+    gameboy.write_io_register(0xFF49, 0xE4).unwrap();
+
+    let shades = ColorPalette::new_grayscale();
+    let shade = |shade: u8| shades.shade(shade);
+    let video = gameboy
+        .video()
+        .set_color_palette(ColorPalette::new_grayscale());
+
+    let tiles = video.tiles(0).unwrap();
+    assert_eq!((tiles.width(), tiles.height()), (128, 192));
+    assert_eq!(tiles.pixel(0, 0), shade(0));
+    assert_eq!(tiles.pixel(8, 0), shade(1));
+    assert_eq!(tiles.pixel(15, 1), shade(2));
+    assert_eq!(tiles.pixel(8, 2), shade(3));
+    assert_eq!(tiles.pixel(8, 3), shade(1));
+    assert_eq!(tiles.pixel(9, 3), shade(0));
+    assert_eq!(video.tiles(1), Err(Error::Bank(1)));
+
+    let cgb = GameboyBuilder::new_without_boot_rom(build_test_cartridge(&DEBUGGER_PROGRAM))
+        .set_model(Model::CGB)
+        .build();
+    assert_eq!(cgb.video().vram_bank_count(), 2);
+    assert!(cgb.video().tiles(0).is_ok());
+    assert_eq!(cgb.video().tiles(1), Err(Error::UnemulatedBank(1)));
+    assert_eq!(cgb.video().tiles(2), Err(Error::Bank(2)));
+
+    // BGP is 0xFC after the boot ROM, mapping the colors 1 to 3 to black
+    let map = video.tile_map(TileMap::Map9800);
+    assert_eq!((map.width(), map.height()), (256, 256));
+    assert_eq!(map.pixel(0, 0), shade(0));
+    assert_eq!(map.pixel(8, 0), shade(3));
+
+    let sprites = video.sprites();
+    assert_eq!(sprites.len(), 40);
+    let sprite = sprites[0];
+    assert_eq!((sprite.x, sprite.y, sprite.tile), (8, 16, 0x01));
+    assert!(sprite.obp1 && sprite.x_flip && !sprite.y_flip && !sprite.behind_bg);
+    assert!(sprite.is_on_screen());
+    assert!(!sprites[1].is_on_screen());
+    assert_eq!(video.sprite(40), Err(Error::Sprite(40)));
+
+    let sprite_image = video.sprite_image(&sprite);
+    assert_eq!((sprite_image.width(), sprite_image.height()), (8, 8));
+    assert_eq!(sprite_image.pixel(7, 3), shade(1));
+    assert_eq!(sprite_image.pixel(0, 3), shade(0));
+    let sheet = video.sprites_image();
+    assert_eq!((sheet.width(), sheet.height()), (64, 40));
+    assert_eq!(sheet.pixel(7, 3), shade(1));
+
+    // With the tile data at 0x8800, the tile 1 of the map is at 0x9010
+    gameboy.write_io_register(0xFF40, 0x81).unwrap();
+    let map = gameboy
+        .video()
+        .set_color_palette(ColorPalette::new_grayscale())
+        .tile_map(TileMap::Map9800);
+    assert_eq!(map.pixel(8, 0), shade(0));
+
+    // The viewport wraps around the map, the window uses the other map
+    let (red, blue) = (Color::new(31, 0, 0), Color::new(0, 0, 31));
+    gameboy.write_io_register(0xFF40, 0xF1).unwrap();
+    gameboy.write_io_register(0xFF43, 250).unwrap();
+    gameboy.write_io_register(0xFF4A, 44).unwrap();
+    gameboy.write_io_register(0xFF4B, 107).unwrap();
+    let video = gameboy.video().set_overlay_colors(red, blue);
+
+    let background = video.tile_map_with_overlay(TileMap::Map9800);
+    assert_eq!(background.pixel(250, 0), red);
+    assert_eq!(background.pixel(153, 143), red);
+    assert_eq!(background.pixel(5, 143), red);
+    assert_ne!(background.pixel(100, 50), red);
+    assert!(!background.pixels().contains(&blue));
+
+    let window = video.tile_map_with_overlay(TileMap::Map9C00);
+    assert_eq!(window.pixel(0, 0), blue);
+    assert_eq!(window.pixel(59, 99), blue);
+    assert_ne!(window.pixel(60, 0), blue);
+    assert!(!window.pixels().contains(&red));
+}
+
+/// Run a Mooneye test ROM until it reports its result through the registers
+fn run_mooneye(name: &str, model: Option<Model>) {
+    let mut gameboy = load_test_rom(&format!("mooneye/{name}"), model);
+
+    for _ in 0..120 {
+        for _ in 0..T_CYCLES_PER_FRAME {
+            gameboy.tick();
+        }
+
+        let registers = gameboy.cpu.registers();
+        let result = [
+            registers.b(),
+            registers.c(),
+            registers.d(),
+            registers.e(),
+            registers.h(),
+            registers.l(),
+        ];
+
+        match result {
+            [3, 5, 8, 13, 21, 34] => return,
+            [0x42, 0x42, 0x42, 0x42, 0x42, 0x42] => panic!("Mooneye test '{name}' failed"),
+            _ => {}
+        }
+    }
+
+    panic!("Mooneye test '{name}' timed out");
+}
+
+/// Run a Blargg test ROM until it reports its result in the cartridge RAM
+fn run_blargg(name: &str) {
+    let mut gameboy = load_test_rom(&format!("blargg/{name}"), None);
+
+    for _ in 0..3600 {
+        for _ in 0..T_CYCLES_PER_FRAME {
+            gameboy.tick();
+        }
+
+        let signature = [
+            gameboy.cartridge.read_ram(0x1),
+            gameboy.cartridge.read_ram(0x2),
+            gameboy.cartridge.read_ram(0x3),
+        ];
+
+        if signature != [0xDE, 0xB0, 0x61] {
+            continue;
+        }
+
+        match gameboy.cartridge.read_ram(0x0) {
+            0x80 => {}
+            0x00 => return,
+            code => {
+                let output = (0x4..0x2000)
+                    .map(|address| gameboy.cartridge.read_ram(address))
+                    .take_while(|byte| *byte != 0)
+                    .map(char::from)
+                    .collect::<String>();
+
+                panic!("Blargg test '{name}' failed with code {code}:\n{output}");
+            }
+        }
+    }
+
+    panic!("Blargg test '{name}' timed out");
+}
+
+macro_rules! blargg_tests {
+    ($($test:ident => $rom:literal),* $(,)?) => {
+        $(
+            #[test]
+            #[ignore = "needs the Blargg test ROMs in ../roms, run with --ignored"]
+            fn $test() {
+                run_blargg($rom);
+            }
+        )*
+    };
+}
+
+macro_rules! mooneye_tests {
+    ($($test:ident => $rom:literal $(on $model:ident)?),* $(,)?) => {
+        $(
+            #[test]
+            #[ignore = "needs the Mooneye test ROMs in ../roms, run with --ignored"]
+            fn $test() {
+                run_mooneye($rom, None $(.or(Some(Model::$model)))?);
+            }
+        )*
+    };
+}
+
+mooneye_tests! {
+    mooneye_boot_regs_dmg0 => "acceptance/boot_regs-dmg0.gb" on DMG0,
+    mooneye_boot_regs_dmg_abc => "acceptance/boot_regs-dmgABC.gb" on DMG,
+    mooneye_boot_regs_mgb => "acceptance/boot_regs-mgb.gb" on MGB,
+    mooneye_boot_regs_sgb => "acceptance/boot_regs-sgb.gb" on SGB,
+    mooneye_boot_regs_sgb2 => "acceptance/boot_regs-sgb2.gb" on SGB2,
+    mooneye_di_timing => "acceptance/di_timing-GS.gb",
+    mooneye_halt_ime0_ei => "acceptance/halt_ime0_ei.gb",
+    mooneye_halt_ime0_nointr_timing => "acceptance/halt_ime0_nointr_timing.gb",
+    mooneye_halt_ime1_timing => "acceptance/halt_ime1_timing.gb",
+    mooneye_halt_ime1_timing2 => "acceptance/halt_ime1_timing2-GS.gb",
+    mooneye_ie_push => "acceptance/ie_push.gb",
+    mooneye_lcdon_timing => "acceptance/ppu/lcdon_timing-GS.gb",
+    mooneye_lcdon_write_timing => "acceptance/ppu/lcdon_write_timing-GS.gb",
+    mooneye_stat_irq_blocking => "acceptance/ppu/stat_irq_blocking.gb",
+}
+
+blargg_tests! {
+    blargg_oam_bug_lcd_sync => "oam_bug/rom_singles/1-lcd_sync.gb",
+    blargg_oam_bug_causes => "oam_bug/rom_singles/2-causes.gb",
+    blargg_oam_bug_non_causes => "oam_bug/rom_singles/3-non_causes.gb",
+    blargg_oam_bug_scanline_timing => "oam_bug/rom_singles/4-scanline_timing.gb",
+    blargg_oam_bug_timing_bug => "oam_bug/rom_singles/5-timing_bug.gb",
+    blargg_oam_bug_timing_no_bug => "oam_bug/rom_singles/6-timing_no_bug.gb",
+    blargg_oam_bug_timing_effect => "oam_bug/rom_singles/7-timing_effect.gb",
+    blargg_oam_bug_instr_effect => "oam_bug/rom_singles/8-instr_effect.gb",
+    blargg_dmg_sound_wave_read_while_on => "dmg_sound/rom_singles/09-wave read while on.gb",
+    blargg_dmg_sound_wave_trigger_while_on => "dmg_sound/rom_singles/10-wave trigger while on.gb",
+    blargg_dmg_sound_wave_write_while_on => "dmg_sound/rom_singles/12-wave write while on.gb",
+}
@@ -1,3 +1,6 @@
 pub mod bootrom;
 pub mod cartridge;
+mod crc;
 pub mod gameboy;
+
+pub use gameboy::debug;